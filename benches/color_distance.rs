@@ -0,0 +1,81 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// the kernel has no dependencies on the rest of the engine, so it is compiled in directly
+#[path = "../src/color_distance.rs"]
+// clippy builds benchmarks with cfg(test) but without the test harness,
+// which leaves the imports of the modules' unit tests unused
+#[allow(dead_code, unused_imports)]
+mod color_distance;
+
+use color_distance::{
+    neighbor_distances, NeighborBatch, NeighborColors, NeighborDistances, NEIGHBOR_SLOTS,
+};
+
+// frontier sizes of a small, a medium and a huge canvas
+const CANDIDATE_COUNTS: [usize; 3] = [256, 4096, 65536];
+
+// frontier cells with between one and eight random neighbor colors
+fn random_neighborhoods(count: usize, rng: &mut StdRng) -> Vec<NeighborColors> {
+    (0..count)
+        .map(|_| {
+            let mut neighbors: NeighborColors = NeighborColors::new();
+            for _ in 0..rng.gen_range(1..=NEIGHBOR_SLOTS) {
+                neighbors.push([rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>()]);
+            }
+            neighbors
+        })
+        .collect()
+}
+
+fn color_distance_benchmark(criterion: &mut Criterion) {
+    let mut rng: StdRng = StdRng::seed_from_u64(0u64);
+    let target: [u8; 3] = [rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>()];
+    let mut group = criterion.benchmark_group("color_distance");
+
+    for candidate_count in CANDIDATE_COUNTS {
+        let neighborhoods: Vec<NeighborColors> = random_neighborhoods(candidate_count, &mut rng);
+        let mut batch: NeighborBatch = NeighborBatch::with_capacity(candidate_count);
+        for neighbors in neighborhoods.iter() {
+            batch.push(neighbors);
+        }
+
+        // both kernels have to agree before their speed is compared
+        let scalar_distances: Vec<NeighborDistances> = neighborhoods
+            .iter()
+            .map(|neighbors| neighbor_distances(neighbors, target))
+            .collect();
+        let mut simd_distances: Vec<NeighborDistances> = Vec::new();
+        batch.distances(target, &mut simd_distances);
+        assert_eq!(scalar_distances, simd_distances);
+
+        group.throughput(Throughput::Elements(candidate_count as u64));
+        group.bench_with_input(
+            BenchmarkId::new("scalar", candidate_count),
+            &neighborhoods,
+            |bencher, neighborhoods| {
+                let mut distances: Vec<NeighborDistances> = Vec::with_capacity(candidate_count);
+                bencher.iter(|| {
+                    distances.clear();
+                    distances.extend(
+                        neighborhoods
+                            .iter()
+                            .map(|neighbors| neighbor_distances(neighbors, black_box(target))),
+                    );
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("simd", candidate_count),
+            &batch,
+            |bencher, batch| {
+                let mut distances: Vec<NeighborDistances> = Vec::with_capacity(candidate_count);
+                bencher.iter(|| batch.distances(black_box(target), &mut distances))
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, color_distance_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,178 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::Rgb;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+// the engine is a binary, so its sources are compiled as a module of the benchmark
+// and its modules find the items they reach through the crate root via the glob import
+#[path = "../src/shredder.rs"]
+// clippy builds benchmarks with cfg(test) but without the test harness,
+// which leaves the imports of the modules' unit tests unused
+#[allow(dead_code, unused_imports)]
+mod shredder;
+
+use shredder::*;
+
+// canvas edge length and number of seeds, the seeds are spread over the canvas in a grid
+// so the larger cases start with a larger frontier
+const FRONTIER_CASES: [(u32, u32); 3] = [(256u32, 1u32), (1024u32, 16u32), (4096u32, 256u32)];
+// placements grown from every seed before the frontier is measured
+const PLACEMENTS_PER_SEED: u32 = 64u32;
+// canvas edge length and config seed of the complete runs
+const FULL_RUN_CASES: [(u32, u64); 4] =
+    [(64u32, 1u64), (64u32, 2u64), (128u32, 1u64), (256u32, 1u64)];
+
+// a config with the default palette, a fixed seed and seeds in an even grid
+fn config(canvas_size: u32, seed_count: u32, seed: u64) -> String {
+    let seeds_across: u32 = (seed_count as f64).sqrt().ceil() as u32;
+    let spacing: u32 = canvas_size / seeds_across;
+    let mut config: String = format!(
+        "config:\n  seed: \"{}\"\n  canvas:\n    size:\n      x: \"{}\"\n      y: \"{}\"\n    starting_locations:\n",
+        seed, canvas_size, canvas_size
+    );
+    for index in 0..seed_count {
+        config.push_str(&format!(
+            "      - x: \"{}\"\n        y: \"{}\"\n",
+            (index % seeds_across) * spacing + spacing / 2,
+            (index / seeds_across) * spacing + spacing / 2
+        ));
+    }
+    return config;
+}
+
+// a painting grown far enough for its frontier to reach a realistic size
+fn grown_painting(canvas_size: u32, seed_count: u32) -> Painting {
+    let mut painting: Painting = initialize_canvas(&config(canvas_size, seed_count, 0u64), None);
+    for _ in 0..seed_count * PLACEMENTS_PER_SEED {
+        place_next_pixel(&mut painting);
+    }
+    return painting;
+}
+
+fn case_name(canvas_size: u32, painting: &Painting) -> String {
+    format!(
+        "{}x{}/frontier {}",
+        canvas_size,
+        canvas_size,
+        frontier_size(painting)
+    )
+}
+
+fn evaluate_position_benchmark(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("evaluate_position");
+    for (canvas_size, seed_count) in FRONTIER_CASES {
+        let painting: Painting = grown_painting(canvas_size, seed_count);
+        let boundry_region_list: &Vec<Coordinate> = &painting.colonies[0].boundry_region_list;
+        let context = scoring_context(Rgb([96u8, 160u8, 32u8]), 0.5f32, 0u64, &painting);
+
+        // every boundry cell is scored once per iteration, throughput is per cell
+        group.throughput(Throughput::Elements(boundry_region_list.len() as u64));
+        group.bench_function(case_name(canvas_size, &painting), |bencher| {
+            bencher.iter(|| {
+                for (index, position) in boundry_region_list.iter().enumerate() {
+                    black_box(evaluate_position(
+                        position,
+                        index,
+                        &context,
+                        &painting.scoring_pipeline,
+                    ));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn get_best_position_for_color_benchmark(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("get_best_position_for_color");
+    group.sample_size(20);
+    for (canvas_size, seed_count) in FRONTIER_CASES {
+        let mut painting: Painting = grown_painting(canvas_size, seed_count);
+        let mut color_rng: StdRng = StdRng::seed_from_u64(0u64);
+
+        // only the search is timed, the chosen pixel is then placed
+        // so the frontier keeps its size over the iterations
+        group.bench_function(case_name(canvas_size, &painting), |bencher| {
+            bencher.iter_custom(|iterations| {
+                let mut elapsed: Duration = Duration::ZERO;
+                for _ in 0..iterations {
+                    if frontier_size(&painting) == 0 {
+                        painting = grown_painting(canvas_size, seed_count);
+                    }
+                    let target_color: Rgb<u8> = Rgb(color_rng.gen::<[u8; 3]>());
+                    let search_start_time: Instant = Instant::now();
+                    let (pixel, score) =
+                        get_best_position_for_color(target_color, 0.5f32, 0usize, &mut painting);
+                    elapsed += search_start_time.elapsed();
+                    place_pixel(&pixel, 0usize, Some(score), &mut painting);
+                }
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+fn place_pixel_benchmark(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("place_pixel");
+    for (canvas_size, seed_count) in FRONTIER_CASES {
+        let mut painting: Painting = grown_painting(canvas_size, seed_count);
+        let mut color_rng: StdRng = StdRng::seed_from_u64(0u64);
+
+        // the newest boundry cell is painted, without any search
+        group.bench_function(case_name(canvas_size, &painting), |bencher| {
+            bencher.iter_custom(|iterations| {
+                let mut elapsed: Duration = Duration::ZERO;
+                for _ in 0..iterations {
+                    if frontier_size(&painting) == 0 {
+                        painting = grown_painting(canvas_size, seed_count);
+                    }
+                    let last_index: usize = painting.colonies[0].boundry_region_list.len() - 1;
+                    let pixel: Pixel = Pixel {
+                        position: take_boundry_position(0usize, last_index, &mut painting),
+                        color: Rgb(color_rng.gen::<[u8; 3]>()),
+                    };
+                    let placement_start_time: Instant = Instant::now();
+                    place_pixel(&pixel, 0usize, Some(0f32), &mut painting);
+                    elapsed += placement_start_time.elapsed();
+                }
+                elapsed
+            })
+        });
+    }
+    group.finish();
+}
+
+fn full_run_benchmark(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("full_run");
+    group.sample_size(10);
+    for (canvas_size, seed) in FULL_RUN_CASES {
+        let config: String = config(canvas_size, 1u32, seed);
+
+        // throughput is per painted cell
+        group.throughput(Throughput::Elements(
+            canvas_size as u64 * canvas_size as u64,
+        ));
+        group.bench_with_input(
+            BenchmarkId::new(format!("{}x{}", canvas_size, canvas_size), seed),
+            &config,
+            |bencher, config| {
+                bencher.iter(|| {
+                    let mut painting: Painting = initialize_canvas(config, None);
+                    while place_next_pixel(&mut painting) {}
+                    painting
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    evaluate_position_benchmark,
+    get_best_position_for_color_benchmark,
+    place_pixel_benchmark,
+    full_run_benchmark
+);
+criterion_main!(benches);
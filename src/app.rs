@@ -1,4 +1,6 @@
 use crate::canvas::Canvas;
+use crate::distribution_preview::DistributionPreview;
+use crate::scoring_panel::ScoringPanel;
 use yew::{html, Component, Context, Html};
 
 // yew component for root App
@@ -20,9 +22,13 @@ impl Component for App {
 
     // app view logic
     fn view(&self, _ctx: &Context<Self>) -> Html {
-        // render HtmlCanvasComponent sub-compononent
+        // render HtmlCanvasComponent, DistributionPreview & ScoringPanel sub-compononents
         html! {
-            <Canvas />
+            <>
+                <Canvas />
+                <DistributionPreview />
+                <ScoringPanel />
+            </>
         }
     }
 }
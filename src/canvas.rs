@@ -1,16 +1,42 @@
-use rand::random;
+use rand::{random, rngs::StdRng, Rng, SeedableRng};
+use strict_yaml_rust::{strict_yaml::Hash, StrictYaml, StrictYamlEmitter, StrictYamlLoader};
 // needed to get the reference to HtmlCanvasElement
 use wasm_bindgen::JsCast;
 
 use gloo_timers::callback::Interval;
+use wasm_bindgen_futures::JsFuture;
 use web_sys::{
-    CanvasRenderingContext2d, HtmlCanvasElement, ImageData, OffscreenCanvas,
-    OffscreenCanvasRenderingContext2d,
+    Blob, CanvasRenderingContext2d, HtmlAnchorElement, HtmlCanvasElement, HtmlInputElement,
+    HtmlTextAreaElement, ImageData, OffscreenCanvas, OffscreenCanvasRenderingContext2d, Url,
 };
+use yew::events::TargetCast;
 use yew::{html, Component, Context, Html, NodeRef};
 use yew_bootstrap::component::Button;
 use yew_bootstrap::util::{include_cdn, include_cdn_js, Color};
 
+use crate::cell::Cell;
+use crate::palette_file::ColorPool;
+use crate::quality::QualityReport;
+use crate::quality_panel::QualityPanel;
+use crate::remote_run::{RemoteEvent, RemoteRun};
+use crate::run_metadata::{RunMetadata, RunOrigin};
+use crate::run_stats::{RunStats, StatsSample};
+use crate::script::{PaintingScript, ScriptStats};
+use crate::tiled_canvas::TiledCanvas;
+
+// the HUD's statistics are sampled at this interval, in milliseconds
+const STATS_SAMPLE_INTERVAL_MS: f64 = 500f64;
+// the events url of the first job of a render service started with its default address
+const DEFAULT_REMOTE_URL: &str = "http://127.0.0.1:7878/jobs/1/events";
+
+// shown in the script editor until the user writes their own
+const EXAMPLE_SCRIPT: &str = "// fades from red to green across the image, with random blue
+fn next_color(stats) {
+    let value = stats.progress * 255.0;
+    [value, 255.0 - value, stats.random[0] * 255.0]
+}
+";
+
 // yew messages
 pub(crate) enum Msg {
     RenderCanvas,
@@ -23,40 +49,82 @@ pub(crate) enum Msg {
     WidthInput(String),
     HeightInput(String),
     SetSize,
+    LoadPalette(web_sys::File),
+    PaletteLoaded(Result<ColorPool, String>),
+    DownloadImage,
+    LoadSettings(web_sys::File),
+    SettingsLoaded(Result<RunMetadata, String>),
+    ToggleHud,
+    ScriptInput(String),
+    RunScript,
+    RemoteUrlInput(String),
+    WatchRemoteRun,
+    RemoteEvent(RemoteEvent),
 }
 
-#[derive(Copy, Clone)]
-pub(crate) struct Pixel {
-    red: u8,
-    green: u8,
-    blue: u8,
-    _boundry_val: u8,
+// how the current image was painted, embedded in downloads to paint it again
+enum Painter {
+    // random colors drawn from the seed, from the palette's colors when one was loaded
+    Random { palette: Option<String> },
+    // the colors of a script's next_color function
+    Script { source: String },
 }
 
 // yew sub-component for an html canvas
 pub(crate) struct Canvas {
     node_ref: NodeRef,
-    image_data: Vec<Pixel>,
+    // packed RGBA cells, the alpha byte holds each cell's state
+    image_data: TiledCanvas<Cell>,
+    // holds the drawn image between frames, only changed tiles are uploaded to it
+    offscreen_canvas: Option<OffscreenCanvas>,
+    // reused buffer for uploading one tile at a time
+    tile_cells: Vec<Cell>,
     height: u32,
     view_height: u32,
     width: u32,
     view_width: u32,
     zoom_scale: u32,
-    pixels_placed_count: u64,
-    boundry_pixels: Vec<Pixel>,
+    run_stats: RunStats,
+    // time the current run started and its stats were last sampled, from Date::now()
+    run_start_ms: f64,
+    last_sample_ms: f64,
+    hud_sample: Option<StatsSample>,
+    show_hud: bool,
+    // how long the previous frame took to draw
+    frame_ms: f64,
+    boundry_pixels: Vec<Cell>,
     width_input: String,
     height_input: String,
+    color_pool: Option<ColorPool>,
+    status_message: String,
+    // seed of the last generated image, embedded in downloads to recreate it
+    seed: u64,
+    painter: Painter,
+    // metrics of the last finished painting, cleared when the canvas changes otherwise
+    quality_report: Option<QualityReport>,
+    script_source: String,
+    remote_url: String,
+    // a render service job shown instead of the canvas's own runs
+    remote_run: Option<RemoteRun>,
     _refresh_interval: Interval,
 }
 impl Canvas {
     // render logic
-    fn render_canvas(&self) {
+    fn render_canvas(&mut self) {
+        let frame_start_ms: f64 = js_sys::Date::now();
+
         // if node_ref can be cast as HtmlCanvasElement then render the canvas
         let Some(canvas_ref) = self.node_ref.cast::<HtmlCanvasElement>() else { return };
 
-        // if node_ref can be cast as HtmlCanvasElement then render the canvas
-        let offscreen_canvas: OffscreenCanvas =
-            OffscreenCanvas::new(self.width, self.height).unwrap();
+        // the offscreen canvas is recreated when the canvas size changes,
+        // then every tile is redrawn
+        if self.offscreen_canvas.as_ref().is_none_or(|offscreen_canvas| {
+            offscreen_canvas.width() != self.width || offscreen_canvas.height() != self.height
+        }) {
+            self.offscreen_canvas = Some(OffscreenCanvas::new(self.width, self.height).unwrap());
+            self.image_data.mark_all_dirty();
+        }
+        let offscreen_canvas: OffscreenCanvas = self.offscreen_canvas.clone().unwrap();
 
         // get canvas context
         let canvas_2d: CanvasRenderingContext2d = canvas_ref
@@ -77,26 +145,27 @@ impl Canvas {
         canvas_2d.set_image_smoothing_enabled(false);
         offscreen_canvas_2d.set_image_smoothing_enabled(false);
 
-        // Convert the RGB buffer to RGBA
-        let rgba_data: Vec<u8> = self
-            .image_data
-            .iter()
-            .flat_map(|pixel| vec![pixel.red, pixel.green, pixel.blue, 255u8])
-            .collect();
-
-        // convert framebuffer into js-sys ImageData object
-        let image_data: ImageData = ImageData::new_with_u8_clamped_array_and_sh(
-            wasm_bindgen::Clamped(&rgba_data),
-            self.width as u32,
-            self.height as u32,
-        )
-        .unwrap();
-
-        // write the ImageData to the html canvas contex
-        offscreen_canvas_2d
-            .put_image_data(&image_data, 0.0, 0.0)
+        // only the tiles changed since the last frame are converted and uploaded
+        for tile_index in self.image_data.take_dirty_tiles() {
+            let (left, top, tile_width, tile_height) = self.image_data.tile_bounds(tile_index);
+            self.image_data.read_tile(tile_index, &mut self.tile_cells);
+
+            // the cells already are RGBA, convert the tile into js-sys ImageData object
+            let image_data: ImageData = ImageData::new_with_u8_clamped_array_and_sh(
+                wasm_bindgen::Clamped(bytemuck::cast_slice(&self.tile_cells)),
+                tile_width,
+                tile_height,
+            )
             .unwrap();
 
+            // write the ImageData to the offscreen canvas at the tile's position
+            offscreen_canvas_2d
+                .put_image_data(&image_data, left as f64, top as f64)
+                .unwrap();
+        }
+
+        // unpainted cells are transparent, so the previous frame has to be cleared
+        canvas_2d.clear_rect(0f64, 0f64, self.view_width as f64, self.view_height as f64);
         canvas_2d
             .draw_image_with_offscreen_canvas_and_dw_and_dh(
                 &offscreen_canvas,
@@ -106,57 +175,130 @@ impl Canvas {
                 self.view_height as f64,
             )
             .unwrap();
+
+        if self.show_hud {
+            self.draw_hud(&canvas_2d);
+        }
+        self.frame_ms = js_sys::Date::now() - frame_start_ms;
+    }
+    // start counting a new run of the current canvas size
+    fn reset_stats(&mut self) {
+        self.run_stats = RunStats::new(self.width as u64 * self.height as u64);
+        self.run_start_ms = js_sys::Date::now();
+        self.last_sample_ms = self.run_start_ms;
+        self.hud_sample = None;
+        self.quality_report = None;
+        // a run of the canvas's own stops showing the remote one
+        self.remote_run = None;
+    }
+    // score the finished painting for the quality panel
+    fn analyze_image(&mut self) {
+        let mut pixels: Vec<Cell> =
+            Vec::with_capacity(self.width as usize * self.height as usize);
+        let mut row: Vec<Cell> = Vec::new();
+        for y in 0..self.height {
+            self.image_data.read_row(y, &mut row);
+            pixels.extend_from_slice(&row);
+        }
+        // the cells already are RGBA with the state byte as alpha
+        self.quality_report = Some(QualityReport::analyze(
+            self.width,
+            self.height,
+            bytemuck::cast_slice(&pixels),
+        ));
+    }
+    // take a new stats sample once the sample interval has passed
+    fn sample_stats(&mut self) {
+        let now_ms: f64 = js_sys::Date::now();
+        // a remote run's samples come with its status events
+        if self.remote_run.is_some() || now_ms - self.last_sample_ms < STATS_SAMPLE_INTERVAL_MS {
+            return;
+        }
+        self.last_sample_ms = now_ms;
+        self.hud_sample = Some(
+            self.run_stats
+                .sample((now_ms - self.run_start_ms) / 1000f64, self.boundry_pixels.len()),
+        );
+    }
+    // draw the latest stats over the top left corner of the canvas
+    fn draw_hud(&self, canvas_2d: &CanvasRenderingContext2d) {
+        let mut lines: Vec<String> = match &self.hud_sample {
+            Some(sample) => self
+                .run_stats
+                .progress_line(sample)
+                .split(" | ")
+                .map(String::from)
+                .collect(),
+            None => vec![String::from("waiting for stats")],
+        };
+        lines.push(format!("frame {:.1} ms", self.frame_ms));
+
+        let line_height: f64 = 16f64;
+        canvas_2d.set_fill_style_str("rgba(0, 0, 0, 0.6)");
+        canvas_2d.fill_rect(
+            0f64,
+            0f64,
+            300f64,
+            line_height * lines.len() as f64 + 8f64,
+        );
+        canvas_2d.set_fill_style_str("#d0d0d0");
+        canvas_2d.set_font("12px monospace");
+        for (line_index, line) in lines.iter().enumerate() {
+            canvas_2d
+                .fill_text(line, 6f64, line_height * (line_index + 1) as f64)
+                .unwrap();
+        }
     }
     fn randomize_canvas(&mut self) {
+        self.seed = random::<u64>();
+        self.generate_image();
+    }
+    fn generate_image(&mut self) {
+        // every color is drawn from the seed, so a seed recreates the image
+        let mut rng: StdRng = StdRng::seed_from_u64(self.seed);
+        self.reset_stats();
+        let generate_start_ms: f64 = js_sys::Date::now();
+
         // Create a test pattern RGB image buffer
-        self.image_data = vec![
-            Pixel {
-                red: 0u8,
-                green: 0u8,
-                blue: 0u8,
-                _boundry_val: 0u8
-            };
-            self.width as usize * self.height as usize
-        ];
-        for pixel in self.image_data.iter_mut() {
-            pixel.red = random::<u8>();
-            pixel.green = random::<u8>();
-            pixel.blue = random::<u8>();
+        self.image_data = blank_image(self.width, self.height);
+        // draw from the loaded palette file, or any color without one, row by row
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let [red, green, blue] = match &self.color_pool {
+                    Some(color_pool) => color_pool.sample(&mut rng, 0f32),
+                    None => [rng.gen::<u8>(), rng.gen::<u8>(), rng.gen::<u8>()],
+                };
+                self.image_data.put(x, y, Cell::painted([red, green, blue]));
+                // every pixel counts as an unscored placement
+                self.run_stats.record_placement(None);
+            }
         }
+        self.run_stats
+            .add_bookkeeping_time((js_sys::Date::now() - generate_start_ms) / 1000f64);
+        self.painter = Painter::Random {
+            palette: self.color_pool.as_ref().map(ColorPool::to_hex_list),
+        };
+        self.analyze_image();
     }
     fn toggle_pixel(&mut self, view_x_coord: i32, view_y_coord: i32) {
         let view_scale: f64 = (self.view_width / self.width) as f64;
 
-        let x_index: usize = (view_x_coord as f64 * (1.0f64 / view_scale)).trunc() as usize;
-        let y_index: usize = (view_y_coord as f64 * (1.0f64 / view_scale)).trunc() as usize;
+        let x_index: u32 = (view_x_coord as f64 * (1.0f64 / view_scale)).trunc() as u32;
+        let y_index: u32 = (view_y_coord as f64 * (1.0f64 / view_scale)).trunc() as u32;
 
-        let linear_index: usize = get_linear_index(x_index, y_index, self.width as usize);
+        let [red, green, blue] = self.image_data.get(x_index, y_index).rgb();
 
-        let inverted_red: u8 = 255u8 - self.image_data[linear_index].red;
-        let inverted_green: u8 = 255u8 - self.image_data[linear_index].green;
-        let inverted_blue: u8 = 255u8 - self.image_data[linear_index].blue;
-
-        self.image_data[linear_index] = Pixel {
-            red: inverted_red,
-            green: inverted_green,
-            blue: inverted_blue,
-            _boundry_val: 0u8,
-        }
+        self.image_data.put(
+            x_index,
+            y_index,
+            Cell::painted([255u8 - red, 255u8 - green, 255u8 - blue]),
+        );
+        // inverting a painted pixel is an edit, not a placement, so the stats are left alone
     }
     fn reset_canvas(&mut self) {
-        let width: usize = self.width as usize;
-        let height: usize = self.height as usize;
-
-        // Create a test pattern RGB image buffer
-        self.image_data = vec![
-            Pixel {
-                red: 0u8,
-                green: 0u8,
-                blue: 0u8,
-                _boundry_val: 0u8
-            };
-            width * height
-        ];
+        // Create a blank RGB image buffer
+        self.image_data = blank_image(self.width, self.height);
+        self.reset_stats();
     }
     fn zoom_in_canvas(&mut self) {
         self.view_width = self.view_width * 2u32;
@@ -198,21 +340,8 @@ impl Canvas {
         let canvas_view_width: u32 = canvas_width * self.zoom_scale;
         let canvas_view_height: u32 = canvas_height * self.zoom_scale;
 
-        // generate blank image data
-        let mut random_image_data: Vec<Pixel> = vec![
-            Pixel {
-                red: 0u8,
-                green: 0u8,
-                blue: 0u8,
-                _boundry_val: 0u8
-            };
-            self.width as usize * self.height as usize
-        ];
-        for pixel in random_image_data.iter_mut() {
-            pixel.red = random::<u8>();
-            pixel.green = random::<u8>();
-            pixel.blue = random::<u8>();
-        }
+        // generate random image data
+        let random_image_data: TiledCanvas<Cell> = random_image(canvas_width, canvas_height);
 
         // get the canvas ref and alter the canvas's size
         let Some(canvas_ref) = self.node_ref.cast::<HtmlCanvasElement>() else { return };
@@ -225,8 +354,8 @@ impl Canvas {
         self.view_height = canvas_view_height;
         self.width = canvas_width;
         self.view_width = canvas_view_width;
-        self.pixels_placed_count = 0u64;
         self.boundry_pixels = Vec::new();
+        self.reset_stats();
     }
     fn set_canvas_size(&mut self) {
         // get window & screen from web-sys
@@ -241,21 +370,9 @@ impl Canvas {
             let canvas_view_width: u32 = canvas_width * self.zoom_scale;
             let canvas_view_height: u32 = canvas_height * self.zoom_scale;
 
-            // generate blank image data
-            let mut random_image_data: Vec<Pixel> = vec![
-                Pixel {
-                    red: 0u8,
-                    green: 0u8,
-                    blue: 0u8,
-                    _boundry_val: 0u8
-                };
-                self.width as usize * self.height as usize
-            ];
-            for pixel in random_image_data.iter_mut() {
-                pixel.red = random::<u8>();
-                pixel.green = random::<u8>();
-                pixel.blue = random::<u8>();
-            }
+            // generate random image data
+            let random_image_data: TiledCanvas<Cell> =
+                random_image(canvas_width, canvas_height);
 
             // get the canvas ref and alter the canvas's size
             let Some(canvas_ref) = self.node_ref.cast::<HtmlCanvasElement>() else { return };
@@ -268,8 +385,8 @@ impl Canvas {
             self.view_height = canvas_view_height;
             self.width = canvas_width;
             self.view_width = canvas_view_width;
-            self.pixels_placed_count = 0u64;
             self.boundry_pixels = Vec::new();
+            self.reset_stats();
         }
     }
     fn width_input(&mut self, input: String) {
@@ -278,6 +395,279 @@ impl Canvas {
     fn height_input(&mut self, input: String) {
         self.height_input = input;
     }
+    fn palette_loaded(&mut self, result: Result<ColorPool, String>) {
+        match result {
+            Ok(color_pool) => {
+                self.status_message = format!("Loaded {} palette colors", color_pool.len());
+                self.color_pool = Some(color_pool);
+            }
+            Err(error) => {
+                self.status_message = format!("Palette error: {}", error);
+            }
+        }
+    }
+    // fill the image in order with the colors of the script's next_color function
+    // returns false when the script could not paint, the status message says why
+    fn run_script(&mut self, seed: u64) -> bool {
+        let script: PaintingScript = match PaintingScript::compile(&self.script_source) {
+            Ok(script) => script,
+            Err(error) => {
+                self.status_message = error;
+                return false;
+            }
+        };
+        if !script.has_next_color() {
+            self.status_message =
+                String::from("the canvas only uses next_color, adjust_score needs the shredder");
+            return false;
+        }
+
+        // the script's random values come from the seed, so a seed recreates the image
+        self.seed = seed;
+        let mut rng: StdRng = StdRng::seed_from_u64(self.seed);
+        self.reset_stats();
+        let total_pixel_count: u64 = self.width as u64 * self.height as u64;
+        for pixel_index in 0..total_pixel_count {
+            let stats: ScriptStats = ScriptStats {
+                pixels_placed_count: pixel_index,
+                total_pixel_count,
+                colony_index: 0usize,
+                random: [rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>()],
+            };
+            let [red, green, blue] = match script.next_color(&stats) {
+                Ok(color) => color,
+                Err(error) => {
+                    self.status_message = error;
+                    return false;
+                }
+            };
+            self.image_data.put(
+                (pixel_index % self.width as u64) as u32,
+                (pixel_index / self.width as u64) as u32,
+                Cell::painted([red, green, blue]),
+            );
+            self.run_stats.record_placement(None);
+        }
+        self.painter = Painter::Script {
+            source: self.script_source.clone(),
+        };
+        self.analyze_image();
+        self.status_message = String::from("Painted with script");
+        return true;
+    }
+    // follow a job of the render service, its previews replace the canvas as they arrive
+    fn watch_remote_run(&mut self, on_event: yew::Callback<RemoteEvent>) {
+        self.reset_stats();
+        match RemoteRun::connect(&self.remote_url, on_event) {
+            Ok(remote_run) => {
+                self.remote_run = Some(remote_run);
+                self.status_message = format!("Watching {}", self.remote_url);
+            }
+            Err(error) => {
+                self.status_message = error;
+            }
+        }
+    }
+    fn remote_event(&mut self, event: RemoteEvent) {
+        match event {
+            RemoteEvent::Status(status) => {
+                // the hud shows the remote run's stats in place of the canvas's own
+                if self.run_stats.total_pixel_count() != status.total_pixel_count {
+                    self.run_stats = RunStats::new(status.total_pixel_count);
+                }
+                if let Some(sample) = status.sample {
+                    self.run_stats.push_sample(sample);
+                }
+                self.hud_sample = status.sample;
+                self.status_message = match &status.error {
+                    Some(error) => format!("Remote run failed: {}", error),
+                    None => format!("Remote run {}", status.state),
+                };
+                // the service ends the stream, closing first keeps the browser from reconnecting
+                if status.is_finished() {
+                    if let Some(remote_run) = &self.remote_run {
+                        remote_run.close();
+                    }
+                }
+            }
+            RemoteEvent::Preview(preview) => {
+                self.show_remote_preview(preview);
+            }
+            RemoteEvent::Error(error) => {
+                if let Some(remote_run) = &self.remote_run {
+                    remote_run.close();
+                }
+                self.status_message = error;
+            }
+        }
+    }
+    fn show_remote_preview(&mut self, preview: TiledCanvas<Cell>) {
+        // the canvas takes the size of the preview, at the current zoom
+        if preview.width() != self.width || preview.height() != self.height {
+            let Some(canvas_ref) = self.node_ref.cast::<HtmlCanvasElement>() else { return };
+            canvas_ref.set_width(preview.width() * self.zoom_scale);
+            canvas_ref.set_height(preview.height() * self.zoom_scale);
+            self.height = preview.height();
+            self.view_height = preview.height() * self.zoom_scale;
+            self.width = preview.width();
+            self.view_width = preview.width() * self.zoom_scale;
+        }
+        self.image_data = preview;
+        self.boundry_pixels = Vec::new();
+    }
+    // the web canvas's own settings, which differ from the shredder's config
+    // the palette's colors and the script's source are embedded, not their files
+    fn settings_config(&self) -> String {
+        let section = |entries: Vec<(&str, StrictYaml)>| -> StrictYaml {
+            let mut hash: Hash = Hash::new();
+            for (key, value) in entries {
+                hash.insert(StrictYaml::String(String::from(key)), value);
+            }
+            StrictYaml::Hash(hash)
+        };
+        let size: StrictYaml = section(vec![
+            ("x", StrictYaml::String(self.width.to_string())),
+            ("y", StrictYaml::String(self.height.to_string())),
+        ]);
+        let mut entries: Vec<(&str, StrictYaml)> = vec![
+            ("seed", StrictYaml::String(self.seed.to_string())),
+            ("canvas", section(vec![("size", size)])),
+        ];
+        match &self.painter {
+            Painter::Random { palette: Some(palette) } => entries.push(
+                ("palette", section(vec![("colors", StrictYaml::String(palette.clone()))])),
+            ),
+            Painter::Random { palette: None } => {}
+            Painter::Script { source } => entries.push(
+                ("script", section(vec![("source", StrictYaml::String(source.clone()))])),
+            ),
+        }
+
+        let mut config: String = String::new();
+        StrictYamlEmitter::new(&mut config)
+            .dump(&section(vec![("config", section(entries))]))
+            .expect("[ERROR] unable to write the canvas settings");
+        return config;
+    }
+    fn download_image(&self) {
+        // gather the rows out of the tiles
+        let mut rgb_data: Vec<u8> =
+            Vec::with_capacity(self.width as usize * self.height as usize * 3usize);
+        let mut row: Vec<Cell> = Vec::new();
+        for y in 0..self.height {
+            self.image_data.read_row(y, &mut row);
+            for cell in row.iter() {
+                rgb_data.extend_from_slice(&cell.rgb());
+            }
+        }
+
+        // encode the image as png with the settings embedded
+        let mut png_bytes: Vec<u8> = Vec::new();
+        let metadata: RunMetadata = RunMetadata::new(self.settings_config(), self.seed, RunOrigin::WebCanvas);
+        if let Err(error) = metadata.write_png(
+            &mut png_bytes,
+            self.width,
+            self.height,
+            png::ColorType::Rgb,
+            png::BitDepth::Eight,
+            &rgb_data,
+        ) {
+            web_sys::console::error_1(&error.into());
+            return;
+        }
+
+        // hand the bytes to the browser through a temporary link
+        let blob_parts: js_sys::Array =
+            js_sys::Array::of1(&js_sys::Uint8Array::from(png_bytes.as_slice()));
+        let blob: Blob = Blob::new_with_u8_array_sequence(&blob_parts).unwrap();
+        let url: String = Url::create_object_url_with_blob(&blob).unwrap();
+        let anchor: HtmlAnchorElement = web_sys::window()
+            .unwrap()
+            .document()
+            .unwrap()
+            .create_element("a")
+            .unwrap()
+            .dyn_into::<HtmlAnchorElement>()
+            .unwrap();
+        anchor.set_href(&url);
+        anchor.set_download(&format!("painting_{}.png", self.seed));
+        anchor.click();
+        Url::revoke_object_url(&url).unwrap();
+    }
+    fn settings_loaded(&mut self, result: Result<RunMetadata, String>) {
+        let metadata: RunMetadata = match result {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                self.status_message = format!("Settings error: {}", error);
+                return;
+            }
+        };
+        // the canvas cannot grow a painting the way the shredder does
+        if metadata.origin != RunOrigin::WebCanvas {
+            self.status_message = String::from(
+                "Settings error: image was painted by the shredder, run shredder from-image to repaint it",
+            );
+            return;
+        }
+
+        // read the canvas size back out of the embedded config
+        let configs: Vec<StrictYaml> =
+            StrictYamlLoader::load_from_str(&metadata.config).unwrap_or_default();
+        let config: &StrictYaml = match configs.first() {
+            Some(document) => &document["config"],
+            None => &StrictYaml::BadValue,
+        };
+        let size_value = |axis: &str| -> Option<u32> {
+            config["canvas"]["size"][axis].as_str()?.parse::<u32>().ok()
+        };
+        let (Some(canvas_width), Some(canvas_height)) = (size_value("x"), size_value("y")) else {
+            self.status_message = String::from("Settings error: image has no canvas size");
+            return;
+        };
+
+        // get the canvas ref and alter the canvas's size
+        let Some(canvas_ref) = self.node_ref.cast::<HtmlCanvasElement>() else { return };
+        canvas_ref.set_width(canvas_width * self.zoom_scale);
+        canvas_ref.set_height(canvas_height * self.zoom_scale);
+
+        // make all other needed state changes, then recreate the image from its seed
+        self.height = canvas_height;
+        self.view_height = canvas_height * self.zoom_scale;
+        self.width = canvas_width;
+        self.view_width = canvas_width * self.zoom_scale;
+        self.boundry_pixels = Vec::new();
+        self.image_data = blank_image(canvas_width, canvas_height);
+
+        // paint the image the way it was painted, with its embedded palette or script
+        if let Some(source) = config["script"]["source"].as_str() {
+            self.script_source = String::from(source);
+            if !self.run_script(metadata.seed) {
+                return;
+            }
+        } else {
+            self.color_pool = match config["palette"]["colors"].as_str() {
+                Some(colors) => match ColorPool::parse(colors.as_bytes()) {
+                    Ok(color_pool) => Some(color_pool),
+                    Err(error) => {
+                        self.status_message = format!("Settings error: {}", error);
+                        return;
+                    }
+                },
+                None => None,
+            };
+            self.seed = metadata.seed;
+            self.generate_image();
+        }
+
+        self.status_message = if metadata.is_from_other_version() {
+            format!(
+                "Loaded settings from version {}, results may differ",
+                metadata.version
+            )
+        } else {
+            String::from("Loaded settings from image")
+        };
+    }
 }
 impl Component for Canvas {
     type Message = Msg;
@@ -306,44 +696,75 @@ impl Component for Canvas {
         let canvas_view_height: u32 = canvas_height * default_scale;
 
         // generate blank image data
-        let blank_image_buffer: Vec<Pixel> = vec![
-            Pixel {
-                red: 0u8,
-                green: 0u8,
-                blue: 0u8,
-                _boundry_val: 0u8
-            };
-            (canvas_height * canvas_width) as usize
-        ];
+        let blank_image_buffer: TiledCanvas<Cell> = blank_image(canvas_width, canvas_height);
 
         // make all other needed state changes
         Self {
             node_ref: NodeRef::default(),
             image_data: blank_image_buffer,
+            offscreen_canvas: None,
+            tile_cells: Vec::new(),
             height: canvas_height,
             view_height: canvas_view_height,
             width: canvas_width,
             view_width: canvas_view_width,
             zoom_scale: default_scale,
-            pixels_placed_count: 0u64,
+            run_stats: RunStats::new(canvas_width as u64 * canvas_height as u64),
+            run_start_ms: js_sys::Date::now(),
+            last_sample_ms: js_sys::Date::now(),
+            hud_sample: None,
+            show_hud: true,
+            frame_ms: 0f64,
             boundry_pixels: Vec::new(),
             width_input: String::default(),
             height_input: String::default(),
+            color_pool: None,
+            status_message: String::default(),
+            seed: random::<u64>(),
+            painter: Painter::Random { palette: None },
+            quality_report: None,
+            script_source: String::from(EXAMPLE_SCRIPT),
+            remote_url: String::from(DEFAULT_REMOTE_URL),
+            remote_run: None,
             _refresh_interval: interval,
         }
     }
 
     // Canvas update logic
-    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
         match msg {
             Msg::ResetCanvas => {
                 self.reset_canvas();
+                return true;
             }
             Msg::RenderCanvas => {
+                self.sample_stats();
                 self.render_canvas();
             }
+            Msg::ToggleHud => {
+                self.show_hud = !self.show_hud;
+            }
+            Msg::ScriptInput(source) => {
+                self.script_source = source;
+            }
+            Msg::RunScript => {
+                self.run_script(random::<u64>());
+                return true;
+            }
+            Msg::RemoteUrlInput(url) => {
+                self.remote_url = url;
+            }
+            Msg::WatchRemoteRun => {
+                self.watch_remote_run(ctx.link().callback(Msg::RemoteEvent));
+                return true;
+            }
+            Msg::RemoteEvent(event) => {
+                self.remote_event(event);
+                return true;
+            }
             Msg::RandomizeCanvas => {
                 self.randomize_canvas();
+                return true;
             }
             Msg::FitCanvas => {
                 self.fit_canvas_to_screen();
@@ -366,6 +787,41 @@ impl Component for Canvas {
             Msg::HeightInput(height) => {
                 self.height_input(height);
             }
+            Msg::LoadPalette(file) => {
+                // read the file's bytes asynchronously, then parse them as a palette
+                ctx.link().send_future(async move {
+                    match JsFuture::from(file.array_buffer()).await {
+                        Ok(array_buffer) => Msg::PaletteLoaded(ColorPool::parse(
+                            &js_sys::Uint8Array::new(&array_buffer).to_vec(),
+                        )),
+                        Err(_) => Msg::PaletteLoaded(Err(String::from(
+                            "unable to read palette file",
+                        ))),
+                    }
+                });
+            }
+            Msg::PaletteLoaded(result) => {
+                self.palette_loaded(result);
+                return true;
+            }
+            Msg::DownloadImage => {
+                self.download_image();
+            }
+            Msg::LoadSettings(file) => {
+                // read the file's bytes asynchronously, then read the png's settings
+                ctx.link().send_future(async move {
+                    match JsFuture::from(file.array_buffer()).await {
+                        Ok(array_buffer) => Msg::SettingsLoaded(RunMetadata::read_png(
+                            &js_sys::Uint8Array::new(&array_buffer).to_vec(),
+                        )),
+                        Err(_) => Msg::SettingsLoaded(Err(String::from("unable to read image"))),
+                    }
+                });
+            }
+            Msg::SettingsLoaded(result) => {
+                self.settings_loaded(result);
+                return true;
+            }
         }
         false
     }
@@ -382,6 +838,8 @@ impl Component for Canvas {
             ctx.link().callback(|_| Msg::ZoomIn);
         let zoom_out_button_callback: yew::Callback<web_sys::MouseEvent> =
             ctx.link().callback(|_| Msg::ZoomOut);
+        let hud_button_callback: yew::Callback<web_sys::MouseEvent> =
+            ctx.link().callback(|_| Msg::ToggleHud);
         let set_canvas_size_callback: yew::Callback<web_sys::MouseEvent> =
             ctx.link().callback(|_| Msg::SetSize);
         let width_input_callback: yew::Callback<web_sys::InputEvent> = ctx
@@ -390,6 +848,36 @@ impl Component for Canvas {
         let height_input_callback: yew::Callback<web_sys::InputEvent> = ctx
             .link()
             .callback(|event: web_sys::InputEvent| Msg::HeightInput(event.data().unwrap()));
+        let palette_file_callback: yew::Callback<web_sys::Event> =
+            ctx.link().batch_callback(|event: web_sys::Event| {
+                let input: HtmlInputElement = event.target_unchecked_into::<HtmlInputElement>();
+                input
+                    .files()
+                    .and_then(|files| files.get(0))
+                    .map(Msg::LoadPalette)
+            });
+        let download_button_callback: yew::Callback<web_sys::MouseEvent> =
+            ctx.link().callback(|_| Msg::DownloadImage);
+        let settings_file_callback: yew::Callback<web_sys::Event> =
+            ctx.link().batch_callback(|event: web_sys::Event| {
+                let input: HtmlInputElement = event.target_unchecked_into::<HtmlInputElement>();
+                input
+                    .files()
+                    .and_then(|files| files.get(0))
+                    .map(Msg::LoadSettings)
+            });
+        let script_input_callback: yew::Callback<web_sys::InputEvent> =
+            ctx.link().callback(|event: web_sys::InputEvent| {
+                Msg::ScriptInput(event.target_unchecked_into::<HtmlTextAreaElement>().value())
+            });
+        let run_script_callback: yew::Callback<web_sys::MouseEvent> =
+            ctx.link().callback(|_| Msg::RunScript);
+        let remote_url_callback: yew::Callback<web_sys::InputEvent> =
+            ctx.link().callback(|event: web_sys::InputEvent| {
+                Msg::RemoteUrlInput(event.target_unchecked_into::<HtmlInputElement>().value())
+            });
+        let watch_remote_callback: yew::Callback<web_sys::MouseEvent> =
+            ctx.link().callback(|_| Msg::WatchRemoteRun);
         let canvas_mouse_callback: yew::Callback<web_sys::MouseEvent> =
             ctx.link().callback(|event: web_sys::MouseEvent| {
                 Msg::TogglePixel(event.offset_x(), event.offset_y())
@@ -406,12 +894,38 @@ impl Component for Canvas {
                 <div class="centered-div">
                     <Button onclick={zoom_in_button_callback} style={Color::Dark} class="centered-button" text={"Zoom In"} />
                     <Button onclick={zoom_out_button_callback} style={Color::Dark} class="centered-button" text={"Zoom Out"} />
+                    <Button onclick={hud_button_callback} style={Color::Dark} class="centered-button" text={"Toggle Stats"} />
                 </div>
                 <div class="centered-div">
                     <input type="text" class="text-input" value="Width Input" oninput={width_input_callback} />
                     <input type="text" class="text-input" value="Height Input" oninput={height_input_callback} />
                     <Button onclick={set_canvas_size_callback} style={Color::Dark} class="centered-button" text={"Set Dimensions"} />
                 </div>
+                <div class="centered-div">
+                    <input type="file" class="text-input status-text" accept=".gpl,.ase,.pal,.hex,.txt" onchange={palette_file_callback} />
+                </div>
+                <div class="centered-div">
+                    <Button onclick={download_button_callback} style={Color::Dark} class="centered-button" text={"Download Image"} />
+                    <label class="status-text">{"Load Settings from Image"}</label>
+                    <input type="file" class="text-input status-text" accept=".png" onchange={settings_file_callback} />
+                </div>
+                <div class="centered-div">
+                    <textarea class="config-snippet" rows="8" value={self.script_source.clone()} oninput={script_input_callback} />
+                    <Button onclick={run_script_callback} style={Color::Dark} class="centered-button" text={"Run Script"} />
+                </div>
+                <div class="centered-div">
+                    <input type="text" class="text-input distribution-input" value={self.remote_url.clone()} oninput={remote_url_callback} />
+                    <Button onclick={watch_remote_callback} style={Color::Dark} class="centered-button" text={"Watch Remote Run"} />
+                </div>
+                <div class="centered-div">
+                    <span class="status-text">{&self.status_message}</span>
+                </div>
+                {
+                    match &self.quality_report {
+                        Some(report) => html! { <QualityPanel report={report.clone()} /> },
+                        None => html! {},
+                    }
+                }
                 <div class="centered-div">
                     <canvas
                         width={self.view_width.to_string()}
@@ -426,6 +940,24 @@ impl Component for Canvas {
     }
 }
 
-fn get_linear_index(x: usize, y: usize, width: usize) -> usize {
-    (y * width) + x
+// an all black image, with every tile marked for redrawing over the previous image
+fn blank_image(width: u32, height: u32) -> TiledCanvas<Cell> {
+    let mut image_data: TiledCanvas<Cell> = TiledCanvas::new(width, height);
+    image_data.mark_all_dirty();
+    image_data
+}
+
+// an image of unseeded random colors
+fn random_image(width: u32, height: u32) -> TiledCanvas<Cell> {
+    let mut image_data: TiledCanvas<Cell> = blank_image(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            image_data.put(
+                x,
+                y,
+                Cell::painted([random::<u8>(), random::<u8>(), random::<u8>()]),
+            );
+        }
+    }
+    image_data
 }
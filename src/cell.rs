@@ -0,0 +1,59 @@
+use bytemuck::{Pod, Zeroable};
+
+// values of a cell's state byte
+const EMPTY_STATE: u8 = 0u8;
+// nearly transparent, so frontier cells still look empty in the browser
+const FRONTIER_STATE: u8 = 1u8;
+const PAINTED_STATE: u8 = 255u8;
+
+// one canvas cell packed into four bytes: its color and whether it is empty, frontier or painted
+// the state byte doubles as the alpha channel, so tiles of cells can be handed to a browser
+// canvas as RGBA without converting them, and black is a color like any other
+#[derive(Copy, Clone, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub(crate) struct Cell {
+    red: u8,
+    green: u8,
+    blue: u8,
+    state: u8,
+}
+impl Cell {
+    pub(crate) const EMPTY: Cell = Cell {
+        red: 0u8,
+        green: 0u8,
+        blue: 0u8,
+        state: EMPTY_STATE,
+    };
+    // an unpainted cell in some colony's boundry region
+    pub(crate) const FRONTIER: Cell = Cell {
+        red: 0u8,
+        green: 0u8,
+        blue: 0u8,
+        state: FRONTIER_STATE,
+    };
+
+    pub(crate) fn painted([red, green, blue]: [u8; 3]) -> Cell {
+        Cell {
+            red,
+            green,
+            blue,
+            state: PAINTED_STATE,
+        }
+    }
+
+    pub(crate) fn rgb(&self) -> [u8; 3] {
+        [self.red, self.green, self.blue]
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.state == EMPTY_STATE
+    }
+
+    pub(crate) fn is_frontier(&self) -> bool {
+        self.state == FRONTIER_STATE
+    }
+
+    pub(crate) fn is_painted(&self) -> bool {
+        self.state == PAINTED_STATE
+    }
+}
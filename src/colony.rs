@@ -0,0 +1,239 @@
+use rand::Rng;
+use strict_yaml_rust::StrictYaml;
+
+use crate::color_source::ColorSource;
+use crate::{parse_config_value, parse_optional_config_value, Coordinate};
+
+// a group of seeds that grows with its own colors and its own boundry region
+pub(crate) struct Colony {
+    pub(crate) color_source: ColorSource,
+    pub(crate) boundry_region_list: Vec<Coordinate>,
+    pub(crate) starting_locations: Vec<Coordinate>,
+    pub(crate) weight: f32,
+    // the finite color pool of position first generation, empty otherwise
+    pub(crate) remaining_colors: Vec<[u8; 3]>,
+}
+impl Colony {
+    // build a colony from one entry of the "colonies" config list
+    pub(crate) fn from_config(config: &StrictYaml, total_pixel_count: u64) -> Colony {
+        // weighted scheduling draws over the sum of the weights,
+        // a NaN or non-positive weight would hand every draw to the last colony
+        let weight: f32 = parse_optional_config_value(&config["weight"], 1f32);
+        if !weight.is_finite() || weight <= 0f32 {
+            panic!("[ERROR] colony weight must be a finite number above 0");
+        }
+
+        return Colony {
+            color_source: ColorSource::from_config(&config["palette"], total_pixel_count),
+            boundry_region_list: Vec::new(),
+            starting_locations: parse_starting_locations(&config["starting_locations"]),
+            weight,
+            remaining_colors: Vec::new(),
+        };
+    }
+}
+
+// how the next colony to grow is chosen
+pub(crate) enum SchedulingPolicy {
+    // every colony takes a turn
+    RoundRobin,
+    // colonies with a larger boundry region grow more often
+    Proportional,
+    // colonies grow according to their configured weight
+    Weighted,
+}
+impl SchedulingPolicy {
+    pub(crate) fn from_config(config: &StrictYaml) -> SchedulingPolicy {
+        match config.as_str() {
+            None | Some("round_robin") => SchedulingPolicy::RoundRobin,
+            Some("proportional") => SchedulingPolicy::Proportional,
+            Some("weighted") => SchedulingPolicy::Weighted,
+            Some(other) => panic!("[ERROR] unknown colony scheduling policy: {}", other),
+        }
+    }
+}
+
+// picks which colony places the next pixel
+pub(crate) struct ColonyScheduler {
+    policy: SchedulingPolicy,
+    round_robin_cursor: usize,
+}
+impl ColonyScheduler {
+    pub(crate) fn new(policy: SchedulingPolicy) -> ColonyScheduler {
+        ColonyScheduler {
+            policy,
+            round_robin_cursor: 0usize,
+        }
+    }
+
+    // return the index of the next colony to grow
+    // returns None once every boundry region is exhausted
+    pub(crate) fn next_colony<R: Rng + ?Sized>(
+        &mut self,
+        colonies: &[Colony],
+        rng: &mut R,
+    ) -> Option<usize> {
+        // colonies with an empty boundry region can no longer grow
+        let open_colonies: Vec<usize> = (0..colonies.len())
+            .filter(|index| !colonies[*index].boundry_region_list.is_empty())
+            .collect();
+        if open_colonies.is_empty() {
+            return None;
+        }
+
+        match self.policy {
+            SchedulingPolicy::RoundRobin => {
+                // advance the cursor to the next colony that can still grow
+                for offset in 0..colonies.len() {
+                    let index: usize = (self.round_robin_cursor + offset) % colonies.len();
+                    if !colonies[index].boundry_region_list.is_empty() {
+                        self.round_robin_cursor = index + 1;
+                        return Some(index);
+                    }
+                }
+                return None;
+            }
+            SchedulingPolicy::Proportional => {
+                let total_size: usize = open_colonies
+                    .iter()
+                    .map(|index| colonies[*index].boundry_region_list.len())
+                    .sum();
+
+                // walk the colonies until the random draw is used up
+                let mut draw: usize = rng.gen_range(0..total_size);
+                for index in open_colonies.iter() {
+                    let size: usize = colonies[*index].boundry_region_list.len();
+                    if draw < size {
+                        return Some(*index);
+                    }
+                    draw -= size;
+                }
+                return open_colonies.last().copied();
+            }
+            SchedulingPolicy::Weighted => {
+                let total_weight: f32 = open_colonies
+                    .iter()
+                    .map(|index| colonies[*index].weight)
+                    .sum();
+
+                // walk the colonies until the random draw is used up
+                let mut draw: f32 = rng.gen::<f32>() * total_weight;
+                for index in open_colonies.iter() {
+                    let weight: f32 = colonies[*index].weight;
+                    if draw < weight {
+                        return Some(*index);
+                    }
+                    draw -= weight;
+                }
+                return open_colonies.last().copied();
+            }
+        }
+    }
+}
+
+// read a list of x/y coordinates from the config
+pub(crate) fn parse_starting_locations(config: &StrictYaml) -> Vec<Coordinate> {
+    let mut starting_points: Vec<Coordinate> = Vec::new();
+
+    for location in config
+        .as_vec()
+        .expect("[ERROR] failed to parse starting_locations as list")
+    {
+        starting_points.push(Coordinate {
+            x: parse_config_value(&location["x"]),
+            y: parse_config_value(&location["y"]),
+        });
+    }
+
+    return starting_points;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+    use strict_yaml_rust::StrictYamlLoader;
+
+    // a colony with the given weight and this many cells in its boundry region
+    fn colony(weight: &str, boundry_size: u32) -> Colony {
+        let config: StrictYaml = StrictYamlLoader::load_from_str(&format!(
+            "weight: \"{}\"\nstarting_locations:\n  - x: \"0\"\n    y: \"0\"",
+            weight
+        ))
+        .unwrap()
+        .remove(0);
+        let mut colony: Colony = Colony::from_config(&config, 64u64);
+        colony.boundry_region_list = (0..boundry_size)
+            .map(|x| Coordinate { x, y: 0u32 })
+            .collect();
+        return colony;
+    }
+
+    // how often each colony is picked over many draws
+    fn pick_counts(policy: SchedulingPolicy, colonies: &[Colony]) -> Vec<u32> {
+        let mut scheduler: ColonyScheduler = ColonyScheduler::new(policy);
+        let mut rng: StdRng = StdRng::seed_from_u64(11u64);
+        let mut counts: Vec<u32> = vec![0u32; colonies.len()];
+        for _ in 0..4000 {
+            counts[scheduler.next_colony(colonies, &mut rng).unwrap()] += 1;
+        }
+        return counts;
+    }
+
+    #[test]
+    fn round_robin_skips_exhausted_colonies() {
+        let colonies: Vec<Colony> = vec![colony("1", 1u32), colony("1", 0u32), colony("1", 1u32)];
+        let mut scheduler: ColonyScheduler = ColonyScheduler::new(SchedulingPolicy::RoundRobin);
+        let mut rng: StdRng = StdRng::seed_from_u64(1u64);
+        let picks: Vec<Option<usize>> = (0..4)
+            .map(|_| scheduler.next_colony(&colonies, &mut rng))
+            .collect();
+        assert_eq!(
+            picks,
+            vec![Some(0usize), Some(2usize), Some(0usize), Some(2usize)]
+        );
+    }
+
+    #[test]
+    fn proportional_follows_the_boundry_sizes() {
+        let colonies: Vec<Colony> = vec![colony("1", 30u32), colony("1", 0u32), colony("1", 10u32)];
+        let counts: Vec<u32> = pick_counts(SchedulingPolicy::Proportional, &colonies);
+        assert_eq!(counts[1], 0u32);
+        assert!((2800u32..3200u32).contains(&counts[0]), "{:?}", counts);
+    }
+
+    #[test]
+    fn weighted_follows_the_weights() {
+        let colonies: Vec<Colony> = vec![colony("3", 1u32), colony("1", 1u32), colony("9", 0u32)];
+        let counts: Vec<u32> = pick_counts(SchedulingPolicy::Weighted, &colonies);
+        assert_eq!(counts[2], 0u32);
+        assert!((2800u32..3200u32).contains(&counts[0]), "{:?}", counts);
+    }
+
+    #[test]
+    fn stops_once_every_colony_is_exhausted() {
+        let colonies: Vec<Colony> = vec![colony("1", 0u32), colony("1", 0u32)];
+        let mut rng: StdRng = StdRng::seed_from_u64(1u64);
+        for policy in [
+            SchedulingPolicy::RoundRobin,
+            SchedulingPolicy::Proportional,
+            SchedulingPolicy::Weighted,
+        ] {
+            assert_eq!(
+                ColonyScheduler::new(policy).next_colony(&colonies, &mut rng),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_weights_that_are_not_positive() {
+        for weight in ["0", "-1", "NaN", "inf"] {
+            assert!(
+                std::panic::catch_unwind(|| colony(weight, 1u32)).is_err(),
+                "accepted weight {}",
+                weight
+            );
+        }
+    }
+}
@@ -0,0 +1,150 @@
+// wide compiles to sse/avx or neon depending on the enabled target features
+// and falls back to plain scalar code on targets without them
+// only the native engine uses this kernel, the web canvas does not score cells
+use wide::{f32x8, CmpGt};
+
+// a cell has at most eight neighbors
+pub(crate) const NEIGHBOR_SLOTS: usize = 8;
+// candidates compared at once by the simd kernel
+const LANES: usize = 8;
+
+// the colors of one cell's painted neighbors
+#[derive(Copy, Clone)]
+pub(crate) struct NeighborColors {
+    colors: [[u8; 3]; NEIGHBOR_SLOTS],
+    count: usize,
+}
+impl NeighborColors {
+    pub(crate) fn new() -> NeighborColors {
+        NeighborColors {
+            colors: [[0u8; 3]; NEIGHBOR_SLOTS],
+            count: 0usize,
+        }
+    }
+
+    pub(crate) fn push(&mut self, color: [u8; 3]) {
+        self.colors[self.count] = color;
+        self.count += 1;
+    }
+
+    pub(crate) fn as_slice(&self) -> &[[u8; 3]] {
+        &self.colors[..self.count]
+    }
+}
+
+// squared RGB distances between a target color and a cell's neighbors
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) struct NeighborDistances {
+    // f32::MAX without neighbors
+    pub(crate) min: f32,
+    pub(crate) sum: f32,
+    pub(crate) count: u32,
+}
+
+// the scalar kernel, for single cells
+pub(crate) fn neighbor_distances(neighbors: &NeighborColors, target: [u8; 3]) -> NeighborDistances {
+    let mut distances: NeighborDistances = NeighborDistances {
+        min: f32::MAX,
+        sum: 0f32,
+        count: 0u32,
+    };
+    for color in neighbors.as_slice() {
+        let red: f32 = color[0] as f32 - target[0] as f32;
+        let green: f32 = color[1] as f32 - target[1] as f32;
+        let blue: f32 = color[2] as f32 - target[2] as f32;
+        let distance: f32 = red * red + green * green + blue * blue;
+        distances.min = distances.min.min(distance);
+        distances.sum += distance;
+        distances.count += 1;
+    }
+    return distances;
+}
+
+// the neighbor colors of a batch of candidate cells, one column per neighbor slot,
+// so the simd kernel reads the same slot of eight candidates at once
+pub(crate) struct NeighborBatch {
+    red: [Vec<f32>; NEIGHBOR_SLOTS],
+    green: [Vec<f32>; NEIGHBOR_SLOTS],
+    blue: [Vec<f32>; NEIGHBOR_SLOTS],
+    // 1 where the slot holds a neighbor, 0 for empty slots
+    present: [Vec<f32>; NEIGHBOR_SLOTS],
+    len: usize,
+}
+impl NeighborBatch {
+    pub(crate) fn with_capacity(capacity: usize) -> NeighborBatch {
+        let column = || -> [Vec<f32>; NEIGHBOR_SLOTS] {
+            std::array::from_fn(|_| Vec::with_capacity(capacity))
+        };
+        NeighborBatch {
+            red: column(),
+            green: column(),
+            blue: column(),
+            present: column(),
+            len: 0usize,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    // add the next candidate
+    pub(crate) fn push(&mut self, neighbors: &NeighborColors) {
+        for slot in 0..NEIGHBOR_SLOTS {
+            let (color, present): ([u8; 3], f32) = if slot < neighbors.count {
+                (neighbors.colors[slot], 1f32)
+            } else {
+                ([0u8; 3], 0f32)
+            };
+            self.red[slot].push(color[0] as f32);
+            self.green[slot].push(color[1] as f32);
+            self.blue[slot].push(color[2] as f32);
+            self.present[slot].push(present);
+        }
+        self.len += 1;
+    }
+
+    // the simd kernel: distances of every candidate's neighbors to the target color, in push order
+    // the last group of candidates is padded with empty slots
+    pub(crate) fn distances(&self, target: [u8; 3], distances: &mut Vec<NeighborDistances>) {
+        distances.clear();
+        let target_red: f32x8 = f32x8::splat(target[0] as f32);
+        let target_green: f32x8 = f32x8::splat(target[1] as f32);
+        let target_blue: f32x8 = f32x8::splat(target[2] as f32);
+        let no_neighbor: f32x8 = f32x8::splat(f32::MAX);
+
+        for start in (0..self.len).step_by(LANES) {
+            let mut min: f32x8 = no_neighbor;
+            let mut sum: f32x8 = f32x8::ZERO;
+            let mut count: f32x8 = f32x8::ZERO;
+            for slot in 0..NEIGHBOR_SLOTS {
+                let red: f32x8 = load_lanes(&self.red[slot], start) - target_red;
+                let green: f32x8 = load_lanes(&self.green[slot], start) - target_green;
+                let blue: f32x8 = load_lanes(&self.blue[slot], start) - target_blue;
+                let present: f32x8 = load_lanes(&self.present[slot], start);
+                let distance: f32x8 = red * red + green * green + blue * blue;
+
+                min = present.cmp_gt(f32x8::ZERO).blend(min.min(distance), min);
+                sum += distance * present;
+                count += present;
+            }
+
+            let (min, sum, count) = (min.to_array(), sum.to_array(), count.to_array());
+            for lane in 0..LANES.min(self.len - start) {
+                distances.push(NeighborDistances {
+                    min: min[lane],
+                    sum: sum[lane],
+                    count: count[lane] as u32,
+                });
+            }
+        }
+    }
+}
+
+// eight values of a column from the given start, zero past its end
+fn load_lanes(column: &[f32], start: usize) -> f32x8 {
+    let mut lanes: [f32; LANES] = [0f32; LANES];
+    let end: usize = (start + LANES).min(column.len());
+    lanes[..end - start].copy_from_slice(&column[start..end]);
+    return f32x8::from(lanes);
+}
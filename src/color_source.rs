@@ -0,0 +1,439 @@
+use image::Rgb;
+use palette::{
+    convert::{IntoColorUnclamped, TryIntoColor},
+    Hsv, Oklch, Srgb,
+};
+use rand::Rng;
+use std::{fs, sync::Arc};
+use strict_yaml_rust::StrictYaml;
+
+use crate::distribution::{fit_to_channel, ChannelDistribution};
+use crate::image_palette::{ImageColorMode, ImageSource};
+use crate::palette_file::ColorPool;
+use crate::{parse_config_value, parse_optional_config_value};
+
+// chroma of the most saturated sRGB colors in oklch
+// the chroma channel samples fractions of this value
+const OKLCH_MAX_CHROMA: f32 = 0.37f32;
+
+// generates the colors of one colony
+// the palette drifts between keyframes as the painting progresses
+pub(crate) struct ColorSource {
+    // always holds at least one keyframe, sorted by position
+    keyframes: Vec<PaletteKeyframe>,
+    // an input image whose pixels are each handed out once, replacing the palette
+    population_image: Option<ImageSource>,
+    // the shuffled pixels not yet handed out
+    population: Vec<[u8; 3]>,
+}
+
+// the palette at a given number of placed pixels
+struct PaletteKeyframe {
+    at_pixel_count: u64,
+    palette: PaletteState,
+}
+
+// the color space the palette channels are sampled in
+#[derive(Copy, Clone, PartialEq)]
+enum ColorSpace {
+    // hue, saturation, value
+    Hsv,
+    // perceptual lightness, chroma, hue
+    Oklch,
+}
+
+// parameters used to generate random colors at one point in time
+#[derive(Clone)]
+pub(crate) struct PaletteState {
+    color_space: ColorSpace,
+    // one distribution per channel, each sampling fractions of the channel's range
+    channels: [ChannelDistribution; 3],
+    // colors loaded from a palette file, replacing the channel distributions when set
+    color_pool: Option<Arc<ColorPool>>,
+    // how far each channel of a pool color may stray from it, in 0-255 steps
+    jitter: f32,
+    // blend between the min (0.0) and average (1.0) neighbor color distance
+    pub(crate) average_weight: f32,
+}
+
+impl ColorSource {
+    // build a color source from a "palette" config section
+    // missing values fall back to the default palette
+    pub(crate) fn from_config(config: &StrictYaml, total_pixel_count: u64) -> ColorSource {
+        // the top level of the palette section is the keyframe at pixel zero
+        // every keyframe shares its color space
+        let color_space: ColorSpace = ColorSpace::from_config(&config["space"]);
+        // the image is decoded once, for both its palette and its population
+        let image_source: Option<ImageSource> = ImageSource::from_config(&config["image"]);
+        let mut keyframes: Vec<PaletteKeyframe> = vec![PaletteKeyframe {
+            at_pixel_count: 0u64,
+            palette: PaletteState::from_config(
+                config,
+                &PaletteState::default_for(color_space),
+                image_source.as_ref(),
+            ),
+        }];
+
+        // every following keyframe inherits missing values from the one before it
+        if let Some(keyframe_configs) = config["keyframes"].as_vec() {
+            for keyframe_config in keyframe_configs {
+                // the population is handed out over the whole painting, it can not start midway
+                let keyframe_image: Option<ImageSource> =
+                    ImageSource::from_config(&keyframe_config["image"]);
+                if let Some(keyframe_image) = &keyframe_image {
+                    if matches!(keyframe_image.mode, ImageColorMode::Population) {
+                        panic!("[ERROR] population images can not be used in palette keyframes");
+                    }
+                }
+                let previous_palette: &PaletteState = &keyframes[keyframes.len() - 1].palette;
+                let palette: PaletteState = PaletteState::from_config(
+                    keyframe_config,
+                    previous_palette,
+                    keyframe_image.as_ref(),
+                );
+                keyframes.push(PaletteKeyframe {
+                    at_pixel_count: parse_keyframe_position(
+                        &keyframe_config["at"],
+                        total_pixel_count,
+                    ),
+                    palette,
+                });
+            }
+        }
+        keyframes.sort_by_key(|keyframe| keyframe.at_pixel_count);
+
+        // an image in population mode rearranges the image's own pixels
+        // when the counts differ its pixels are reshuffled and reused, or some are left over
+        let population_image: Option<ImageSource> = image_source
+            .filter(|image_source| matches!(image_source.mode, ImageColorMode::Population));
+        if let Some(population_image) = &population_image {
+            if population_image.pixel_count() as u64 != total_pixel_count {
+                eprintln!(
+                    "[WARNING] the population image has {} pixels but the canvas has {}, {}",
+                    population_image.pixel_count(),
+                    total_pixel_count,
+                    if (population_image.pixel_count() as u64) < total_pixel_count {
+                        "its pixels will be used more than once"
+                    } else {
+                        "some of its pixels will not be used"
+                    }
+                );
+            }
+        }
+
+        return ColorSource {
+            keyframes,
+            population_image,
+            population: Vec::new(),
+        };
+    }
+
+    // generate the next color, drawing from the image population before the palette
+    pub(crate) fn next_color<R: Rng + ?Sized>(
+        &mut self,
+        palette_state: &PaletteState,
+        rng: &mut R,
+    ) -> Rgb<u8> {
+        if let Some(population_image) = &self.population_image {
+            // once every pixel was used, start over with a fresh shuffle
+            if self.population.is_empty() {
+                self.population = population_image.shuffled_population(rng);
+            }
+            if let Some(color) = self.population.pop() {
+                return Rgb(color);
+            }
+        }
+        return palette_state.next_color(rng);
+    }
+
+    // interpolate the palette for the current number of placed pixels
+    pub(crate) fn palette_at(&self, pixels_placed_count: u64) -> PaletteState {
+        // find the first keyframe that has not been reached yet
+        let next_index: usize = self
+            .keyframes
+            .iter()
+            .position(|keyframe| keyframe.at_pixel_count > pixels_placed_count)
+            .unwrap_or(self.keyframes.len());
+
+        // before the first or after the last keyframe the palette holds still
+        if next_index == 0 {
+            return self.keyframes[0].palette.clone();
+        }
+        if next_index == self.keyframes.len() {
+            return self.keyframes[next_index - 1].palette.clone();
+        }
+
+        let previous: &PaletteKeyframe = &self.keyframes[next_index - 1];
+        let next: &PaletteKeyframe = &self.keyframes[next_index];
+        let fraction: f32 = (pixels_placed_count - previous.at_pixel_count) as f32
+            / (next.at_pixel_count - previous.at_pixel_count) as f32;
+
+        return previous.palette.interpolate(&next.palette, fraction);
+    }
+}
+impl Default for ColorSource {
+    fn default() -> Self {
+        ColorSource {
+            keyframes: vec![PaletteKeyframe {
+                at_pixel_count: 0u64,
+                palette: PaletteState::default(),
+            }],
+            population_image: None,
+            population: Vec::new(),
+        }
+    }
+}
+
+impl ColorSpace {
+    fn from_config(config: &StrictYaml) -> ColorSpace {
+        match config.as_str() {
+            None | Some("hsv") => ColorSpace::Hsv,
+            Some("oklch") => ColorSpace::Oklch,
+            Some(other) => panic!("[ERROR] unknown palette color space: {}", other),
+        }
+    }
+
+    // config keys of the three channels, in sampling order
+    fn channel_names(&self) -> [&'static str; 3] {
+        match self {
+            ColorSpace::Hsv => ["hue", "saturation", "value"],
+            ColorSpace::Oklch => ["lightness", "chroma", "hue"],
+        }
+    }
+
+    // index of the channel that wraps around the color wheel
+    fn hue_channel(&self) -> usize {
+        match self {
+            ColorSpace::Hsv => 0usize,
+            ColorSpace::Oklch => 2usize,
+        }
+    }
+}
+
+impl PaletteState {
+    // the palette used when the config does not override a channel
+    fn default_for(color_space: ColorSpace) -> PaletteState {
+        match color_space {
+            // the original two-hue blue palette scored by min neighbor distance
+            ColorSpace::Hsv => PaletteState {
+                color_space,
+                channels: [
+                    ChannelDistribution::Discrete {
+                        values: vec![0.55f32, 0.59f32],
+                        weights: vec![1f32, 1f32],
+                    },
+                    ChannelDistribution::Uniform {
+                        min: 0.5f32,
+                        max: 1.0f32,
+                    },
+                    ChannelDistribution::Uniform {
+                        min: 0.0f32,
+                        max: 1.0f32,
+                    },
+                ],
+                color_pool: None,
+                jitter: 0.0f32,
+                average_weight: 0.0f32,
+            },
+            // every lightness, chroma and hue equally likely
+            ColorSpace::Oklch => PaletteState {
+                color_space,
+                channels: [
+                    ChannelDistribution::Uniform {
+                        min: 0.0f32,
+                        max: 1.0f32,
+                    },
+                    ChannelDistribution::Uniform {
+                        min: 0.0f32,
+                        max: 1.0f32,
+                    },
+                    ChannelDistribution::Uniform {
+                        min: 0.0f32,
+                        max: 1.0f32,
+                    },
+                ],
+                color_pool: None,
+                jitter: 0.0f32,
+                average_weight: 0.0f32,
+            },
+        }
+    }
+
+    // read palette values from the config, inheriting anything missing from the base
+    // the image is the one named in this config section, already decoded by the caller
+    fn from_config(
+        config: &StrictYaml,
+        base: &PaletteState,
+        image_source: Option<&ImageSource>,
+    ) -> PaletteState {
+        let channel_names: [&str; 3] = base.color_space.channel_names();
+        let mut channels: [ChannelDistribution; 3] = base.channels.clone();
+
+        for (channel, channel_name) in channels.iter_mut().zip(channel_names) {
+            *channel = parse_channel(&config[channel_name], channel);
+        }
+
+        // a plain list of hue centers picks one of them with equal chance
+        if let Some(hues) = config["hues"].as_vec() {
+            let values: Vec<f32> = hues.iter().map(parse_config_value::<f32>).collect();
+            if values.is_empty() {
+                panic!("[ERROR] palette hues must not be empty");
+            }
+            channels[base.color_space.hue_channel()] = ChannelDistribution::Discrete {
+                weights: vec![1f32; values.len()],
+                values,
+            };
+        }
+
+        // a palette file replaces the channel distributions with its colors
+        let color_pool: Option<Arc<ColorPool>> = match config["file"].as_str() {
+            Some(path) => {
+                let bytes: Vec<u8> = fs::read(path).expect("[ERROR] unable to read palette file");
+                let color_pool: ColorPool = ColorPool::parse(&bytes).unwrap_or_else(|error| {
+                    panic!("[ERROR] unable to parse palette file {}: {}", path, error)
+                });
+                Some(Arc::new(color_pool))
+            }
+            None => base.color_pool.clone(),
+        };
+
+        // an image in palette mode replaces them with its most representative colors
+        let color_pool: Option<Arc<ColorPool>> = match image_source {
+            Some(image_source) if matches!(image_source.mode, ImageColorMode::Palette) => {
+                Some(Arc::new(image_source.extract_palette()))
+            }
+            _ => color_pool,
+        };
+
+        // the pool's sampler draws offsets from -jitter to jitter
+        let jitter: f32 = parse_optional_config_value(&config["jitter"], base.jitter);
+        if !jitter.is_finite() || jitter < 0f32 {
+            panic!("[ERROR] palette jitter must be a finite number of at least 0");
+        }
+
+        return PaletteState {
+            color_space: base.color_space,
+            channels,
+            color_pool,
+            jitter,
+            average_weight: parse_optional_config_value(
+                &config["average_weight"],
+                base.average_weight,
+            ),
+        };
+    }
+
+    // blend every channel towards another palette, hues take the short way around the wheel
+    fn interpolate(&self, other: &PaletteState, fraction: f32) -> PaletteState {
+        let hue_channel: usize = self.color_space.hue_channel();
+
+        return PaletteState {
+            color_space: self.color_space,
+            channels: [0usize, 1usize, 2usize].map(|channel_index| {
+                self.channels[channel_index].interpolate(
+                    &other.channels[channel_index],
+                    fraction,
+                    channel_index == hue_channel,
+                )
+            }),
+            // a different palette file takes over halfway between keyframes
+            color_pool: if fraction < 0.5f32 {
+                self.color_pool.clone()
+            } else {
+                other.color_pool.clone()
+            },
+            jitter: self.jitter + (other.jitter - self.jitter) * fraction,
+            average_weight: self.average_weight
+                + (other.average_weight - self.average_weight) * fraction,
+        };
+    }
+
+    // generate the next color of this palette
+    fn next_color<R: Rng + ?Sized>(&self, rng: &mut R) -> Rgb<u8> {
+        // draw from the palette file's colors when one is loaded
+        if let Some(color_pool) = &self.color_pool {
+            return Rgb(color_pool.sample(rng, self.jitter));
+        }
+
+        let hue_channel: usize = self.color_space.hue_channel();
+
+        // sample every channel, hues wrap around while the rest stay in range
+        let mut samples: [f32; 3] = [0f32; 3];
+        for (channel_index, channel) in self.channels.iter().enumerate() {
+            samples[channel_index] =
+                fit_to_channel(channel.sample(rng), channel_index == hue_channel);
+        }
+
+        let temp_color: Srgb = match self.color_space {
+            ColorSpace::Hsv => Hsv::new(samples[0] * 360f32, samples[1], samples[2])
+                .try_into_color()
+                .unwrap(),
+            // lightness, chroma and hue may fall outside of sRGB, clamped below
+            ColorSpace::Oklch => Oklch::new(
+                samples[0],
+                samples[1] * OKLCH_MAX_CHROMA,
+                samples[2] * 360f32,
+            )
+            .into_color_unclamped(),
+        };
+
+        return Rgb([
+            (temp_color.red.clamp(0f32, 1f32) * 255f32).floor() as u8,
+            (temp_color.green.clamp(0f32, 1f32) * 255f32).floor() as u8,
+            (temp_color.blue.clamp(0f32, 1f32) * 255f32).floor() as u8,
+        ]);
+    }
+}
+impl Default for PaletteState {
+    fn default() -> Self {
+        PaletteState::default_for(ColorSpace::Hsv)
+    }
+}
+
+// a channel is either a distribution spec string or a legacy min/max range
+fn parse_channel(config: &StrictYaml, base: &ChannelDistribution) -> ChannelDistribution {
+    if let Some(spec) = config.as_str() {
+        return ChannelDistribution::parse(spec)
+            .unwrap_or_else(|error| panic!("[ERROR] invalid palette distribution: {}", error));
+    }
+    if config["min"].is_badvalue() && config["max"].is_badvalue() {
+        return base.clone();
+    }
+
+    // a min/max range samples uniformly, inheriting a missing bound from a uniform base
+    let (base_min, base_max) = match base {
+        ChannelDistribution::Uniform { min, max } => (*min, *max),
+        _ => (0f32, 1f32),
+    };
+    let distribution: ChannelDistribution = ChannelDistribution::Uniform {
+        min: parse_optional_config_value(&config["min"], base_min),
+        max: parse_optional_config_value(&config["max"], base_max),
+    };
+    distribution
+        .validate()
+        .unwrap_or_else(|error| panic!("[ERROR] invalid palette range: {}", error));
+    return distribution;
+}
+
+// keyframe positions are either a pixel count ("12000") or a percentage ("40%")
+fn parse_keyframe_position(value: &StrictYaml, total_pixel_count: u64) -> u64 {
+    let position: &str = value
+        .as_str()
+        .expect("[ERROR] palette keyframes require an \"at\" position");
+
+    match position.strip_suffix('%') {
+        Some(percentage) => {
+            let percentage: f64 = percentage
+                .trim()
+                .parse::<f64>()
+                .expect("[ERROR] failed to convert keyframe percentage to float");
+            return (percentage / 100f64 * total_pixel_count as f64).round() as u64;
+        }
+        None => {
+            return position
+                .trim()
+                .parse::<u64>()
+                .expect("[ERROR] failed to convert keyframe position to int");
+        }
+    }
+}
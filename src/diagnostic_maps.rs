@@ -0,0 +1,299 @@
+use bytemuck::Pod;
+use image::Rgb;
+use palette::{FromColor, Hsv, Srgb};
+
+use crate::cell::Cell;
+use crate::tiled_canvas::TiledCanvas;
+use crate::Coordinate;
+
+// which per cell records a run keeps, each only when an output map or the engine reads it
+#[derive(Copy, Clone, Default)]
+pub(crate) struct RecordedMaps {
+    // the placement order and age maps
+    pub(crate) placement_order: bool,
+    // the score map
+    pub(crate) chosen_scores: bool,
+    // the ownership map and the seed distance term
+    pub(crate) seed_owners: bool,
+    // the frontier age term and the cell selections that prefer older cells
+    pub(crate) boundry_since: bool,
+}
+impl RecordedMaps {
+    // every record either side needs
+    pub(crate) fn union(self, other: RecordedMaps) -> RecordedMaps {
+        RecordedMaps {
+            placement_order: self.placement_order || other.placement_order,
+            chosen_scores: self.chosen_scores || other.chosen_scores,
+            seed_owners: self.seed_owners || other.seed_owners,
+            boundry_since: self.boundry_since || other.boundry_since,
+        }
+    }
+}
+
+// per cell records of how the painting grew, for the auxiliary output maps
+// the maps are read back a row at a time, so writing them never copies a whole map
+// the scoring terms read the seed ownership and boundry ages as well
+// stored as tiles like the painting, so only the grown part of a huge canvas costs memory,
+// and a record nothing reads is not kept at all
+pub(crate) struct DiagnosticMaps {
+    width: u32,
+    // 1-based placement index of each cell, 0 while unpainted
+    placement_order: Option<TiledCanvas<u32>>,
+    // the score the cell won its color with, seeds score 0
+    chosen_scores: Option<TiledCanvas<f32>>,
+    // 1-based index of the seed whose growth reached the cell, 0 while unclaimed
+    // boundry cells are claimed by the first seed to reach them
+    seed_owners: Option<TiledCanvas<u32>>,
+    // starting location of every seed, by owner id - 1
+    seed_positions: Vec<Coordinate>,
+    // placement count at the time a cell joined a boundry region
+    boundry_since: Option<TiledCanvas<u32>>,
+}
+impl DiagnosticMaps {
+    // the requested maps in memory, or in scratch files in the given directory
+    pub(crate) fn new(
+        width: u32,
+        height: u32,
+        mapped_directory: Option<&str>,
+        recorded_maps: RecordedMaps,
+    ) -> DiagnosticMaps {
+        DiagnosticMaps {
+            width,
+            placement_order: create_map(
+                recorded_maps.placement_order,
+                width,
+                height,
+                mapped_directory,
+                "order",
+            ),
+            chosen_scores: create_map(
+                recorded_maps.chosen_scores,
+                width,
+                height,
+                mapped_directory,
+                "scores",
+            ),
+            seed_owners: create_map(
+                recorded_maps.seed_owners,
+                width,
+                height,
+                mapped_directory,
+                "owners",
+            ),
+            seed_positions: Vec::new(),
+            boundry_since: create_map(
+                recorded_maps.boundry_since,
+                width,
+                height,
+                mapped_directory,
+                "boundry_since",
+            ),
+        }
+    }
+
+    pub(crate) fn width(&self) -> u32 {
+        self.width
+    }
+
+    // give a starting location its own owner id
+    pub(crate) fn claim_seed(&mut self, position: &Coordinate) {
+        self.seed_positions.push(*position);
+        if let Some(seed_owners) = &mut self.seed_owners {
+            seed_owners.put(position.x, position.y, self.seed_positions.len() as u32);
+        }
+    }
+
+    // a boundry cell belongs to the seed of the painted cell that reached it first
+    // and joins the boundry at the given placement count
+    pub(crate) fn claim_boundry(
+        &mut self,
+        position: &Coordinate,
+        painted_from: &Coordinate,
+        placement_count: u64,
+    ) {
+        if let Some(seed_owners) = &mut self.seed_owners {
+            if seed_owners.get(position.x, position.y) == 0 {
+                let owner: u32 = seed_owners.get(painted_from.x, painted_from.y);
+                seed_owners.put(position.x, position.y, owner);
+            }
+        }
+        if let Some(boundry_since) = &mut self.boundry_since {
+            boundry_since.put(
+                position.x,
+                position.y,
+                placement_count.min(u32::MAX as u64) as u32,
+            );
+        }
+    }
+
+    // the starting location of the seed a cell belongs to
+    pub(crate) fn seed_position(&self, position: &Coordinate) -> Option<Coordinate> {
+        let owner: u32 = recorded(&self.seed_owners, "seed ownership").get(position.x, position.y);
+        if owner == 0 {
+            return None;
+        }
+        Some(self.seed_positions[owner as usize - 1])
+    }
+
+    // the placement count at which a boundry cell joined its boundry region
+    pub(crate) fn boundry_since(&self, position: &Coordinate) -> u64 {
+        recorded(&self.boundry_since, "boundry age").get(position.x, position.y) as u64
+    }
+
+    // record the placement of a cell as the `placement_count`-th of the painting
+    pub(crate) fn record_placement(
+        &mut self,
+        position: &Coordinate,
+        placement_count: u64,
+        chosen_score: f32,
+    ) {
+        if let Some(placement_order) = &mut self.placement_order {
+            placement_order.put(
+                position.x,
+                position.y,
+                placement_count.min(u32::MAX as u64) as u32,
+            );
+        }
+        if let Some(chosen_scores) = &mut self.chosen_scores {
+            chosen_scores.put(position.x, position.y, chosen_score);
+        }
+    }
+
+    // exact placement order of row y, 0 stays unpainted
+    pub(crate) fn placement_order_row(&self, y: u32, orders: &mut Vec<u32>) {
+        recorded(&self.placement_order, "placement order").read_row(y, orders);
+    }
+
+    // the highest placement order, which the 16 bit order map scales to its top
+    pub(crate) fn last_placement(&self, height: u32) -> u32 {
+        let mut orders: Vec<u32> = Vec::new();
+        let mut last_order: u32 = 0u32;
+        for y in 0..height {
+            self.placement_order_row(y, &mut orders);
+            last_order = orders.iter().copied().fold(last_order, u32::max);
+        }
+        last_order
+    }
+
+    // placement order of row y scaled to fill 16 bits, 0 stays unpainted
+    pub(crate) fn placement_order_16_row(&self, y: u32, last_order: u32, samples: &mut Vec<u16>) {
+        let mut orders: Vec<u32> = Vec::new();
+        self.placement_order_row(y, &mut orders);
+        samples.clear();
+        samples.extend(orders.iter().map(|order| {
+            if last_order <= u16::MAX as u32 {
+                *order as u16
+            } else {
+                (*order as u64 * u16::MAX as u64).div_ceil(last_order as u64) as u16
+            }
+        }));
+    }
+
+    // how long ago each cell of row y was painted, oldest cells brightest
+    pub(crate) fn age_row(&self, y: u32, placement_count: u64, colors: &mut Vec<Rgb<u8>>) {
+        let mut orders: Vec<u32> = Vec::new();
+        self.placement_order_row(y, &mut orders);
+        colors.clear();
+        colors.extend(orders.iter().map(|order| {
+            if *order == 0 || placement_count == 0 {
+                return Rgb([0u8, 0u8, 0u8]);
+            }
+            let age: f32 = (placement_count - *order as u64) as f32 / placement_count as f32;
+            heat_color(age)
+        }));
+    }
+
+    // the lowest and highest score a painted cell was chosen with
+    pub(crate) fn score_range(&self, canvas: &TiledCanvas<Cell>) -> (f32, f32) {
+        let chosen_scores: &TiledCanvas<f32> = recorded(&self.chosen_scores, "score");
+        let mut cells: Vec<Cell> = Vec::new();
+        let mut scores: Vec<f32> = Vec::new();
+        let mut range: (f32, f32) = (f32::MAX, f32::MIN);
+        for y in 0..canvas.height() {
+            canvas.read_row(y, &mut cells);
+            chosen_scores.read_row(y, &mut scores);
+            for (cell, score) in cells.iter().zip(scores.iter()) {
+                if cell.is_painted() && score.is_finite() {
+                    range = (range.0.min(*score), range.1.max(*score));
+                }
+            }
+        }
+        if range.0 > range.1 {
+            return (0f32, 0f32);
+        }
+        range
+    }
+
+    // the score each cell of row y was chosen with, from black for the lowest score
+    // in the range to white for the highest, unpainted cells of the row's `cells` stay black
+    pub(crate) fn score_row(
+        &self,
+        y: u32,
+        range: (f32, f32),
+        cells: &[Cell],
+        samples: &mut Vec<u16>,
+    ) {
+        let mut scores: Vec<f32> = Vec::new();
+        recorded(&self.chosen_scores, "score").read_row(y, &mut scores);
+        let (lowest, highest) = range;
+        samples.clear();
+        samples.extend(cells.iter().zip(scores.iter()).map(|(cell, score)| {
+            if !cell.is_painted() || highest <= lowest {
+                return 0u16;
+            }
+            let normalized: f32 = ((score - lowest) / (highest - lowest)).clamp(0f32, 1f32);
+            (normalized * u16::MAX as f32).round() as u16
+        }));
+    }
+
+    // each seed's territory in row y in its own color, only cells painted in the row's `cells`
+    pub(crate) fn seed_ownership_row(&self, y: u32, cells: &[Cell], colors: &mut Vec<Rgb<u8>>) {
+        let mut owners: Vec<u32> = Vec::new();
+        recorded(&self.seed_owners, "seed ownership").read_row(y, &mut owners);
+        colors.clear();
+        colors.extend(cells.iter().zip(owners.iter()).map(|(cell, owner)| {
+            if !cell.is_painted() {
+                return Rgb([0u8, 0u8, 0u8]);
+            }
+            owner_color(*owner)
+        }));
+    }
+}
+
+// a map in memory or mapped storage, None when the run does not record it
+fn create_map<T: Pod>(
+    enabled: bool,
+    width: u32,
+    height: u32,
+    mapped_directory: Option<&str>,
+    name: &str,
+) -> Option<TiledCanvas<T>> {
+    if !enabled {
+        return None;
+    }
+    Some(TiledCanvas::create(width, height, mapped_directory, name))
+}
+
+// a map the run's settings asked for, so it was created with the others
+fn recorded<'a, T: Pod>(map: &'a Option<TiledCanvas<T>>, name: &str) -> &'a TiledCanvas<T> {
+    map.as_ref()
+        .unwrap_or_else(|| panic!("[ERROR] the {} map is not recorded", name))
+}
+
+// black through red and yellow to white
+fn heat_color(value: f32) -> Rgb<u8> {
+    let scaled: f32 = value.clamp(0f32, 1f32) * 3f32;
+    let channel = |offset: f32| ((scaled - offset).clamp(0f32, 1f32) * 255f32).round() as u8;
+    Rgb([channel(0f32), channel(1f32), channel(2f32)])
+}
+
+// spread owner hues by the golden angle so neighboring seed ids look different
+fn owner_color(owner: u32) -> Rgb<u8> {
+    if owner == 0 {
+        return Rgb([128u8, 128u8, 128u8]);
+    }
+    let hue: f32 = (owner as f32 * 137.507_77f32) % 360f32;
+    let rgb: Srgb = Srgb::from_color(Hsv::new(hue, 0.75f32, 0.95f32));
+    let rgb: Srgb<u8> = rgb.into_format();
+    Rgb([rgb.red, rgb.green, rgb.blue])
+}
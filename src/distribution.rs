@@ -0,0 +1,541 @@
+use rand::Rng;
+use rand_distr::{Beta, Distribution, Normal};
+
+// attempts made to land a truncated normal sample inside its bounds before clamping
+const TRUNCATED_NORMAL_MAX_ATTEMPTS: u32 = 64;
+
+// a random distribution for a single color channel
+// samples are fractions of the channel's range (0.0 to 1.0)
+#[derive(Clone, PartialEq)]
+pub(crate) enum ChannelDistribution {
+    // every value between min and max is equally likely
+    Uniform {
+        min: f32,
+        max: f32,
+    },
+    // bell curve around the mean, clamped into the channel's range
+    Normal {
+        mean: f32,
+        std_dev: f32,
+    },
+    // bell curve around the mean, re-drawn until it lands between min and max
+    TruncatedNormal {
+        mean: f32,
+        std_dev: f32,
+        min: f32,
+        max: f32,
+    },
+    // beta(alpha, beta) distribution scaled onto min..max
+    Beta {
+        alpha: f32,
+        beta: f32,
+        min: f32,
+        max: f32,
+    },
+    // one of a fixed set of values, chosen by relative weight
+    Discrete {
+        values: Vec<f32>,
+        weights: Vec<f32>,
+    },
+}
+impl ChannelDistribution {
+    // parse a distribution spec such as "normal mean=0.5 std_dev=0.1"
+    // discrete specs take comma separated lists: "discrete values=0.55,0.59 weights=3,1"
+    pub(crate) fn parse(spec: &str) -> Result<ChannelDistribution, String> {
+        let mut words = spec.split_whitespace();
+        let kind: &str = words
+            .next()
+            .ok_or_else(|| String::from("empty distribution spec"))?;
+
+        // collect the key=value pairs following the distribution kind
+        let mut parameters: Vec<(&str, &str)> = Vec::new();
+        for word in words {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or_else(|| format!("expected key=value, found \"{}\"", word))?;
+            parameters.push((key, value));
+        }
+        let lookup = |key: &str| -> Option<&str> {
+            parameters
+                .iter()
+                .find(|(parameter_key, _)| *parameter_key == key)
+                .map(|(_, value)| *value)
+        };
+        let number = |key: &str, default: Option<f32>| -> Result<f32, String> {
+            match lookup(key) {
+                Some(value) => value
+                    .parse::<f32>()
+                    .map_err(|_| format!("\"{}\" is not a number for {}", value, key)),
+                None => default.ok_or_else(|| format!("{} requires {}", kind, key)),
+            }
+        };
+        let list = |key: &str| -> Result<Option<Vec<f32>>, String> {
+            match lookup(key) {
+                Some(value) => value
+                    .split(',')
+                    .map(|item| {
+                        item.trim()
+                            .parse::<f32>()
+                            .map_err(|_| format!("\"{}\" is not a number for {}", item, key))
+                    })
+                    .collect::<Result<Vec<f32>, String>>()
+                    .map(Some),
+                None => Ok(None),
+            }
+        };
+
+        let distribution: ChannelDistribution = match kind {
+            "uniform" => ChannelDistribution::Uniform {
+                min: number("min", Some(0f32))?,
+                max: number("max", Some(1f32))?,
+            },
+            "normal" => ChannelDistribution::Normal {
+                mean: number("mean", None)?,
+                std_dev: number("std_dev", None)?,
+            },
+            "truncated_normal" => ChannelDistribution::TruncatedNormal {
+                mean: number("mean", None)?,
+                std_dev: number("std_dev", None)?,
+                min: number("min", Some(0f32))?,
+                max: number("max", Some(1f32))?,
+            },
+            "beta" => ChannelDistribution::Beta {
+                alpha: number("alpha", None)?,
+                beta: number("beta", None)?,
+                min: number("min", Some(0f32))?,
+                max: number("max", Some(1f32))?,
+            },
+            "discrete" => {
+                let values: Vec<f32> =
+                    list("values")?.ok_or_else(|| String::from("discrete requires values"))?;
+                // without weights every value is equally likely
+                let weights: Vec<f32> =
+                    list("weights")?.unwrap_or_else(|| vec![1f32; values.len()]);
+                ChannelDistribution::Discrete { values, weights }
+            }
+            other => return Err(format!("unknown distribution \"{}\"", other)),
+        };
+
+        distribution.validate()?;
+        return Ok(distribution);
+    }
+
+    // reject parameters the samplers cannot work with
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        // infinite parameters pass the range checks below but make the samplers panic
+        if !self
+            .parameters()
+            .iter()
+            .all(|parameter| parameter.is_finite())
+        {
+            return Err(String::from(
+                "distribution parameters must be finite numbers",
+            ));
+        }
+        match self {
+            ChannelDistribution::Uniform { min, max } => {
+                if min > max {
+                    return Err(String::from("uniform min must not exceed max"));
+                }
+            }
+            ChannelDistribution::Normal { std_dev, .. } => {
+                if *std_dev < 0f32 {
+                    return Err(String::from("normal std_dev must not be negative"));
+                }
+            }
+            ChannelDistribution::TruncatedNormal {
+                std_dev, min, max, ..
+            } => {
+                if *std_dev < 0f32 || min > max {
+                    return Err(String::from(
+                        "truncated_normal needs std_dev >= 0 and min <= max",
+                    ));
+                }
+            }
+            ChannelDistribution::Beta {
+                alpha,
+                beta,
+                min,
+                max,
+            } => {
+                if *alpha <= 0f32 || *beta <= 0f32 || min > max {
+                    return Err(String::from(
+                        "beta needs positive alpha and beta and min <= max",
+                    ));
+                }
+            }
+            ChannelDistribution::Discrete { values, weights } => {
+                if values.is_empty() || values.len() != weights.len() {
+                    return Err(String::from(
+                        "discrete needs one weight per value and at least one value",
+                    ));
+                }
+                if weights.iter().any(|weight| *weight < 0f32)
+                    || weights.iter().sum::<f32>() <= 0f32
+                {
+                    return Err(String::from(
+                        "discrete weights must be non-negative and not all zero",
+                    ));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // every number of the distribution, for the checks all kinds share
+    fn parameters(&self) -> Vec<f32> {
+        match self {
+            ChannelDistribution::Uniform { min, max } => vec![*min, *max],
+            ChannelDistribution::Normal { mean, std_dev } => vec![*mean, *std_dev],
+            ChannelDistribution::TruncatedNormal {
+                mean,
+                std_dev,
+                min,
+                max,
+            } => vec![*mean, *std_dev, *min, *max],
+            ChannelDistribution::Beta {
+                alpha,
+                beta,
+                min,
+                max,
+            } => vec![*alpha, *beta, *min, *max],
+            ChannelDistribution::Discrete { values, weights } => {
+                values.iter().chain(weights.iter()).copied().collect()
+            }
+        }
+    }
+
+    // draw one sample
+    pub(crate) fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> f32 {
+        match self {
+            ChannelDistribution::Uniform { min, max } => {
+                return min + (max - min) * rng.gen::<f32>();
+            }
+            ChannelDistribution::Normal { mean, std_dev } => {
+                return Normal::new(*mean, *std_dev).unwrap().sample(rng);
+            }
+            ChannelDistribution::TruncatedNormal {
+                mean,
+                std_dev,
+                min,
+                max,
+            } => {
+                let normal: Normal<f32> = Normal::new(*mean, *std_dev).unwrap();
+                let mut value: f32 = *mean;
+                for _ in 0..TRUNCATED_NORMAL_MAX_ATTEMPTS {
+                    value = normal.sample(rng);
+                    if value >= *min && value <= *max {
+                        return value;
+                    }
+                }
+                // bounds far out in the tails would otherwise never terminate
+                return value.clamp(*min, *max);
+            }
+            ChannelDistribution::Beta {
+                alpha,
+                beta,
+                min,
+                max,
+            } => {
+                let fraction: f32 = Beta::new(*alpha, *beta).unwrap().sample(rng);
+                return min + (max - min) * fraction;
+            }
+            ChannelDistribution::Discrete { values, weights } => {
+                // walk the weights until the random draw is used up
+                let total_weight: f32 = weights.iter().sum();
+                let mut draw: f32 = rng.gen::<f32>() * total_weight;
+                for (value, weight) in values.iter().zip(weights.iter()) {
+                    if draw < *weight {
+                        return *value;
+                    }
+                    draw -= weight;
+                }
+                return values[values.len() - 1];
+            }
+        }
+    }
+
+    // blend towards another distribution of the same kind
+    // distributions of different kinds switch over halfway between keyframes
+    // discrete lists of different lengths fade from one list to the other instead
+    // circular channels (hues) interpolate the short way around the wheel
+    pub(crate) fn interpolate(
+        &self,
+        other: &ChannelDistribution,
+        fraction: f32,
+        circular: bool,
+    ) -> ChannelDistribution {
+        let lerp = |a: f32, b: f32| {
+            if circular {
+                let difference: f32 = (b - a + 0.5f32).rem_euclid(1f32) - 0.5f32;
+                return a + difference * fraction;
+            }
+            return a + (b - a) * fraction;
+        };
+
+        match (self, other) {
+            (
+                ChannelDistribution::Uniform { min, max },
+                ChannelDistribution::Uniform {
+                    min: other_min,
+                    max: other_max,
+                },
+            ) => ChannelDistribution::Uniform {
+                min: lerp(*min, *other_min),
+                max: lerp(*max, *other_max),
+            },
+            (
+                ChannelDistribution::Normal { mean, std_dev },
+                ChannelDistribution::Normal {
+                    mean: other_mean,
+                    std_dev: other_std_dev,
+                },
+            ) => ChannelDistribution::Normal {
+                mean: lerp(*mean, *other_mean),
+                std_dev: std_dev + (other_std_dev - std_dev) * fraction,
+            },
+            (
+                ChannelDistribution::TruncatedNormal {
+                    mean,
+                    std_dev,
+                    min,
+                    max,
+                },
+                ChannelDistribution::TruncatedNormal {
+                    mean: other_mean,
+                    std_dev: other_std_dev,
+                    min: other_min,
+                    max: other_max,
+                },
+            ) => ChannelDistribution::TruncatedNormal {
+                mean: lerp(*mean, *other_mean),
+                std_dev: std_dev + (other_std_dev - std_dev) * fraction,
+                min: lerp(*min, *other_min),
+                max: lerp(*max, *other_max),
+            },
+            (
+                ChannelDistribution::Beta {
+                    alpha,
+                    beta,
+                    min,
+                    max,
+                },
+                ChannelDistribution::Beta {
+                    alpha: other_alpha,
+                    beta: other_beta,
+                    min: other_min,
+                    max: other_max,
+                },
+            ) => ChannelDistribution::Beta {
+                alpha: alpha + (other_alpha - alpha) * fraction,
+                beta: beta + (other_beta - beta) * fraction,
+                min: lerp(*min, *other_min),
+                max: lerp(*max, *other_max),
+            },
+            (
+                ChannelDistribution::Discrete { values, weights },
+                ChannelDistribution::Discrete {
+                    values: other_values,
+                    weights: other_weights,
+                },
+            ) if values.len() == other_values.len() => ChannelDistribution::Discrete {
+                values: values
+                    .iter()
+                    .zip(other_values.iter())
+                    .map(|(a, b)| lerp(*a, *b))
+                    .collect(),
+                weights: weights
+                    .iter()
+                    .zip(other_weights.iter())
+                    .map(|(a, b)| a + (b - a) * fraction)
+                    .collect(),
+            },
+            // lists of different lengths have no value to value pairing,
+            // so both lists are kept and the chance moves from one to the other
+            (
+                ChannelDistribution::Discrete { values, weights },
+                ChannelDistribution::Discrete {
+                    values: other_values,
+                    weights: other_weights,
+                },
+            ) => {
+                let total_weight: f32 = weights.iter().sum();
+                let other_total_weight: f32 = other_weights.iter().sum();
+                ChannelDistribution::Discrete {
+                    values: values.iter().chain(other_values.iter()).copied().collect(),
+                    weights: weights
+                        .iter()
+                        .map(|weight| weight / total_weight * (1f32 - fraction))
+                        .chain(
+                            other_weights
+                                .iter()
+                                .map(|weight| weight / other_total_weight * fraction),
+                        )
+                        .collect(),
+                }
+            }
+            _ => {
+                if fraction < 0.5f32 {
+                    self.clone()
+                } else {
+                    other.clone()
+                }
+            }
+        }
+    }
+
+    // count samples into equally sized bins over 0.0 to 1.0
+    // used to preview the shape of a distribution, samples are fitted like the palettes fit them
+    pub(crate) fn histogram<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        sample_count: u32,
+        bin_count: usize,
+        is_hue: bool,
+    ) -> Vec<u32> {
+        let mut bins: Vec<u32> = vec![0u32; bin_count];
+        for _ in 0..sample_count {
+            let value: f32 = fit_to_channel(self.sample(rng), is_hue);
+            let bin_index: usize = ((value * bin_count as f32) as usize).min(bin_count - 1);
+            bins[bin_index] += 1;
+        }
+        return bins;
+    }
+}
+
+// bring a sample into a channel's 0.0 to 1.0 range, hues wrap around while the rest are clamped
+pub(crate) fn fit_to_channel(sample: f32, is_hue: bool) -> f32 {
+    if is_hue {
+        return sample.rem_euclid(1f32);
+    }
+    return sample.clamp(0f32, 1f32);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn parses_every_kind_and_its_defaults() {
+        assert!(
+            ChannelDistribution::parse("uniform max=0.5")
+                == Ok(ChannelDistribution::Uniform {
+                    min: 0f32,
+                    max: 0.5f32
+                })
+        );
+        assert!(
+            ChannelDistribution::parse("discrete values=0.2,0.8")
+                == Ok(ChannelDistribution::Discrete {
+                    values: vec![0.2f32, 0.8f32],
+                    weights: vec![1f32, 1f32],
+                })
+        );
+        for spec in [
+            "normal mean=0.5 std_dev=0.1",
+            "truncated_normal mean=0.5 std_dev=0.2 min=0.4 max=0.6",
+            "beta alpha=2 beta=5",
+        ] {
+            assert!(
+                ChannelDistribution::parse(spec).is_ok(),
+                "rejected {}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        for spec in [
+            "",
+            "gaussian mean=0.5",
+            "normal mean=0.5",
+            "uniform min=low",
+            "uniform min=0.8 max=0.2",
+            "normal mean=0.5 std_dev=inf",
+            "beta alpha=0 beta=1",
+            "discrete values=0.1,0.2 weights=1",
+            "discrete values=0.1 weights=-1",
+        ] {
+            assert!(
+                ChannelDistribution::parse(spec).is_err(),
+                "accepted {}",
+                spec
+            );
+        }
+    }
+
+    #[test]
+    fn samples_stay_within_their_bounds() {
+        let mut rng: StdRng = StdRng::seed_from_u64(3u64);
+        for spec in [
+            "uniform min=0.2 max=0.4",
+            "truncated_normal mean=0.3 std_dev=1 min=0.2 max=0.4",
+            "beta alpha=2 beta=2 min=0.2 max=0.4",
+        ] {
+            let distribution: ChannelDistribution = ChannelDistribution::parse(spec).unwrap();
+            for _ in 0..1000 {
+                let sample: f32 = distribution.sample(&mut rng);
+                assert!(
+                    (0.2f32..=0.4f32).contains(&sample),
+                    "{} drew {}",
+                    spec,
+                    sample
+                );
+            }
+        }
+        let discrete: ChannelDistribution =
+            ChannelDistribution::parse("discrete values=0.1,0.9 weights=1,0").unwrap();
+        assert!((0..100).all(|_| discrete.sample(&mut rng) == 0.1f32));
+    }
+
+    #[test]
+    fn histogram_wraps_hues_and_clamps_the_rest() {
+        let distribution: ChannelDistribution =
+            ChannelDistribution::parse("uniform min=0.75 max=1.25").unwrap();
+        let mut rng: StdRng = StdRng::seed_from_u64(5u64);
+        let hue_bins: Vec<u32> = distribution.histogram(&mut rng, 4000u32, 4usize, true);
+        let clamped_bins: Vec<u32> = distribution.histogram(&mut rng, 4000u32, 4usize, false);
+        // half of the samples lie above 1, they wrap into the first bin or pile up in the last
+        assert!(hue_bins[0] > 1800u32 && hue_bins[3] > 1800u32);
+        assert_eq!(clamped_bins[0], 0u32);
+        assert_eq!(clamped_bins[3], 4000u32);
+    }
+
+    #[test]
+    fn interpolates_hues_the_short_way_around() {
+        let from: ChannelDistribution =
+            ChannelDistribution::parse("uniform min=0.9 max=0.9").unwrap();
+        let to: ChannelDistribution =
+            ChannelDistribution::parse("uniform min=0.1 max=0.1").unwrap();
+        let ChannelDistribution::Uniform { min, .. } = from.interpolate(&to, 0.5f32, true) else {
+            panic!("uniform distributions interpolate to a uniform distribution");
+        };
+        // 0.9 to 0.1 passes through 1.0, not through 0.5
+        assert!((min - 1f32).abs() < 1e-5);
+        let ChannelDistribution::Uniform { min, .. } = from.interpolate(&to, 0.5f32, false) else {
+            panic!("uniform distributions interpolate to a uniform distribution");
+        };
+        assert!((min - 0.5f32).abs() < 1e-5);
+    }
+
+    #[test]
+    fn fades_between_discrete_lists_of_different_lengths() {
+        let from: ChannelDistribution =
+            ChannelDistribution::parse("discrete values=0.0,0.3").unwrap();
+        let to: ChannelDistribution = ChannelDistribution::parse("discrete values=0.6").unwrap();
+        let ChannelDistribution::Discrete { values, weights } =
+            from.interpolate(&to, 0.25f32, true)
+        else {
+            panic!("discrete distributions interpolate to a discrete distribution");
+        };
+        assert_eq!(values, vec![0.0f32, 0.3f32, 0.6f32]);
+        assert_eq!(weights, vec![0.375f32, 0.375f32, 0.25f32]);
+
+        // at the keyframe itself only the next list is drawn from
+        let mut rng: StdRng = StdRng::seed_from_u64(9u64);
+        let reached: ChannelDistribution = from.interpolate(&to, 1f32, true);
+        assert!((0..100).all(|_| reached.sample(&mut rng) == 0.6f32));
+    }
+}
@@ -0,0 +1,155 @@
+use rand::thread_rng;
+// needed to get the reference to HtmlCanvasElement
+use wasm_bindgen::JsCast;
+
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, HtmlInputElement};
+use yew::events::TargetCast;
+use yew::{html, Component, Context, Html, NodeRef};
+use yew_bootstrap::component::Button;
+use yew_bootstrap::util::Color;
+
+use crate::distribution::ChannelDistribution;
+
+// histogram layout
+const PREVIEW_WIDTH: u32 = 512;
+const PREVIEW_HEIGHT: u32 = 128;
+const PREVIEW_BIN_COUNT: usize = 64;
+const PREVIEW_SAMPLE_COUNT: u32 = 20000;
+
+// yew messages
+pub(crate) enum Msg {
+    SpecInput(String),
+    ToggleHue,
+    Preview,
+}
+
+// yew sub-component that draws a histogram of a channel distribution
+pub(crate) struct DistributionPreview {
+    node_ref: NodeRef,
+    spec_input: String,
+    // hue channels wrap around instead of being clamped
+    is_hue: bool,
+    error_message: Option<String>,
+}
+impl DistributionPreview {
+    // sample the distribution and draw the bins onto the preview canvas
+    fn render_histogram(&self, distribution: &ChannelDistribution) {
+        // if node_ref can be cast as HtmlCanvasElement then render the histogram
+        let Some(canvas_ref) = self.node_ref.cast::<HtmlCanvasElement>() else { return };
+
+        // get canvas context
+        let canvas_2d: CanvasRenderingContext2d = canvas_ref
+            .get_context("2d")
+            .unwrap()
+            .unwrap()
+            .dyn_into::<CanvasRenderingContext2d>()
+            .unwrap();
+
+        let bins: Vec<u32> = distribution.histogram(
+            &mut thread_rng(),
+            PREVIEW_SAMPLE_COUNT,
+            PREVIEW_BIN_COUNT,
+            self.is_hue,
+        );
+        let tallest_bin: u32 = bins.iter().copied().max().unwrap_or(1u32).max(1u32);
+        let bin_width: f64 = PREVIEW_WIDTH as f64 / PREVIEW_BIN_COUNT as f64;
+
+        // clear the previous histogram
+        canvas_2d.set_fill_style_str("#202020");
+        canvas_2d.fill_rect(0.0, 0.0, PREVIEW_WIDTH as f64, PREVIEW_HEIGHT as f64);
+
+        // draw one bar per bin, scaled to the tallest bin
+        canvas_2d.set_fill_style_str("#d0d0d0");
+        for (bin_index, bin_count) in bins.iter().enumerate() {
+            let bar_height: f64 =
+                PREVIEW_HEIGHT as f64 * (*bin_count as f64 / tallest_bin as f64);
+            canvas_2d.fill_rect(
+                bin_index as f64 * bin_width,
+                PREVIEW_HEIGHT as f64 - bar_height,
+                bin_width - 1.0,
+                bar_height,
+            );
+        }
+    }
+    fn preview(&mut self) {
+        match ChannelDistribution::parse(&self.spec_input) {
+            Ok(distribution) => {
+                self.error_message = None;
+                self.render_histogram(&distribution);
+            }
+            Err(error) => {
+                self.error_message = Some(error);
+            }
+        }
+    }
+}
+impl Component for DistributionPreview {
+    type Message = Msg;
+    type Properties = ();
+
+    // DistributionPreview init
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            node_ref: NodeRef::default(),
+            spec_input: String::from("uniform min=0.5 max=1.0"),
+            is_hue: false,
+            error_message: None,
+        }
+    }
+
+    // DistributionPreview update logic
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::SpecInput(spec) => {
+                self.spec_input = spec;
+                false
+            }
+            Msg::ToggleHue => {
+                self.is_hue = !self.is_hue;
+                self.preview();
+                true
+            }
+            Msg::Preview => {
+                self.preview();
+                true
+            }
+        }
+    }
+
+    // DistributionPreview view logic
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let spec_input_callback: yew::Callback<web_sys::InputEvent> =
+            ctx.link().callback(|event: web_sys::InputEvent| {
+                Msg::SpecInput(event.target_unchecked_into::<HtmlInputElement>().value())
+            });
+        let preview_button_callback: yew::Callback<web_sys::MouseEvent> =
+            ctx.link().callback(|_| Msg::Preview);
+        let hue_button_callback: yew::Callback<web_sys::MouseEvent> =
+            ctx.link().callback(|_| Msg::ToggleHue);
+        let hue_button_text: &str = if self.is_hue {
+            "Hue Channel: Wraps"
+        } else {
+            "Other Channel: Clamps"
+        };
+
+        html! {
+            <div>
+                <div class="centered-div">
+                    <input type="text" class="text-input distribution-input" value={self.spec_input.clone()} oninput={spec_input_callback} />
+                    <Button onclick={preview_button_callback} style={Color::Dark} class="centered-button" text={"Preview Distribution"} />
+                    <Button onclick={hue_button_callback} style={Color::Dark} class="centered-button" text={hue_button_text} />
+                </div>
+                if let Some(error_message) = &self.error_message {
+                    <div class="centered-div error-text">{error_message}</div>
+                }
+                <div class="centered-div">
+                    <canvas
+                        width={PREVIEW_WIDTH.to_string()}
+                        height={PREVIEW_HEIGHT.to_string()}
+                        ref={self.node_ref.clone()}
+                    ></canvas>
+                </div>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use image::RgbImage;
+use std::f32::consts::TAU;
+use strict_yaml_rust::StrictYaml;
+
+use crate::{parse_config_value, parse_optional_config_value, tie_breaker, Constraints};
+
+// a direction for every cell, growth along it is cheaper than growth against it
+pub(crate) struct FlowField {
+    width: u32,
+    height: u32,
+    directions: FieldDirections,
+}
+
+// how the direction of a cell is found, every kind is computed when a cell is scored
+enum FieldDirections {
+    // the same unit vector everywhere
+    Constant([f32; 2]),
+    // away from the center, or towards it with a direction of -1
+    Radial { center: [f32; 2], direction: f32 },
+    // red and green encode the x and y components, stretched over the canvas
+    Image(RgbImage),
+    // value noise turned into an angle, `scale` is the size of a swirl in cells
+    Noise { seed: u64, scale: f32 },
+}
+
+impl FlowField {
+    // read the "flow" config section, returns None when no flow is configured
+    // the seed only shapes noise fields
+    pub(crate) fn from_config(
+        config: &StrictYaml,
+        canvas_constraints: &Constraints,
+        seed: u64,
+    ) -> Option<FlowField> {
+        let field_type: &str = config["type"].as_str()?;
+        let width: u32 = canvas_constraints.x_size;
+        let height: u32 = canvas_constraints.y_size;
+
+        let directions: FieldDirections = match field_type {
+            // the same direction everywhere, in degrees with 0 pointing right and 90 down
+            "wind" => {
+                let angle: f32 = parse_config_value::<f32>(&config["direction"]).to_radians();
+                if !angle.is_finite() {
+                    panic!("[ERROR] wind direction must be a finite number");
+                }
+                FieldDirections::Constant([angle.cos(), angle.sin()])
+            }
+            // away from (or towards) a center point, the canvas center by default
+            "radial" => {
+                let center: [f32; 2] = [
+                    parse_optional_config_value(&config["center"]["x"], width as f32 / 2f32),
+                    parse_optional_config_value(&config["center"]["y"], height as f32 / 2f32),
+                ];
+                if !center.iter().all(|coordinate| coordinate.is_finite()) {
+                    panic!("[ERROR] radial flow center must be finite numbers");
+                }
+                FieldDirections::Radial {
+                    center,
+                    direction: if parse_optional_config_value(&config["outward"], true) {
+                        1f32
+                    } else {
+                        -1f32
+                    },
+                }
+            }
+            // 128 is zero in either channel
+            "image" => {
+                let path: String = parse_config_value(&config["path"]);
+                FieldDirections::Image(
+                    image::open(&path)
+                        .expect("[ERROR] unable to read flow field image")
+                        .to_rgb8(),
+                )
+            }
+            // directions turn smoothly across the canvas
+            "noise" => {
+                let scale: f32 = parse_optional_config_value(&config["scale"], 64f32);
+                if !scale.is_finite() || scale <= 0f32 {
+                    panic!("[ERROR] flow noise scale must be a finite number greater than 0");
+                }
+                FieldDirections::Noise { seed, scale }
+            }
+            other => panic!("[ERROR] unknown flow field type: {}", other),
+        };
+
+        return Some(FlowField {
+            width,
+            height,
+            directions,
+        });
+    }
+
+    // the unit vector of a cell, or zero where the field has no preferred direction
+    fn direction_at(&self, x: u32, y: u32) -> [f32; 2] {
+        match &self.directions {
+            FieldDirections::Constant(vector) => *vector,
+            FieldDirections::Radial { center, direction } => normalize([
+                (x as f32 - center[0]) * direction,
+                (y as f32 - center[1]) * direction,
+            ]),
+            FieldDirections::Image(image) => {
+                let pixel = image.get_pixel(
+                    (x as u64 * image.width() as u64 / self.width as u64) as u32,
+                    (y as u64 * image.height() as u64 / self.height as u64) as u32,
+                );
+                normalize([
+                    (pixel[0] as f32 - 128f32) / 127f32,
+                    (pixel[1] as f32 - 128f32) / 127f32,
+                ])
+            }
+            FieldDirections::Noise { seed, scale } => {
+                let angle: f32 = value_noise(*seed, x as f32 / scale, y as f32 / scale) * TAU;
+                [angle.cos(), angle.sin()]
+            }
+        }
+    }
+
+    // how far growing into a cell from its painted neighbors strays from the field, 0..1
+    // `neighbor_offset` is the sum of the offsets from the cell to its painted neighbors,
+    // so growth arrives from the opposite direction
+    pub(crate) fn misalignment(&self, x: u32, y: u32, neighbor_offset: [f32; 2]) -> f32 {
+        let field: [f32; 2] = self.direction_at(x, y);
+        let growth: [f32; 2] = normalize([-neighbor_offset[0], -neighbor_offset[1]]);
+
+        // aligned growth is 0, opposed growth 1,
+        // cells without a direction on either side sit in between
+        let alignment: f32 = field[0] * growth[0] + field[1] * growth[1];
+        return (1f32 - alignment) / 2f32;
+    }
+}
+
+// unit length, or zero for vectors without a direction
+fn normalize(vector: [f32; 2]) -> [f32; 2] {
+    let length: f32 = (vector[0] * vector[0] + vector[1] * vector[1]).sqrt();
+    if length <= f32::EPSILON {
+        return [0f32, 0f32];
+    }
+    return [vector[0] / length, vector[1] / length];
+}
+
+// smoothly interpolated random values in 0..1 on an integer lattice
+fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let lattice_value = |lattice_x: i64, lattice_y: i64| -> f32 {
+        let index: u64 = (lattice_x as u64).wrapping_mul(0x9e3779b97f4a7c15) ^ (lattice_y as u64);
+        (tie_breaker(seed, index as usize) >> 40) as f32 / (1u64 << 24) as f32
+    };
+    let smooth = |t: f32| t * t * (3f32 - 2f32 * t);
+
+    let (cell_x, cell_y): (f32, f32) = (x.floor(), y.floor());
+    let (fraction_x, fraction_y): (f32, f32) = (smooth(x - cell_x), smooth(y - cell_y));
+    let (cell_x, cell_y): (i64, i64) = (cell_x as i64, cell_y as i64);
+
+    let top: f32 = lattice_value(cell_x, cell_y)
+        + (lattice_value(cell_x + 1, cell_y) - lattice_value(cell_x, cell_y)) * fraction_x;
+    let bottom: f32 = lattice_value(cell_x, cell_y + 1)
+        + (lattice_value(cell_x + 1, cell_y + 1) - lattice_value(cell_x, cell_y + 1)) * fraction_x;
+    return top + (bottom - top) * fraction_y;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NeighborhoodShape;
+    use strict_yaml_rust::StrictYamlLoader;
+
+    fn flow_field(section: &str) -> Option<FlowField> {
+        let config: StrictYaml = StrictYamlLoader::load_from_str(section).unwrap().remove(0);
+        let canvas_constraints: Constraints = Constraints {
+            x_size: 64u32,
+            y_size: 32u32,
+            neighborhood: NeighborhoodShape::Moore,
+        };
+        return FlowField::from_config(&config, &canvas_constraints, 7u64);
+    }
+
+    #[test]
+    fn computes_noise_directions_per_cell() {
+        let field: FlowField = flow_field("type: \"noise\"\nscale: \"8\"").unwrap();
+        let again: FlowField = flow_field("type: \"noise\"\nscale: \"8\"").unwrap();
+        for (x, y) in [(0u32, 0u32), (13u32, 7u32), (63u32, 31u32)] {
+            let direction: [f32; 2] = field.direction_at(x, y);
+            let length: f32 = (direction[0] * direction[0] + direction[1] * direction[1]).sqrt();
+            assert!((length - 1f32).abs() < 1e-5);
+            assert_eq!(direction, again.direction_at(x, y));
+        }
+    }
+
+    #[test]
+    fn rejects_scales_that_are_not_positive() {
+        for scale in ["0", "-4", "NaN", "inf"] {
+            let section: String = format!("type: \"noise\"\nscale: \"{}\"", scale);
+            assert!(
+                std::panic::catch_unwind(|| flow_field(&section)).is_err(),
+                "accepted scale {}",
+                scale
+            );
+        }
+    }
+}
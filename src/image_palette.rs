@@ -0,0 +1,253 @@
+use image::RgbImage;
+use palette::{convert::IntoColorUnclamped, Oklab, Srgb};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use strict_yaml_rust::StrictYaml;
+
+use crate::palette_file::ColorPool;
+use crate::parse_optional_config_value;
+
+// k-means only looks at this many pixels, which is plenty to place the centers
+const KMEANS_SAMPLE_LIMIT: usize = 20000;
+const KMEANS_ITERATIONS: u32 = 16;
+// k-means uses its own fixed seed so an image always extracts to the same palette
+const KMEANS_SEED: u64 = 0x5eed;
+
+// how the colors of an input image feed the painting
+pub(crate) enum ImageColorMode {
+    // a handful of representative colors, weighted by how much of the image they cover
+    Palette,
+    // every pixel of the image exactly once, in random order
+    Population,
+}
+
+// how representative colors are found
+enum ExtractionMethod {
+    MedianCut,
+    KMeans,
+}
+
+// the settings of an "image" palette config section
+pub(crate) struct ImageSource {
+    pub(crate) mode: ImageColorMode,
+    method: ExtractionMethod,
+    color_count: usize,
+    colors: Vec<[u8; 3]>,
+}
+impl ImageSource {
+    // read the image named in the config, returns None when no image is configured
+    pub(crate) fn from_config(config: &StrictYaml) -> Option<ImageSource> {
+        let path: &str = config["path"].as_str()?;
+        let image: RgbImage = image::open(path)
+            .expect("[ERROR] unable to read palette image")
+            .to_rgb8();
+
+        let mode: ImageColorMode = match config["mode"].as_str() {
+            None | Some("palette") => ImageColorMode::Palette,
+            Some("population") => ImageColorMode::Population,
+            Some(other) => panic!("[ERROR] unknown image color mode: {}", other),
+        };
+        let method: ExtractionMethod = match config["method"].as_str() {
+            None | Some("kmeans") => ExtractionMethod::KMeans,
+            Some("median_cut") => ExtractionMethod::MedianCut,
+            Some(other) => panic!("[ERROR] unknown palette extraction method: {}", other),
+        };
+        let color_count: usize = parse_optional_config_value(&config["colors"], 16usize);
+        if color_count == 0 {
+            panic!("[ERROR] palette image colors must be at least 1");
+        }
+
+        return Some(ImageSource {
+            mode,
+            method,
+            color_count,
+            colors: image.pixels().map(|pixel| pixel.0).collect(),
+        });
+    }
+
+    // reduce the image to a weighted pool of representative colors
+    pub(crate) fn extract_palette(&self) -> ColorPool {
+        let lab_colors: Vec<[f32; 3]> = self.colors.iter().map(to_oklab).collect();
+
+        let clusters: Vec<([f32; 3], f32)> = match self.method {
+            ExtractionMethod::MedianCut => median_cut(&lab_colors, self.color_count),
+            ExtractionMethod::KMeans => kmeans(&lab_colors, self.color_count),
+        };
+
+        return ColorPool::from_entries(
+            clusters
+                .iter()
+                .map(|(center, weight)| (from_oklab(center), *weight))
+                .collect(),
+        )
+        .expect("[ERROR] palette image contains no colors");
+    }
+
+    pub(crate) fn pixel_count(&self) -> usize {
+        self.colors.len()
+    }
+
+    // every pixel of the image, shuffled
+    pub(crate) fn shuffled_population<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<[u8; 3]> {
+        let mut population: Vec<[u8; 3]> = self.colors.clone();
+        population.shuffle(rng);
+        return population;
+    }
+}
+
+// split the colors into boxes along their widest axis until there are enough boxes
+// each box becomes one color: the mean of its members, weighted by its size
+fn median_cut(lab_colors: &[[f32; 3]], color_count: usize) -> Vec<([f32; 3], f32)> {
+    let mut boxes: Vec<Vec<[f32; 3]>> = vec![lab_colors.to_vec()];
+
+    while boxes.len() < color_count {
+        // find the box with the widest spread along any axis
+        let mut widest: Option<(usize, usize, f32)> = None;
+        for (box_index, colors) in boxes.iter().enumerate() {
+            if colors.len() < 2 {
+                continue;
+            }
+            for axis in 0..3 {
+                let (min, max) = colors
+                    .iter()
+                    .fold((f32::MAX, f32::MIN), |(min, max), color| {
+                        (min.min(color[axis]), max.max(color[axis]))
+                    });
+                if widest.is_none_or(|(_, _, spread)| max - min > spread) {
+                    widest = Some((box_index, axis, max - min));
+                }
+            }
+        }
+
+        // stop early once every box holds a single color
+        let Some((box_index, axis, spread)) = widest else {
+            break;
+        };
+        if spread <= 0f32 {
+            break;
+        }
+
+        // split the box at the median of its widest axis
+        let mut colors: Vec<[f32; 3]> = boxes.swap_remove(box_index);
+        colors.sort_by(|a, b| a[axis].total_cmp(&b[axis]));
+        let upper_half: Vec<[f32; 3]> = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper_half);
+    }
+
+    return boxes
+        .iter()
+        .filter(|colors| !colors.is_empty())
+        .map(|colors| (mean_color(colors.iter()), colors.len() as f32))
+        .collect();
+}
+
+// lloyd's k-means with k-means++ seeding, on a random sample of the colors
+// each center is weighted by the number of sampled colors closest to it
+fn kmeans(lab_colors: &[[f32; 3]], color_count: usize) -> Vec<([f32; 3], f32)> {
+    let mut rng: StdRng = StdRng::seed_from_u64(KMEANS_SEED);
+    let samples: Vec<[f32; 3]> = if lab_colors.len() > KMEANS_SAMPLE_LIMIT {
+        lab_colors
+            .choose_multiple(&mut rng, KMEANS_SAMPLE_LIMIT)
+            .copied()
+            .collect()
+    } else {
+        lab_colors.to_vec()
+    };
+    if samples.is_empty() {
+        return Vec::new();
+    }
+
+    // k-means++: each new center is drawn with probability proportional to its squared
+    // distance from the closest existing center
+    let mut centers: Vec<[f32; 3]> = vec![samples[rng.gen_range(0..samples.len())]];
+    while centers.len() < color_count.min(samples.len()) {
+        let distances: Vec<f32> = samples
+            .iter()
+            .map(|sample| nearest_center(sample, &centers).1)
+            .collect();
+        let total_distance: f32 = distances.iter().sum();
+        if total_distance <= 0f32 {
+            break;
+        }
+        let mut draw: f32 = rng.gen::<f32>() * total_distance;
+        let mut chosen_index: usize = samples.len() - 1;
+        for (sample_index, distance) in distances.iter().enumerate() {
+            if draw < *distance {
+                chosen_index = sample_index;
+                break;
+            }
+            draw -= distance;
+        }
+        centers.push(samples[chosen_index]);
+    }
+
+    // move every center to the mean of the samples assigned to it
+    let mut assignments: Vec<usize> = vec![0usize; samples.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        for (sample, assignment) in samples.iter().zip(assignments.iter_mut()) {
+            *assignment = nearest_center(sample, &centers).0;
+        }
+        for (center_index, center) in centers.iter_mut().enumerate() {
+            let members = samples
+                .iter()
+                .zip(assignments.iter())
+                .filter(|(_, assignment)| **assignment == center_index)
+                .map(|(sample, _)| sample);
+            if members.clone().next().is_some() {
+                *center = mean_color(members);
+            }
+        }
+    }
+
+    return centers
+        .iter()
+        .enumerate()
+        .map(|(center_index, center)| {
+            let member_count: usize = assignments
+                .iter()
+                .filter(|assignment| **assignment == center_index)
+                .count();
+            (*center, member_count as f32)
+        })
+        .filter(|(_, weight)| *weight > 0f32)
+        .collect();
+}
+
+// index of and squared distance to the closest center
+fn nearest_center(color: &[f32; 3], centers: &[[f32; 3]]) -> (usize, f32) {
+    let mut nearest: (usize, f32) = (0usize, f32::MAX);
+    for (center_index, center) in centers.iter().enumerate() {
+        let distance: f32 = (0..3)
+            .map(|axis| (color[axis] - center[axis]).powi(2))
+            .sum();
+        if distance < nearest.1 {
+            nearest = (center_index, distance);
+        }
+    }
+    return nearest;
+}
+
+fn mean_color<'a>(colors: impl Iterator<Item = &'a [f32; 3]>) -> [f32; 3] {
+    let mut sum: [f32; 3] = [0f32; 3];
+    let mut count: f32 = 0f32;
+    for color in colors {
+        for (total, value) in sum.iter_mut().zip(color.iter()) {
+            *total += value;
+        }
+        count += 1f32;
+    }
+    return sum.map(|total| total / count);
+}
+
+fn to_oklab(color: &[u8; 3]) -> [f32; 3] {
+    let lab: Oklab = Srgb::new(color[0], color[1], color[2])
+        .into_format::<f32>()
+        .into_color_unclamped();
+    return [lab.l, lab.a, lab.b];
+}
+
+fn from_oklab(lab: &[f32; 3]) -> [u8; 3] {
+    let rgb: Srgb = Oklab::new(lab[0], lab[1], lab[2]).into_color_unclamped();
+    return [rgb.red, rgb.green, rgb.blue]
+        .map(|channel| (channel.clamp(0f32, 1f32) * 255f32).round() as u8);
+}
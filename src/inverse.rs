@@ -0,0 +1,140 @@
+use rand::Rng;
+use strict_yaml_rust::StrictYaml;
+
+use crate::cell::Cell;
+use crate::color_source::PaletteState;
+use crate::diagnostic_maps::{DiagnosticMaps, RecordedMaps};
+use crate::tiled_canvas::TiledCanvas;
+use crate::{next_color_at, Constraints, Coordinate, Painting};
+
+// how the next pixel is found
+pub(crate) enum GenerationMode {
+    // draw a color, then search the boundry region for the best cell
+    ColorFirst,
+    // pick a boundry cell, then search the remaining colors for the best match
+    PositionFirst(CellSelection),
+}
+impl GenerationMode {
+    // read the "generation" config section
+    pub(crate) fn from_config(config: &StrictYaml) -> GenerationMode {
+        match config["mode"].as_str() {
+            None | Some("color_first") => GenerationMode::ColorFirst,
+            Some("position_first") => {
+                GenerationMode::PositionFirst(CellSelection::from_config(&config["cell"]))
+            }
+            Some(other) => panic!("[ERROR] unknown generation mode: {}", other),
+        }
+    }
+
+    // the per cell records the cell selection reads
+    pub(crate) fn recorded_maps(&self) -> RecordedMaps {
+        RecordedMaps {
+            boundry_since: matches!(
+                self,
+                GenerationMode::PositionFirst(CellSelection::Oldest | CellSelection::MostNeighbors)
+            ),
+            ..RecordedMaps::default()
+        }
+    }
+}
+
+// which boundry cell is painted next in position first mode
+#[derive(Copy, Clone)]
+pub(crate) enum CellSelection {
+    Random,
+    // the cell that has waited in the boundry region the longest
+    Oldest,
+    // the cell with the most colored neighbors, the oldest of those on ties
+    // fills the gaps first, which keeps the growth front smooth
+    MostNeighbors,
+}
+impl CellSelection {
+    fn from_config(config: &StrictYaml) -> CellSelection {
+        match config.as_str() {
+            None | Some("random") => CellSelection::Random,
+            Some("oldest") => CellSelection::Oldest,
+            Some("most_neighbors") => CellSelection::MostNeighbors,
+            Some(other) => panic!("[ERROR] unknown cell selection: {}", other),
+        }
+    }
+
+    // index into the colony's boundry region list of the cell to paint next
+    pub(crate) fn choose_cell<R: Rng + ?Sized>(
+        &self,
+        boundry_region_list: &[Coordinate],
+        diagnostic_maps: &DiagnosticMaps,
+        canvas_image: &TiledCanvas<Cell>,
+        canvas_constraints: &Constraints,
+        rng: &mut R,
+    ) -> usize {
+        match self {
+            CellSelection::Random => rng.gen_range(0..boundry_region_list.len()),
+            CellSelection::Oldest => (0..boundry_region_list.len())
+                .min_by_key(|index| diagnostic_maps.boundry_since(&boundry_region_list[*index]))
+                .unwrap(),
+            CellSelection::MostNeighbors => (0..boundry_region_list.len())
+                .max_by_key(|index| {
+                    let position: &Coordinate = &boundry_region_list[*index];
+                    (
+                        colored_neighbor_count(position, canvas_image, canvas_constraints),
+                        // older cells win ties, max_by_key keeps the last maximum
+                        u64::MAX - diagnostic_maps.boundry_since(position),
+                        usize::MAX - index,
+                    )
+                })
+                .unwrap(),
+        }
+    }
+}
+
+// draw a colony's finite color pool from its palettes, spread over the palette timeline
+// each colony gets a share of the canvas proportional to its weight
+// a script that defines next_color draws the pool as it draws every other color
+pub(crate) fn draw_remaining_colors(working_canvas: &mut Painting, total_pixel_count: u64) {
+    let total_weight: f32 = working_canvas
+        .colonies
+        .iter()
+        .map(|colony| colony.weight.max(0f32))
+        .sum();
+    let colony_count: usize = working_canvas.colonies.len();
+    for colony_index in 0..colony_count {
+        let weight: f32 = working_canvas.colonies[colony_index].weight;
+        let share: f32 = if total_weight > 0f32 {
+            weight.max(0f32) / total_weight
+        } else {
+            1f32 / colony_count as f32
+        };
+        let color_count: u64 = (total_pixel_count as f64 * share as f64).ceil() as u64;
+
+        let mut remaining_colors: Vec<[u8; 3]> = Vec::with_capacity(color_count as usize);
+        for color_index in 0..color_count {
+            let at: u64 = color_index * total_pixel_count / color_count.max(1);
+            let palette_state: PaletteState = working_canvas.colonies[colony_index]
+                .color_source
+                .palette_at(at);
+            remaining_colors
+                .push(next_color_at(colony_index, &palette_state, at, working_canvas).0);
+        }
+        working_canvas.colonies[colony_index].remaining_colors = remaining_colors;
+    }
+}
+
+fn colored_neighbor_count(
+    position: &Coordinate,
+    canvas_image: &TiledCanvas<Cell>,
+    canvas_constraints: &Constraints,
+) -> u32 {
+    let mut count: u32 = 0u32;
+    for y in position.y.saturating_sub(1)..=(position.y + 1).min(canvas_constraints.y_size - 1) {
+        for x in position.x.saturating_sub(1)..=(position.x + 1).min(canvas_constraints.x_size - 1)
+        {
+            let is_neighbor: bool = canvas_constraints
+                .neighborhood
+                .contains(x + 1 - position.x, y + 1 - position.y);
+            if is_neighbor && canvas_image.get(x, y).is_painted() {
+                count += 1;
+            }
+        }
+    }
+    return count;
+}
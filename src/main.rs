@@ -1,5 +1,27 @@
 mod app;
 mod canvas;
+// shared with the native shredder, which also marks frontier cells
+#[allow(dead_code)]
+mod cell;
+// shared with the native shredder, which uses the parts the web app does not
+#[allow(dead_code)]
+mod distribution;
+mod distribution_preview;
+mod palette_file;
+mod quality;
+mod quality_panel;
+mod remote_run;
+mod run_metadata;
+// shared with the native shredder, which also writes the time series files
+#[allow(dead_code)]
+mod run_stats;
+mod scoring_panel;
+// shared with the native shredder, which also scores with adjust_score
+#[allow(dead_code)]
+mod script;
+// shared with the native shredder, which can also map the tiles to scratch files
+#[allow(dead_code)]
+mod tiled_canvas;
 
 use app::App;
 
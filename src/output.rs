@@ -0,0 +1,650 @@
+use image::codecs::pnm::{PnmSubtype, SampleEncoding};
+use image::{DynamicImage, GrayImage, ImageOutputFormat, Luma, Rgb, RgbImage, Rgba, RgbaImage};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use strict_yaml_rust::StrictYaml;
+
+use crate::cell::Cell;
+use crate::diagnostic_maps::{DiagnosticMaps, RecordedMaps};
+use crate::run_metadata::RunMetadata;
+use crate::run_stats::{RunStats, StatsSample};
+use crate::tiled_canvas::TiledCanvas;
+use crate::{parse_optional_config_value, Constraints};
+
+// the file formats the output images can be written in
+#[derive(Copy, Clone)]
+enum OutputFormat {
+    Png,
+    // 16 bits per channel png, for tools that only take 16 bit input
+    // the 8 bit colors are widened (multiplied by 257), no precision is gained
+    Png16,
+    // lossless webp
+    WebP,
+    Tiff,
+    Qoi,
+    // binary netpbm (ppm for color, pgm for gray)
+    Ppm,
+}
+impl OutputFormat {
+    fn from_config(config: &StrictYaml) -> OutputFormat {
+        match config.as_str() {
+            None | Some("png") => OutputFormat::Png,
+            Some("png16") => OutputFormat::Png16,
+            Some("webp") => OutputFormat::WebP,
+            Some("tiff") => OutputFormat::Tiff,
+            Some("qoi") => OutputFormat::Qoi,
+            Some("ppm") => OutputFormat::Ppm,
+            Some(other) => panic!("[ERROR] unknown output format: {}", other),
+        }
+    }
+
+    // the encoder of the format, chosen here since the partial file has no telling extension
+    fn output_format(&self, is_gray: bool) -> ImageOutputFormat {
+        match self {
+            OutputFormat::Png | OutputFormat::Png16 => ImageOutputFormat::Png,
+            OutputFormat::WebP => ImageOutputFormat::WebP,
+            OutputFormat::Tiff => ImageOutputFormat::Tiff,
+            OutputFormat::Qoi => ImageOutputFormat::Qoi,
+            OutputFormat::Ppm if is_gray => {
+                ImageOutputFormat::Pnm(PnmSubtype::Graymap(SampleEncoding::Binary))
+            }
+            OutputFormat::Ppm => ImageOutputFormat::Pnm(PnmSubtype::Pixmap(SampleEncoding::Binary)),
+        }
+    }
+
+    fn extension(&self, is_gray: bool) -> &'static str {
+        match self {
+            OutputFormat::Png | OutputFormat::Png16 => "png",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Tiff => "tiff",
+            OutputFormat::Qoi => "qoi",
+            OutputFormat::Ppm if is_gray => "pgm",
+            OutputFormat::Ppm => "ppm",
+        }
+    }
+
+    fn supports_alpha(&self) -> bool {
+        !matches!(self, OutputFormat::Ppm)
+    }
+
+    // the largest width and height the encoder accepts, None when it has no limit of its own
+    fn max_size(&self) -> Option<u32> {
+        match self {
+            OutputFormat::WebP => Some(16384u32),
+            _ => None,
+        }
+    }
+
+    // the sample depth of the png formats, None for the others
+    fn png_bit_depth(&self) -> Option<png::BitDepth> {
+        match self {
+            OutputFormat::Png => Some(png::BitDepth::Eight),
+            OutputFormat::Png16 => Some(png::BitDepth::Sixteen),
+            _ => None,
+        }
+    }
+}
+
+// bit depth of the placement order map
+#[derive(Copy, Clone)]
+enum OrderDepth {
+    // scaled to fill 16 bits when the painting has more cells than that, written as png
+    Sixteen,
+    // exact placement indices, written as 32 bit grayscale tiff
+    ThirtyTwo,
+}
+
+// which auxiliary maps are written once the painting is finished
+// maps always use png (or tiff for 32 bit order), whatever the painting format
+struct MapSettings {
+    placement_order: Option<OrderDepth>,
+    age: bool,
+    score: bool,
+    ownership: bool,
+    // painting path without its extension, each map appends its own suffix
+    path_stem: String,
+}
+impl MapSettings {
+    // read the "output: maps" config section, every map is off by default
+    fn from_config(config: &StrictYaml, path_stem: String) -> MapSettings {
+        let placement_order: Option<OrderDepth> = match config["placement_order"].as_str() {
+            None | Some("false") => None,
+            Some("16") => Some(OrderDepth::Sixteen),
+            Some("32") => Some(OrderDepth::ThirtyTwo),
+            Some(other) => panic!("[ERROR] placement order depth must be 16 or 32: {}", other),
+        };
+        MapSettings {
+            placement_order,
+            age: parse_optional_config_value(&config["age"], false),
+            score: parse_optional_config_value(&config["score"], false),
+            ownership: parse_optional_config_value(&config["ownership"], false),
+            path_stem,
+        }
+    }
+}
+
+// machine readable format of the statistics time series
+#[derive(Copy, Clone)]
+enum StatsFormat {
+    Json,
+    Csv,
+}
+
+// how the run statistics are reported while painting
+struct StatsSettings {
+    // redraw a progress line on stderr at every update
+    show_progress: bool,
+    // the time series is rewritten at every update, so other tools can follow the run
+    series_format: Option<StatsFormat>,
+    series_path: String,
+}
+impl StatsSettings {
+    // read the "output: stats" config section
+    fn from_config(config: &StrictYaml, path_stem: &str) -> StatsSettings {
+        let series_format: Option<StatsFormat> = match config["series"].as_str() {
+            None | Some("false") => None,
+            Some("json") => Some(StatsFormat::Json),
+            Some("csv") => Some(StatsFormat::Csv),
+            Some(other) => panic!("[ERROR] stats series must be json or csv: {}", other),
+        };
+        let extension: &str = match series_format {
+            Some(StatsFormat::Csv) => "csv",
+            _ => "json",
+        };
+        StatsSettings {
+            show_progress: parse_optional_config_value(&config["progress"], true),
+            series_format,
+            series_path: format!("{}_stats.{}", path_stem, extension),
+        }
+    }
+}
+
+// where and how the output images are written
+pub(crate) struct OutputSettings {
+    format: OutputFormat,
+    // unpainted cells become fully transparent instead of black
+    transparent_background: bool,
+    painting_path: String,
+    boundry_path: String,
+    maps: MapSettings,
+    stats: StatsSettings,
+    // embedded into png outputs, other formats have no place for it
+    metadata: RunMetadata,
+}
+impl OutputSettings {
+    // read the "output" config section
+    // file names may contain the tokens {seed}, {width}, {height} and {timestamp}
+    pub(crate) fn from_config(
+        config: &StrictYaml,
+        metadata: RunMetadata,
+        canvas_constraints: &Constraints,
+    ) -> OutputSettings {
+        let seed: u64 = metadata.seed;
+        let format: OutputFormat = OutputFormat::from_config(&config["format"]);
+        let transparent_background: bool =
+            parse_optional_config_value(&config["transparent_background"], false);
+        if transparent_background && !format.supports_alpha() {
+            panic!("[ERROR] the ppm output format has no transparent background");
+        }
+        // checked up front, the encoder would only refuse the painting once it is finished
+        if let Some(max_size) = format.max_size() {
+            if canvas_constraints.x_size > max_size || canvas_constraints.y_size > max_size {
+                panic!(
+                    "[ERROR] webp output is limited to {} pixels on a side, use png instead",
+                    max_size
+                );
+            }
+        }
+
+        let directory: String =
+            parse_optional_config_value(&config["directory"], String::from("./output"));
+        let painting_name: String =
+            parse_optional_config_value(&config["painting_name"], String::from("painting"));
+        let boundry_name: String =
+            parse_optional_config_value(&config["boundry_name"], String::from("boundry"));
+
+        // the timestamp is taken once so every update of a run writes to the same files
+        let timestamp: u64 = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("[ERROR] system clock is before the unix epoch")
+            .as_secs();
+        let expand_tokens = |name: &str| -> String {
+            name.replace("{seed}", &seed.to_string())
+                .replace("{width}", &canvas_constraints.x_size.to_string())
+                .replace("{height}", &canvas_constraints.y_size.to_string())
+                .replace("{timestamp}", &timestamp.to_string())
+        };
+
+        let painting_stem: String = format!("{}/{}", directory, expand_tokens(&painting_name));
+        return OutputSettings {
+            format,
+            transparent_background,
+            painting_path: format!("{}.{}", painting_stem, format.extension(false)),
+            boundry_path: format!(
+                "{}/{}.{}",
+                directory,
+                expand_tokens(&boundry_name),
+                format.extension(true)
+            ),
+            stats: StatsSettings::from_config(&config["stats"], &painting_stem),
+            maps: MapSettings::from_config(&config["maps"], painting_stem),
+            metadata,
+        };
+    }
+
+    // write the RGB painting file
+    pub(crate) fn write_painting(&self, image: &TiledCanvas<Cell>) {
+        // unpainted cells are black, or transparent with a transparent background
+        let alpha = |cell: &Cell| -> u8 {
+            if cell.is_painted() {
+                255u8
+            } else {
+                0u8
+            }
+        };
+
+        // png is streamed row by row, the painting is never copied out of its tiles
+        if let Some(bit_depth) = self.format.png_bit_depth() {
+            let color_type: png::ColorType = if self.transparent_background {
+                png::ColorType::Rgba
+            } else {
+                png::ColorType::Rgb
+            };
+            let mut cells: Vec<Cell> = Vec::new();
+            self.stream_png(
+                &self.painting_path,
+                image.width(),
+                image.height(),
+                color_type,
+                bit_depth,
+                |y, row| {
+                    image.read_row(y, &mut cells);
+                    for cell in cells.iter() {
+                        for channel in cell.rgb() {
+                            push_sample(row, channel, bit_depth);
+                        }
+                        if self.transparent_background {
+                            push_sample(row, alpha(cell), bit_depth);
+                        }
+                    }
+                },
+            );
+            return;
+        }
+
+        // the other encoders need the whole image at once
+        let output_image: DynamicImage = if self.transparent_background {
+            DynamicImage::ImageRgba8(RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+                let cell: Cell = image.get(x, y);
+                let [red, green, blue] = cell.rgb();
+                Rgba([red, green, blue, alpha(&cell)])
+            }))
+        } else {
+            DynamicImage::ImageRgb8(RgbImage::from_fn(image.width(), image.height(), |x, y| {
+                Rgb(image.get(x, y).rgb())
+            }))
+        };
+        self.save(&output_image, &self.painting_path);
+    }
+
+    // write the boundry region image, white for frontier cells
+    pub(crate) fn write_boundry(&self, image: &TiledCanvas<Cell>) {
+        let luma = |cell: &Cell| -> u8 {
+            if cell.is_frontier() {
+                255u8
+            } else {
+                0u8
+            }
+        };
+
+        if let Some(bit_depth) = self.format.png_bit_depth() {
+            let mut cells: Vec<Cell> = Vec::new();
+            self.stream_png(
+                &self.boundry_path,
+                image.width(),
+                image.height(),
+                png::ColorType::Grayscale,
+                bit_depth,
+                |y, row| {
+                    image.read_row(y, &mut cells);
+                    for cell in cells.iter() {
+                        push_sample(row, luma(cell), bit_depth);
+                    }
+                },
+            );
+            return;
+        }
+
+        let gray_image: GrayImage = GrayImage::from_fn(image.width(), image.height(), |x, y| {
+            Luma([luma(&image.get(x, y))])
+        });
+        let output_image: DynamicImage = match self.format {
+            // qoi only stores color images
+            OutputFormat::Qoi => {
+                DynamicImage::ImageRgb8(DynamicImage::ImageLuma8(gray_image).to_rgb8())
+            }
+            _ => DynamicImage::ImageLuma8(gray_image),
+        };
+        self.save(&output_image, &self.boundry_path);
+    }
+
+    // show the progress line and rewrite the statistics time series
+    pub(crate) fn report_progress(&self, stats: &RunStats, sample: &StatsSample) {
+        if self.stats.show_progress {
+            // clear the rest of the previous line, which may have been longer
+            eprint!("\r{}\x1b[K", stats.progress_line(sample));
+            std::io::stderr().flush().unwrap();
+        }
+        let series: String = match self.stats.series_format {
+            Some(StatsFormat::Json) => stats.to_json(),
+            Some(StatsFormat::Csv) => stats.to_csv(),
+            None => return,
+        };
+        write_atomically(&self.stats.series_path, |partial_path| {
+            fs::write(partial_path, &series).map_err(|error| error.to_string())
+        });
+    }
+
+    pub(crate) fn painting_path(&self) -> &str {
+        &self.painting_path
+    }
+
+    // end the progress line so later output starts on a fresh line
+    pub(crate) fn finish_progress(&self) {
+        if self.stats.show_progress {
+            eprintln!();
+        }
+    }
+
+    // the per cell records the enabled maps are drawn from
+    pub(crate) fn recorded_maps(&self) -> RecordedMaps {
+        RecordedMaps {
+            placement_order: self.maps.placement_order.is_some() || self.maps.age,
+            chosen_scores: self.maps.score,
+            seed_owners: self.maps.ownership,
+            boundry_since: false,
+        }
+    }
+
+    // write the enabled auxiliary maps next to the painting, streamed row by row
+    // the painting's cells tell which cells were painted
+    pub(crate) fn write_maps(
+        &self,
+        maps: &DiagnosticMaps,
+        canvas: &TiledCanvas<Cell>,
+        placement_count: u64,
+    ) {
+        let settings: &MapSettings = &self.maps;
+        let width: u32 = maps.width();
+        let height: u32 = canvas.height();
+        let mut cells: Vec<Cell> = Vec::new();
+        match settings.placement_order {
+            Some(OrderDepth::Sixteen) => {
+                let last_order: u32 = maps.last_placement(height);
+                let mut samples: Vec<u16> = Vec::new();
+                self.stream_png(
+                    &format!("{}_order.png", settings.path_stem),
+                    width,
+                    height,
+                    png::ColorType::Grayscale,
+                    png::BitDepth::Sixteen,
+                    |y, row| {
+                        maps.placement_order_16_row(y, last_order, &mut samples);
+                        row.extend(samples.iter().flat_map(|sample| sample.to_be_bytes()));
+                    },
+                );
+            }
+            Some(OrderDepth::ThirtyTwo) => {
+                let path: String = format!("{}_order.tiff", settings.path_stem);
+                write_atomically(&path, |partial_path| {
+                    let file: File =
+                        File::create(partial_path).map_err(|error| error.to_string())?;
+                    let mut encoder = tiff::encoder::TiffEncoder::new(BufWriter::new(file))
+                        .map_err(|error| error.to_string())?;
+                    let mut image = encoder
+                        .new_image::<tiff::encoder::colortype::Gray32>(width, height)
+                        .map_err(|error| error.to_string())?;
+                    image
+                        .rows_per_strip(1u32)
+                        .map_err(|error| error.to_string())?;
+                    let mut orders: Vec<u32> = Vec::new();
+                    for y in 0..height {
+                        maps.placement_order_row(y, &mut orders);
+                        image
+                            .write_strip(&orders)
+                            .map_err(|error| error.to_string())?;
+                    }
+                    image.finish().map_err(|error| error.to_string())
+                });
+            }
+            None => {}
+        }
+        if settings.age {
+            let mut colors: Vec<Rgb<u8>> = Vec::new();
+            self.stream_png(
+                &format!("{}_age.png", settings.path_stem),
+                width,
+                height,
+                png::ColorType::Rgb,
+                png::BitDepth::Eight,
+                |y, row| {
+                    maps.age_row(y, placement_count, &mut colors);
+                    row.extend(colors.iter().flat_map(|color| color.0));
+                },
+            );
+        }
+        if settings.score {
+            let range: (f32, f32) = maps.score_range(canvas);
+            let mut samples: Vec<u16> = Vec::new();
+            self.stream_png(
+                &format!("{}_score.png", settings.path_stem),
+                width,
+                height,
+                png::ColorType::Grayscale,
+                png::BitDepth::Sixteen,
+                |y, row| {
+                    canvas.read_row(y, &mut cells);
+                    maps.score_row(y, range, &cells, &mut samples);
+                    row.extend(samples.iter().flat_map(|sample| sample.to_be_bytes()));
+                },
+            );
+        }
+        if settings.ownership {
+            let mut colors: Vec<Rgb<u8>> = Vec::new();
+            self.stream_png(
+                &format!("{}_ownership.png", settings.path_stem),
+                width,
+                height,
+                png::ColorType::Rgb,
+                png::BitDepth::Eight,
+                |y, row| {
+                    canvas.read_row(y, &mut cells);
+                    maps.seed_ownership_row(y, &cells, &mut colors);
+                    row.extend(colors.iter().flat_map(|color| color.0));
+                },
+            );
+        }
+    }
+
+    // write an image in one of the formats without metadata, pngs are streamed by stream_png
+    fn save(&self, image: &DynamicImage, path: &str) {
+        let is_gray: bool = matches!(image, DynamicImage::ImageLuma8(_));
+        write_atomically(path, |partial_path| {
+            let file: File = File::create(partial_path).map_err(|error| error.to_string())?;
+            let mut writer: BufWriter<File> = BufWriter::new(file);
+            image
+                .write_to(&mut writer, self.format.output_format(is_gray))
+                .map_err(|error| error.to_string())?;
+            writer.flush().map_err(|error| error.to_string())
+        });
+    }
+
+    // write a png with the run metadata embedded, one row at a time
+    // write_row appends the encoded samples of row y to the cleared buffer
+    fn stream_png(
+        &self,
+        path: &str,
+        width: u32,
+        height: u32,
+        color_type: png::ColorType,
+        bit_depth: png::BitDepth,
+        mut write_row: impl FnMut(u32, &mut Vec<u8>),
+    ) {
+        write_atomically(path, |partial_path| {
+            let file: File = File::create(partial_path).map_err(|error| error.to_string())?;
+            let mut png_writer = self
+                .metadata
+                .png_encoder(BufWriter::new(file), width, height, color_type, bit_depth)?
+                .write_header()
+                .map_err(|error| error.to_string())?;
+            let mut stream_writer = png_writer
+                .stream_writer()
+                .map_err(|error| error.to_string())?;
+            let mut row: Vec<u8> = Vec::new();
+            for y in 0..height {
+                row.clear();
+                write_row(y, &mut row);
+                stream_writer
+                    .write_all(&row)
+                    .map_err(|error| error.to_string())?;
+            }
+            stream_writer.finish().map_err(|error| error.to_string())
+        });
+    }
+}
+
+// write to a temporary file next to the target, then rename it into place
+// so viewers never see a half written file
+fn write_atomically(path: &str, write: impl FnOnce(&str) -> Result<(), String>) {
+    let partial_path: String = format!("{}.partial", path);
+    write(&partial_path)
+        .and_then(|_| fs::rename(&partial_path, path).map_err(|error| error.to_string()))
+        .unwrap_or_else(|error| panic!("[ERROR] unable to write {}: {}", path, error));
+}
+
+// append an 8 bit sample, widened to big endian 16 bits for 16 bit pngs
+fn push_sample(row: &mut Vec<u8>, sample: u8, bit_depth: png::BitDepth) {
+    match bit_depth {
+        png::BitDepth::Sixteen => row.extend_from_slice(&(sample as u16 * 257u16).to_be_bytes()),
+        _ => row.push(sample),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_metadata::RunOrigin;
+    use crate::NeighborhoodShape;
+    use strict_yaml_rust::StrictYamlLoader;
+
+    fn output_settings(section: &str, x_size: u32, y_size: u32) -> OutputSettings {
+        let config: StrictYaml = StrictYamlLoader::load_from_str(section).unwrap().remove(0);
+        let canvas_constraints: Constraints = Constraints {
+            x_size,
+            y_size,
+            neighborhood: NeighborhoodShape::Moore,
+        };
+        let metadata: RunMetadata = RunMetadata::new(String::new(), 5u64, RunOrigin::Shredder);
+        return OutputSettings::from_config(&config, metadata, &canvas_constraints);
+    }
+
+    // a fresh directory for the files of one test
+    fn test_directory(name: &str) -> String {
+        let directory: String = format!(
+            "{}/shredder-output-{}-{}",
+            std::env::temp_dir().display(),
+            name,
+            std::process::id()
+        );
+        fs::create_dir_all(&directory).unwrap();
+        return directory;
+    }
+
+    #[test]
+    fn names_files_by_format_and_tokens() {
+        let settings: OutputSettings = output_settings(
+            "format: \"ppm\"\ndirectory: \"out\"\npainting_name: \"p_{seed}_{width}x{height}\"",
+            8u32,
+            4u32,
+        );
+        assert_eq!(settings.painting_path, "out/p_5_8x4.ppm");
+        assert_eq!(settings.boundry_path, "out/boundry.pgm");
+    }
+
+    #[test]
+    fn rejects_settings_the_format_can_not_write() {
+        assert!(std::panic::catch_unwind(|| output_settings(
+            "format: \"ppm\"\ntransparent_background: \"true\"",
+            8u32,
+            8u32
+        ))
+        .is_err());
+        assert!(
+            std::panic::catch_unwind(|| output_settings("format: \"webp\"", 16385u32, 8u32))
+                .is_err()
+        );
+        output_settings("format: \"webp\"", 16384u32, 16384u32);
+    }
+
+    #[test]
+    fn widens_8_bit_colors_for_png16() {
+        let directory: String = test_directory("png16");
+        let settings: OutputSettings = output_settings(
+            &format!("format: \"png16\"\ndirectory: \"{}\"", directory),
+            2u32,
+            1u32,
+        );
+        let mut canvas: TiledCanvas<Cell> = TiledCanvas::new(2u32, 1u32);
+        canvas.put(0u32, 0u32, Cell::painted([10u8, 20u8, 255u8]));
+        settings.write_painting(&canvas);
+
+        let bytes: Vec<u8> = fs::read(&settings.painting_path).unwrap();
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut data: Vec<u8> = vec![0u8; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        let samples: Vec<u16> = data
+            .chunks(2usize)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        assert_eq!(samples, vec![2570u16, 5140u16, 65535u16, 0u16, 0u16, 0u16]);
+        assert_eq!(RunMetadata::read_png(&bytes).unwrap().seed, 5u64);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn writes_the_other_formats_losslessly() {
+        let directory: String = test_directory("formats");
+        let mut canvas: TiledCanvas<Cell> = TiledCanvas::new(3u32, 2u32);
+        canvas.put(1u32, 0u32, Cell::painted([1u8, 128u8, 254u8]));
+        canvas.put(2u32, 1u32, Cell::painted([0u8, 0u8, 0u8]));
+        canvas.put(0u32, 1u32, Cell::FRONTIER);
+        for format in ["webp", "tiff", "qoi", "ppm"] {
+            let settings: OutputSettings = output_settings(
+                &format!("format: \"{}\"\ndirectory: \"{}\"", format, directory),
+                3u32,
+                2u32,
+            );
+            settings.write_painting(&canvas);
+            let painting: RgbImage = image::open(&settings.painting_path).unwrap().to_rgb8();
+            for (x, y, pixel) in painting.enumerate_pixels() {
+                let expected: [u8; 3] = canvas.get(x, y).rgb();
+                assert_eq!(pixel.0, expected, "{} at {}, {}", format, x, y);
+            }
+
+            settings.write_boundry(&canvas);
+            let boundry: GrayImage = image::open(&settings.boundry_path).unwrap().to_luma8();
+            for (x, y, pixel) in boundry.enumerate_pixels() {
+                let expected: u8 = if canvas.get(x, y).is_frontier() {
+                    255u8
+                } else {
+                    0u8
+                };
+                assert_eq!(pixel.0, [expected], "{} boundry at {}, {}", format, x, y);
+            }
+        }
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
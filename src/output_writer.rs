@@ -0,0 +1,146 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::cell::Cell;
+use crate::output::OutputSettings;
+use crate::tiled_canvas::TiledCanvas;
+use crate::Constraints;
+
+// updates are spaced at least this many encode times apart,
+// so encoding never takes more than a fraction of the run
+const ENCODE_INTERVAL_FACTOR: f64 = 4f64;
+
+// the state of the canvas handed to the writer
+pub(crate) enum Snapshot {
+    // the painting's tiles, shared with it,
+    // only tiles painted while the snapshot is being written are copied
+    Shared(TiledCanvas<Cell>),
+    // copies of the tiles of a mapped painting that changed since the previous snapshot,
+    // by tile index, patched into the writer's own mapped canvas
+    ChangedTiles(Vec<(usize, Vec<Cell>)>),
+}
+
+// encodes and writes snapshots on a background thread so the painting never waits for it
+pub(crate) struct OutputWriter {
+    sender: Option<SyncSender<Snapshot>>,
+    thread: Option<JoinHandle<()>>,
+    // true from handing over a snapshot until it has been written
+    busy: Arc<AtomicBool>,
+    // how long the last snapshot took to write
+    last_encode_nanos: Arc<AtomicU64>,
+    minimum_interval: Duration,
+}
+impl OutputWriter {
+    pub(crate) fn spawn(
+        settings: Arc<OutputSettings>,
+        minimum_interval: Duration,
+        canvas_constraints: &Constraints,
+        mapped_directory: Option<&str>,
+    ) -> OutputWriter {
+        // one snapshot at a time, the painting skips updates while the writer is busy
+        let (sender, receiver): (SyncSender<Snapshot>, Receiver<Snapshot>) = sync_channel(1);
+        let busy: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+        let last_encode_nanos: Arc<AtomicU64> = Arc::new(AtomicU64::new(0u64));
+
+        let thread_busy: Arc<AtomicBool> = Arc::clone(&busy);
+        let thread_encode_nanos: Arc<AtomicU64> = Arc::clone(&last_encode_nanos);
+        // a mapped painting cannot share its tiles,
+        // so the writer keeps a mapped copy of it, patched with the changed tiles
+        let mut mapped_copy: Option<TiledCanvas<Cell>> = mapped_directory.map(|directory| {
+            TiledCanvas::create(
+                canvas_constraints.x_size,
+                canvas_constraints.y_size,
+                Some(directory),
+                "writer",
+            )
+        });
+        let thread: JoinHandle<()> = thread::Builder::new()
+            .name(String::from("output writer"))
+            .spawn(move || {
+                for snapshot in receiver {
+                    let _busy_guard: BusyGuard = BusyGuard(Arc::clone(&thread_busy));
+                    let encode_start_time: Instant = Instant::now();
+                    let canvas: &TiledCanvas<Cell> = match &snapshot {
+                        Snapshot::Shared(canvas) => canvas,
+                        Snapshot::ChangedTiles(tiles) => {
+                            let canvas: &mut TiledCanvas<Cell> = mapped_copy
+                                .as_mut()
+                                .expect("[ERROR] changed tiles need a mapped canvas copy");
+                            for (tile_index, cells) in tiles {
+                                canvas.write_tile(*tile_index, cells);
+                            }
+                            canvas
+                        }
+                    };
+                    settings.write_painting(canvas);
+                    settings.write_boundry(canvas);
+                    thread_encode_nanos.store(
+                        encode_start_time.elapsed().as_nanos() as u64,
+                        Ordering::Relaxed,
+                    );
+                }
+            })
+            .expect("[ERROR] unable to start the output writer thread");
+
+        return OutputWriter {
+            sender: Some(sender),
+            thread: Some(thread),
+            busy,
+            last_encode_nanos,
+            minimum_interval,
+        };
+    }
+
+    // time to wait between updates, grows with the time the last update took to write
+    pub(crate) fn interval(&self) -> Duration {
+        let encode_time: Duration =
+            Duration::from_nanos(self.last_encode_nanos.load(Ordering::Relaxed));
+        self.minimum_interval
+            .max(encode_time.mul_f64(ENCODE_INTERVAL_FACTOR))
+    }
+
+    // hand a snapshot to the writer unless it is still busy with the previous one
+    // the snapshot is only taken when it will be written
+    pub(crate) fn submit(&self, take_snapshot: impl FnOnce() -> Snapshot) {
+        if self.busy.swap(true, Ordering::Acquire) {
+            return;
+        }
+        self.sender
+            .as_ref()
+            .expect("[ERROR] output writer already finished")
+            .send(take_snapshot())
+            .expect("[ERROR] output writer thread stopped");
+    }
+
+    // write a last snapshot and wait until everything is on disk
+    pub(crate) fn finish(&mut self, snapshot: Snapshot) {
+        if let Some(sender) = self.sender.take() {
+            // waits for the channel slot if a previous snapshot has not been picked up
+            self.busy.store(true, Ordering::Release);
+            sender
+                .send(snapshot)
+                .expect("[ERROR] output writer thread stopped");
+        }
+        if let Some(thread) = self.thread.take() {
+            thread
+                .join()
+                .expect("[ERROR] output writer thread panicked");
+        }
+    }
+}
+
+// clears the busy flag once a snapshot is written, or once writing it panicked
+// so the next submit finds the stopped thread instead of skipping every update
+struct BusyGuard(Arc<AtomicBool>);
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,300 @@
+use rand::Rng;
+
+// a weighted set of colors loaded from a palette file
+// colors listed more than once are drawn more often
+pub(crate) struct ColorPool {
+    colors: Vec<[u8; 3]>,
+    weights: Vec<f32>,
+    // running total of the weights, used to pick a color with one random draw
+    cumulative_weights: Vec<f32>,
+}
+impl ColorPool {
+    // parse a palette file, detecting the format from its contents
+    // supports GIMP .gpl, JASC .pal, Adobe .ase and plain hex lists
+    pub(crate) fn parse(bytes: &[u8]) -> Result<ColorPool, String> {
+        let entries: Vec<([u8; 3], f32)> = if bytes.starts_with(b"ASEF") {
+            parse_ase(bytes)?
+        } else {
+            let text: &str = std::str::from_utf8(bytes)
+                .map_err(|_| String::from("palette file is neither ASE nor text"))?;
+            let first_line: &str = text.lines().next().unwrap_or("").trim();
+            if first_line == "GIMP Palette" {
+                parse_gpl(text)?
+            } else if first_line == "JASC-PAL" {
+                parse_jasc_pal(text)?
+            } else {
+                parse_hex_list(text)?
+            }
+        };
+
+        return ColorPool::from_entries(entries);
+    }
+
+    // merge duplicate colors into a single weighted entry
+    pub(crate) fn from_entries(entries: Vec<([u8; 3], f32)>) -> Result<ColorPool, String> {
+        let mut colors: Vec<[u8; 3]> = Vec::new();
+        let mut weights: Vec<f32> = Vec::new();
+        for (color, weight) in entries {
+            // a negative or non-finite weight would break the ordering the sampler searches
+            if !weight.is_finite() || weight < 0f32 {
+                return Err(format!(
+                    "palette weights must be finite and not negative, found {}",
+                    weight
+                ));
+            }
+            match colors.iter().position(|existing| *existing == color) {
+                Some(index) => weights[index] += weight,
+                None => {
+                    colors.push(color);
+                    weights.push(weight);
+                }
+            }
+        }
+        if colors.is_empty() {
+            return Err(String::from("palette file contains no colors"));
+        }
+        let mut total_weight: f32 = 0f32;
+        let cumulative_weights: Vec<f32> = weights
+            .iter()
+            .map(|weight| {
+                total_weight += weight;
+                total_weight
+            })
+            .collect();
+        if total_weight <= 0f32 {
+            return Err(String::from("palette weights must not all be zero"));
+        }
+        if !total_weight.is_finite() {
+            return Err(String::from(
+                "palette weights add up to more than a float holds",
+            ));
+        }
+
+        return Ok(ColorPool {
+            colors,
+            weights,
+            cumulative_weights,
+        });
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    // the pool as a weighted hex list, which parses back into the same pool
+    pub(crate) fn to_hex_list(&self) -> String {
+        return self
+            .colors
+            .iter()
+            .zip(self.weights.iter())
+            .map(|(color, weight)| {
+                format!(
+                    "#{:02x}{:02x}{:02x} {}\n",
+                    color[0], color[1], color[2], weight
+                )
+            })
+            .collect();
+    }
+
+    // draw a color by weight, then move each channel up to `jitter` steps away from it
+    pub(crate) fn sample<R: Rng + ?Sized>(&self, rng: &mut R, jitter: f32) -> [u8; 3] {
+        let total_weight: f32 = self.cumulative_weights[self.cumulative_weights.len() - 1];
+        let draw: f32 = rng.gen::<f32>() * total_weight;
+        let index: usize = self
+            .cumulative_weights
+            .partition_point(|cumulative_weight| *cumulative_weight <= draw)
+            .min(self.colors.len() - 1);
+
+        let mut color: [u8; 3] = self.colors[index];
+        if jitter > 0f32 {
+            for channel in color.iter_mut() {
+                let offset: f32 = rng.gen_range(-jitter..=jitter);
+                *channel = (*channel as f32 + offset).round().clamp(0f32, 255f32) as u8;
+            }
+        }
+        return color;
+    }
+}
+
+// GIMP palette: a header, optional "Name:"/"Columns:" lines, '#' comments,
+// then one "R G B [name]" color per line
+fn parse_gpl(text: &str) -> Result<Vec<([u8; 3], f32)>, String> {
+    let mut entries: Vec<([u8; 3], f32)> = Vec::new();
+    for line in text.lines().skip(1) {
+        let line: &str = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:")
+        {
+            continue;
+        }
+        entries.push((parse_rgb_triplet(line)?, 1f32));
+    }
+    return Ok(entries);
+}
+
+// JASC palette: "JASC-PAL", a version line, the color count, then one "R G B" color per line
+fn parse_jasc_pal(text: &str) -> Result<Vec<([u8; 3], f32)>, String> {
+    let mut lines = text.lines().skip(2);
+    let color_count: usize = lines
+        .next()
+        .and_then(|line| line.trim().parse::<usize>().ok())
+        .ok_or_else(|| String::from("JASC palette is missing its color count"))?;
+
+    let mut entries: Vec<([u8; 3], f32)> = Vec::new();
+    for line in lines
+        .filter(|line| !line.trim().is_empty())
+        .take(color_count)
+    {
+        entries.push((parse_rgb_triplet(line.trim())?, 1f32));
+    }
+    if entries.len() != color_count {
+        return Err(format!(
+            "JASC palette lists {} colors but contains {}",
+            color_count,
+            entries.len()
+        ));
+    }
+    return Ok(entries);
+}
+
+// hex list: one "#RRGGBB" (or "RRGGBB") color per line, optionally followed by a weight
+fn parse_hex_list(text: &str) -> Result<Vec<([u8; 3], f32)>, String> {
+    let mut entries: Vec<([u8; 3], f32)> = Vec::new();
+    for line in text.lines() {
+        let line: &str = line.trim();
+        if line.is_empty() || line.starts_with(';') || line.starts_with("//") {
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+        let hex: &str = words.next().unwrap_or("");
+        let hex: &str = hex.strip_prefix('#').unwrap_or(hex);
+        if hex.len() != 6 || !hex.is_ascii() {
+            return Err(format!("\"{}\" is not a hex color", line));
+        }
+
+        let mut color: [u8; 3] = [0u8; 3];
+        for (channel_index, channel) in color.iter_mut().enumerate() {
+            *channel = u8::from_str_radix(&hex[channel_index * 2..channel_index * 2 + 2], 16)
+                .map_err(|_| format!("\"{}\" is not a hex color", line))?;
+        }
+
+        let weight: f32 = match words.next() {
+            Some(weight) => weight
+                .parse::<f32>()
+                .map_err(|_| format!("\"{}\" is not a valid weight", weight))?,
+            None => 1f32,
+        };
+        entries.push((color, weight));
+    }
+    return Ok(entries);
+}
+
+// Adobe swatch exchange: a binary list of blocks, of which only color entries matter
+// colors are stored as big endian floats in RGB, CMYK or gray
+fn parse_ase(bytes: &[u8]) -> Result<Vec<([u8; 3], f32)>, String> {
+    let truncated = || String::from("ASE file is truncated");
+    // lengths come from the file, so every offset is checked before it is used
+    let advance = |offset: usize, length: usize| -> Result<usize, String> {
+        offset.checked_add(length).ok_or_else(truncated)
+    };
+    let read_bytes = |offset: usize, length: usize| -> Result<&[u8], String> {
+        bytes
+            .get(offset..advance(offset, length)?)
+            .ok_or_else(truncated)
+    };
+    let read_u16 = |offset: usize| -> Result<u16, String> {
+        let slice: &[u8] = read_bytes(offset, 2usize)?;
+        Ok(u16::from_be_bytes([slice[0], slice[1]]))
+    };
+    let read_u32 = |offset: usize| -> Result<u32, String> {
+        let slice: &[u8] = read_bytes(offset, 4usize)?;
+        Ok(u32::from_be_bytes([slice[0], slice[1], slice[2], slice[3]]))
+    };
+    let read_f32 = |offset: usize| -> Result<f32, String> { Ok(f32::from_bits(read_u32(offset)?)) };
+    let to_channel = |value: f32| (value.clamp(0f32, 1f32) * 255f32).round() as u8;
+
+    // skip the signature and version, then read the block count
+    let block_count: u32 = read_u32(8)?;
+    let mut offset: usize = 12usize;
+
+    let mut entries: Vec<([u8; 3], f32)> = Vec::new();
+    for _ in 0..block_count {
+        let block_type: u16 = read_u16(offset)?;
+        let block_length: usize = read_u32(advance(offset, 2usize)?)? as usize;
+        let block_start: usize = advance(offset, 6usize)?;
+        offset = advance(block_start, block_length)?;
+        if offset > bytes.len() {
+            return Err(truncated());
+        }
+
+        // group start and end blocks carry no colors
+        if block_type != 0x0001 {
+            continue;
+        }
+
+        // skip the UTF-16 swatch name to reach the color model
+        let name_length: usize = read_u16(block_start)? as usize;
+        let model_start: usize = advance(advance(block_start, 2usize)?, name_length * 2usize)?;
+        let values_start: usize = advance(model_start, 4usize)?;
+        if values_start > offset {
+            return Err(truncated());
+        }
+        let model: &[u8] = read_bytes(model_start, 4usize)?;
+        // the values must also end inside their own block
+        let block_end: usize = offset;
+        let read_value = |index: usize| -> Result<f32, String> {
+            let value_start: usize = advance(values_start, index * 4usize)?;
+            if advance(value_start, 4usize)? > block_end {
+                return Err(truncated());
+            }
+            read_f32(value_start)
+        };
+
+        let color: [u8; 3] = match model {
+            b"RGB " => [
+                to_channel(read_value(0usize)?),
+                to_channel(read_value(1usize)?),
+                to_channel(read_value(2usize)?),
+            ],
+            b"CMYK" => {
+                let cyan: f32 = read_value(0usize)?;
+                let magenta: f32 = read_value(1usize)?;
+                let yellow: f32 = read_value(2usize)?;
+                let black: f32 = read_value(3usize)?;
+                [
+                    to_channel((1f32 - cyan) * (1f32 - black)),
+                    to_channel((1f32 - magenta) * (1f32 - black)),
+                    to_channel((1f32 - yellow) * (1f32 - black)),
+                ]
+            }
+            b"Gray" => {
+                let gray: u8 = to_channel(read_value(0usize)?);
+                [gray, gray, gray]
+            }
+            _ => {
+                return Err(format!(
+                    "unsupported ASE color model \"{}\"",
+                    String::from_utf8_lossy(model).trim()
+                ))
+            }
+        };
+        entries.push((color, 1f32));
+    }
+    return Ok(entries);
+}
+
+// parse the first three whitespace separated numbers of a line as a color
+fn parse_rgb_triplet(line: &str) -> Result<[u8; 3], String> {
+    let mut words = line.split_whitespace();
+    let mut color: [u8; 3] = [0u8; 3];
+    for channel in color.iter_mut() {
+        *channel = words
+            .next()
+            .and_then(|word| word.parse::<u8>().ok())
+            .ok_or_else(|| format!("\"{}\" is not an R G B color", line))?;
+    }
+    return Ok(color);
+}
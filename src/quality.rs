@@ -0,0 +1,264 @@
+// color distances above both this and three standard deviations over the mean count as seams
+const MIN_SEAM_DISTANCE: f32 = 32f32;
+// fewer connected seam cells than this are noise, not a visible seam
+const MIN_SEAM_LENGTH: usize = 8usize;
+// gamut coverage counts the occupied cells of the RGB cube divided into this many steps per axis
+const GAMUT_STEPS: usize = 16usize;
+// pixels with this alpha are painted, the shredder's state byte and opaque png pixels alike
+const PAINTED_ALPHA: u8 = 255u8;
+
+// metrics of a finished painting, so runs can be compared by more than eye
+#[derive(Clone, PartialEq)]
+pub(crate) struct QualityReport {
+    pub(crate) painted_count: u64,
+    // RGB distance between horizontally and vertically adjacent painted pixels
+    pub(crate) mean_distance: f32,
+    pub(crate) median_distance: f32,
+    pub(crate) p90_distance: f32,
+    pub(crate) p99_distance: f32,
+    // connected runs of pixels with an outlying distance to their neighbor
+    pub(crate) seam_count: usize,
+    // fraction of the RGB cube's cells holding at least one painted color
+    pub(crate) gamut_coverage: f32,
+    // box counting dimension of the seam pixels, 1 for straight seams, towards 2 for ragged ones
+    pub(crate) seam_dimension: f32,
+}
+impl QualityReport {
+    // score an image given as rows of RGBA pixels, pixels that are not opaque are unpainted
+    pub(crate) fn analyze(width: u32, height: u32, pixels: &[[u8; 4]]) -> QualityReport {
+        let (width, height): (usize, usize) = (width as usize, height as usize);
+        let painted = |x: usize, y: usize| -> Option<[u8; 4]> {
+            let pixel: [u8; 4] = pixels[y * width + x];
+            return (pixel[3] == PAINTED_ALPHA).then_some(pixel);
+        };
+
+        // the distance of every pixel to its right and lower neighbor
+        let mut right_distances: Vec<Option<f32>> = vec![None; width * height];
+        let mut lower_distances: Vec<Option<f32>> = vec![None; width * height];
+        let mut distances: Vec<f32> = Vec::new();
+        let mut gamut: Vec<bool> = vec![false; GAMUT_STEPS * GAMUT_STEPS * GAMUT_STEPS];
+        let mut painted_count: u64 = 0u64;
+        for y in 0..height {
+            for x in 0..width {
+                let Some(pixel) = painted(x, y) else { continue };
+                painted_count += 1;
+                let step = |channel: u8| -> usize { channel as usize * GAMUT_STEPS / 256usize };
+                gamut[(step(pixel[0]) * GAMUT_STEPS + step(pixel[1])) * GAMUT_STEPS
+                    + step(pixel[2])] = true;
+
+                if let Some(right) = (x + 1 < width).then(|| painted(x + 1, y)).flatten() {
+                    let distance: f32 = color_distance(pixel, right);
+                    right_distances[y * width + x] = Some(distance);
+                    distances.push(distance);
+                }
+                if let Some(lower) = (y + 1 < height).then(|| painted(x, y + 1)).flatten() {
+                    let distance: f32 = color_distance(pixel, lower);
+                    lower_distances[y * width + x] = Some(distance);
+                    distances.push(distance);
+                }
+            }
+        }
+
+        let mean_distance: f32 = if distances.is_empty() {
+            0f32
+        } else {
+            distances.iter().sum::<f32>() / distances.len() as f32
+        };
+        let variance: f32 = if distances.is_empty() {
+            0f32
+        } else {
+            distances
+                .iter()
+                .map(|distance| (distance - mean_distance).powi(2))
+                .sum::<f32>()
+                / distances.len() as f32
+        };
+        let seam_threshold: f32 = (mean_distance + 3f32 * variance.sqrt()).max(MIN_SEAM_DISTANCE);
+        distances.sort_by(f32::total_cmp);
+
+        // a pixel belongs to a seam when the step to its right or lower neighbor is an outlier
+        let seam_pixels: Vec<bool> = (0..width * height)
+            .map(|index| {
+                right_distances[index].is_some_and(|distance| distance > seam_threshold)
+                    || lower_distances[index].is_some_and(|distance| distance > seam_threshold)
+            })
+            .collect();
+
+        return QualityReport {
+            painted_count,
+            mean_distance,
+            median_distance: percentile(&distances, 0.5f32),
+            p90_distance: percentile(&distances, 0.9f32),
+            p99_distance: percentile(&distances, 0.99f32),
+            seam_count: count_seams(width, height, &seam_pixels),
+            gamut_coverage: gamut.iter().filter(|occupied| **occupied).count() as f32
+                / gamut.len() as f32,
+            seam_dimension: box_counting_dimension(width, height, &seam_pixels),
+        };
+    }
+
+    // label and formatted value of every metric, for the command line and the web panel
+    pub(crate) fn lines(&self) -> Vec<(&'static str, String)> {
+        return vec![
+            ("painted pixels", self.painted_count.to_string()),
+            (
+                "mean neighbor distance",
+                format!("{:.2}", self.mean_distance),
+            ),
+            (
+                "median neighbor distance",
+                format!("{:.2}", self.median_distance),
+            ),
+            (
+                "90th percentile distance",
+                format!("{:.2}", self.p90_distance),
+            ),
+            (
+                "99th percentile distance",
+                format!("{:.2}", self.p99_distance),
+            ),
+            ("visible seams", self.seam_count.to_string()),
+            (
+                "gamut coverage",
+                format!("{:.1}%", self.gamut_coverage * 100f32),
+            ),
+            ("seam roughness", format!("{:.3}", self.seam_dimension)),
+        ];
+    }
+}
+
+fn color_distance(a: [u8; 4], b: [u8; 4]) -> f32 {
+    let red: f32 = a[0] as f32 - b[0] as f32;
+    let green: f32 = a[1] as f32 - b[1] as f32;
+    let blue: f32 = a[2] as f32 - b[2] as f32;
+    return (red * red + green * green + blue * blue).sqrt();
+}
+
+// nearest rank percentile of sorted values, 0 without values
+fn percentile(sorted: &[f32], fraction: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0f32;
+    }
+    let rank: usize = ((sorted.len() - 1) as f32 * fraction).round() as usize;
+    return sorted[rank];
+}
+
+// groups of eight-connected seam pixels that are long enough to be seen
+fn count_seams(width: usize, height: usize, seam_pixels: &[bool]) -> usize {
+    let mut visited: Vec<bool> = vec![false; width * height];
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    let mut seam_count: usize = 0usize;
+    for start in 0..width * height {
+        if !seam_pixels[start] || visited[start] {
+            continue;
+        }
+
+        // flood fill the group from its first pixel
+        visited[start] = true;
+        stack.push((start % width, start / width));
+        let mut group_size: usize = 0usize;
+        while let Some((x, y)) = stack.pop() {
+            group_size += 1;
+            for neighbor_y in y.saturating_sub(1)..(y + 2).min(height) {
+                for neighbor_x in x.saturating_sub(1)..(x + 2).min(width) {
+                    let index: usize = neighbor_y * width + neighbor_x;
+                    if seam_pixels[index] && !visited[index] {
+                        visited[index] = true;
+                        stack.push((neighbor_x, neighbor_y));
+                    }
+                }
+            }
+        }
+        if group_size >= MIN_SEAM_LENGTH {
+            seam_count += 1;
+        }
+    }
+    return seam_count;
+}
+
+// slope of log(occupied boxes) over log(1 / box size), for box sizes doubling from one pixel
+fn box_counting_dimension(width: usize, height: usize, seam_pixels: &[bool]) -> f32 {
+    let mut points: Vec<(f32, f32)> = Vec::new();
+    let mut box_size: usize = 1usize;
+    while box_size < width.max(height) {
+        let boxes_across: usize = width.div_ceil(box_size);
+        let mut occupied: Vec<bool> = vec![false; boxes_across * height.div_ceil(box_size)];
+        for (index, _) in seam_pixels.iter().enumerate().filter(|(_, seam)| **seam) {
+            occupied[(index / width / box_size) * boxes_across + index % width / box_size] = true;
+        }
+        let occupied_count: usize = occupied.iter().filter(|occupied| **occupied).count();
+        if occupied_count == 0 {
+            return 0f32;
+        }
+        points.push(((1f32 / box_size as f32).ln(), (occupied_count as f32).ln()));
+        box_size *= 2;
+    }
+    if points.len() < 2 {
+        return 0f32;
+    }
+
+    // least squares fit
+    let count: f32 = points.len() as f32;
+    let mean_x: f32 = points.iter().map(|(x, _)| x).sum::<f32>() / count;
+    let mean_y: f32 = points.iter().map(|(_, y)| y).sum::<f32>() / count;
+    let covariance: f32 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f32 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    return covariance / variance;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [u8; 4] = [255u8, 255u8, 255u8, 255u8];
+    const BLACK: [u8; 4] = [0u8, 0u8, 0u8, 255u8];
+    const UNPAINTED: [u8; 4] = [255u8, 255u8, 255u8, 0u8];
+
+    // a square image drawn by a function of the pixel coordinates
+    fn image(size: u32, pixel: impl Fn(u32, u32) -> [u8; 4]) -> Vec<[u8; 4]> {
+        return (0..size * size)
+            .map(|index| pixel(index % size, index / size))
+            .collect();
+    }
+
+    #[test]
+    fn finds_a_straight_seam() {
+        let pixels: Vec<[u8; 4]> = image(32u32, |x, _| if x < 16 { BLACK } else { WHITE });
+        let report: QualityReport = QualityReport::analyze(32u32, 32u32, &pixels);
+        assert_eq!(report.painted_count, 1024u64);
+        assert_eq!(report.seam_count, 1usize);
+        assert!((report.seam_dimension - 1f32).abs() < 1e-4);
+        assert_eq!(report.median_distance, 0f32);
+        assert!((report.p99_distance - color_distance(BLACK, WHITE)).abs() < 1e-3);
+        assert_eq!(report.gamut_coverage, 2f32 / 4096f32);
+    }
+
+    #[test]
+    fn smooth_gradients_have_no_seams() {
+        let pixels: Vec<[u8; 4]> = image(64u32, |x, y| [x as u8 * 4, y as u8 * 4, 128u8, 255u8]);
+        let report: QualityReport = QualityReport::analyze(64u32, 64u32, &pixels);
+        assert_eq!(report.seam_count, 0usize);
+        assert_eq!(report.seam_dimension, 0f32);
+        assert!((report.mean_distance - 4f32).abs() < 1e-4);
+    }
+
+    #[test]
+    fn skips_unpainted_pixels() {
+        // every painted pixel borders only unpainted ones
+        let pixels: Vec<[u8; 4]> = image(16u32, |x, y| {
+            if (x + y).is_multiple_of(2u32) {
+                BLACK
+            } else {
+                UNPAINTED
+            }
+        });
+        let report: QualityReport = QualityReport::analyze(16u32, 16u32, &pixels);
+        assert_eq!(report.painted_count, 128u64);
+        assert_eq!(report.mean_distance, 0f32);
+        assert_eq!(report.seam_count, 0usize);
+        assert_eq!(report.gamut_coverage, 1f32 / 4096f32);
+    }
+}
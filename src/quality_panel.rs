@@ -0,0 +1,44 @@
+use yew::{html, Component, Context, Html, Properties};
+
+use crate::quality::QualityReport;
+
+#[derive(Properties, PartialEq)]
+pub(crate) struct QualityPanelProps {
+    pub(crate) report: QualityReport,
+}
+
+// yew sub-component listing the quality metrics of the finished painting
+pub(crate) struct QualityPanel;
+impl Component for QualityPanel {
+    type Message = ();
+    type Properties = QualityPanelProps;
+
+    // QualityPanel init
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self
+    }
+
+    // QualityPanel view logic
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let rows: Html = ctx
+            .props()
+            .report
+            .lines()
+            .into_iter()
+            .map(|(label, value)| {
+                html! {
+                    <div class="centered-div">
+                        <label class="status-text slider-label">{label}</label>
+                        <span class="status-text">{value}</span>
+                    </div>
+                }
+            })
+            .collect();
+
+        html! {
+            <div>
+                {rows}
+            </div>
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use serde_json::Value;
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{Event, EventSource, MessageEvent};
+use yew::Callback;
+
+use crate::cell::Cell;
+use crate::run_stats::StatsSample;
+use crate::tiled_canvas::TiledCanvas;
+
+// what the event stream of a render service job reports
+pub(crate) enum RemoteEvent {
+    Status(RemoteStatus),
+    // the job's canvas, downscaled by the service
+    Preview(TiledCanvas<Cell>),
+    // the connection was lost or an event could not be read
+    Error(String),
+}
+
+pub(crate) struct RemoteStatus {
+    // queued, running, done, cancelled or failed
+    pub(crate) state: String,
+    pub(crate) total_pixel_count: u64,
+    // None until the job's first progress update
+    pub(crate) sample: Option<StatsSample>,
+    pub(crate) error: Option<String>,
+}
+impl RemoteStatus {
+    // the service ends the stream after the status of a finished job
+    pub(crate) fn is_finished(&self) -> bool {
+        matches!(self.state.as_str(), "done" | "cancelled" | "failed")
+    }
+}
+
+// a render service job followed over its event stream, dropping it closes the connection
+pub(crate) struct RemoteRun {
+    event_source: EventSource,
+    // the listeners are called by the event source for as long as it is open
+    _status_listener: Closure<dyn FnMut(MessageEvent)>,
+    _preview_listener: Closure<dyn FnMut(MessageEvent)>,
+    _error_listener: Closure<dyn FnMut(Event)>,
+}
+impl RemoteRun {
+    // connect to the events url of a job, e.g. http://127.0.0.1:7878/jobs/1/events
+    pub(crate) fn connect(url: &str, on_event: Callback<RemoteEvent>) -> Result<RemoteRun, String> {
+        let event_source: EventSource =
+            EventSource::new(url).map_err(|_| format!("unable to connect to {}", url))?;
+
+        let status_callback: Callback<RemoteEvent> = on_event.clone();
+        let status_listener =
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                let data: String = event.data().as_string().unwrap_or_default();
+                status_callback.emit(parse_status(&data));
+            });
+        let preview_callback: Callback<RemoteEvent> = on_event.clone();
+        let preview_listener =
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                let data: String = event.data().as_string().unwrap_or_default();
+                preview_callback.emit(parse_preview(&data));
+            });
+        // the browser would keep reconnecting, the canvas gives up instead
+        let error_listener = Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+            on_event.emit(RemoteEvent::Error(String::from(
+                "lost the connection to the render service",
+            )));
+        });
+
+        event_source
+            .add_event_listener_with_callback("status", status_listener.as_ref().unchecked_ref())
+            .and_then(|_| {
+                event_source.add_event_listener_with_callback(
+                    "preview",
+                    preview_listener.as_ref().unchecked_ref(),
+                )
+            })
+            .map_err(|_| String::from("unable to listen to the event stream"))?;
+        event_source.set_onerror(Some(error_listener.as_ref().unchecked_ref()));
+
+        return Ok(RemoteRun {
+            event_source,
+            _status_listener: status_listener,
+            _preview_listener: preview_listener,
+            _error_listener: error_listener,
+        });
+    }
+
+    // stop listening, no more events are reported after this
+    pub(crate) fn close(&self) {
+        self.event_source.close();
+    }
+}
+impl Drop for RemoteRun {
+    fn drop(&mut self) {
+        self.event_source.close();
+    }
+}
+
+// the job status json of the service
+fn parse_status(data: &str) -> RemoteEvent {
+    let Ok(value) = serde_json::from_str::<Value>(data) else {
+        return RemoteEvent::Error(String::from("unreadable status from the render service"));
+    };
+    return RemoteEvent::Status(RemoteStatus {
+        state: String::from(value["state"].as_str().unwrap_or("unknown")),
+        total_pixel_count: value["total_pixels"].as_u64().unwrap_or(0u64),
+        sample: StatsSample::from_json(&value["stats"]),
+        error: value["error"].as_str().map(String::from),
+    });
+}
+
+// width, height and the base64 encoded cells of the preview, row by row
+fn parse_preview(data: &str) -> RemoteEvent {
+    let preview: Option<TiledCanvas<Cell>> =
+        serde_json::from_str::<Value>(data).ok().and_then(|value| {
+            let width: u32 = value["width"].as_u64()? as u32;
+            let height: u32 = value["height"].as_u64()? as u32;
+            let bytes: Vec<u8> = decode_base64(value["cells"].as_str()?)?;
+            if width == 0 || bytes.len() != width as usize * height as usize * 4usize {
+                return None;
+            }
+
+            // the cells are RGBA with the state byte as alpha, like the canvas's own
+            let cells: &[Cell] = bytemuck::cast_slice(&bytes);
+            let mut canvas: TiledCanvas<Cell> = TiledCanvas::new(width, height);
+            for (cell_index, cell) in cells.iter().enumerate() {
+                canvas.put(cell_index as u32 % width, cell_index as u32 / width, *cell);
+            }
+            canvas.mark_all_dirty();
+            Some(canvas)
+        });
+    return match preview {
+        Some(canvas) => RemoteEvent::Preview(canvas),
+        None => RemoteEvent::Error(String::from("unreadable preview from the render service")),
+    };
+}
+
+// the browser's atob decodes into a string with one character per byte
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let decoded: String = web_sys::window()?.atob(text).ok()?;
+    return Some(decoded.chars().map(|character| character as u8).collect());
+}
@@ -0,0 +1,826 @@
+// local http render service, so other tools can request paintings without a config file
+//     cargo run --release --features server --bin render-server -- [address] [output directory]
+// POST   /jobs                    queue a config, given as the json form of config.yaml
+// GET    /jobs                    status of every job
+// GET    /jobs/<id>               status and progress of a job
+// GET    /jobs/<id>/painting.png  the painting so far, the final painting once the job is done
+// GET    /jobs/<id>/events        server-sent events with the status and a downscaled preview
+//                                 of the job whenever it changes, until the job has finished
+// DELETE /jobs/<id>               cancel a queued or running job
+// configs may not name input files, and the service picks every output path itself
+// only the latest finished jobs are kept, older ones are forgotten (their files stay on disk)
+// a fixed set of threads answers the connections, event streams may only hold part of them
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs,
+    io::{Cursor, Write},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use strict_yaml_rust::{StrictYaml, StrictYamlEmitter};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// the engine is a binary, so its sources are compiled as a module of the service
+// and its modules find the items they reach through the crate root via the glob import
+#[path = "shredder.rs"]
+#[allow(dead_code)]
+mod shredder;
+
+use shredder::cell::Cell;
+use shredder::run_stats::StatsSample;
+use shredder::sweep::set_config_value;
+use shredder::tiled_canvas::TiledCanvas;
+use shredder::*;
+
+const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+const DEFAULT_OUTPUT_DIRECTORY: &str = "./output/jobs";
+// the engine already scores on every core, so jobs are rendered one at a time
+const RENDER_WORKERS: usize = 1usize;
+// submissions beyond this many waiting jobs are turned away until the queue drains
+const QUEUE_CAPACITY: usize = 16usize;
+// finished jobs beyond this many are forgotten, oldest first
+const FINISHED_JOB_LIMIT: usize = 64usize;
+// largest canvas a job may paint, 4096 by 4096 cells
+const MAX_CANVAS_PIXELS: u64 = 1u64 << 24;
+// config keys that name files to read, the service does not hand out its file system
+const INPUT_FILE_KEYS: [&str; 2] = ["path", "file"];
+// edge length the event stream's previews are shrunk to fit
+const PREVIEW_SIZE: u32 = 256u32;
+// an idle event stream sends a comment this often, so closed connections are noticed
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+// threads answering connections, each one holds a connection until it is answered
+const CONNECTION_THREADS: usize = 32usize;
+// event streams stay open until their job finishes, the other threads keep answering requests
+const MAX_EVENT_STREAMS: usize = 24usize;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+enum JobState {
+    Queued,
+    Running,
+    Done,
+    Cancelled,
+    // the engine's panic message, usually a config error
+    Failed(String),
+}
+impl JobState {
+    fn name(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Done => "done",
+            JobState::Cancelled => "cancelled",
+            JobState::Failed(_) => "failed",
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobState::Done | JobState::Cancelled | JobState::Failed(_)
+        )
+    }
+}
+
+struct Job {
+    // the submitted config as yaml, with the service's output settings
+    config: String,
+    state: JobState,
+    // checked by the worker after every placement
+    cancel_requested: Arc<AtomicBool>,
+    // known once the canvas is initialized
+    total_pixel_count: u64,
+    painting_path: Option<String>,
+    // latest statistics of the run, None until its first update
+    progress: Option<StatsSample>,
+    // data of the latest preview event, shared by every event stream of the job
+    preview: Option<Arc<String>>,
+    // counts the changes of the job, event streams send an update when it moved on
+    update_count: u64,
+}
+impl Job {
+    fn to_json(&self, id: u64) -> String {
+        let error: String = match &self.state {
+            JobState::Failed(message) => Value::String(message.clone()).to_string(),
+            _ => String::from("null"),
+        };
+        let pixels_placed: u64 = self
+            .progress
+            .map_or(0u64, |sample| sample.pixels_placed_count);
+        let progress: f64 = match self.state {
+            JobState::Done => 1f64,
+            _ if self.total_pixel_count > 0 => pixels_placed as f64 / self.total_pixel_count as f64,
+            _ => 0f64,
+        };
+        format!(
+            "{{\"id\": {}, \"state\": \"{}\", \"progress\": {:.4}, \"pixels_placed\": {}, \"total_pixels\": {}, \"stats\": {}, \"error\": {}}}",
+            id,
+            self.state.name(),
+            progress,
+            pixels_placed,
+            self.total_pixel_count,
+            self.progress
+                .map_or(String::from("null"), |sample| sample.to_json()),
+            error
+        )
+    }
+}
+
+struct Jobs {
+    entries: BTreeMap<u64, Job>,
+    // ids of the queued jobs, oldest first
+    queue: VecDeque<u64>,
+    next_id: u64,
+}
+
+// the jobs shared by the http handlers and the render workers
+struct RenderService {
+    jobs: Mutex<Jobs>,
+    // signalled whenever a job is queued
+    job_queued: Condvar,
+    // signalled whenever any job changes
+    job_updated: Condvar,
+    // event streams currently holding a connection thread
+    open_event_streams: AtomicUsize,
+    output_directory: String,
+}
+impl RenderService {
+    fn new(output_directory: String) -> RenderService {
+        RenderService {
+            jobs: Mutex::new(Jobs {
+                entries: BTreeMap::new(),
+                queue: VecDeque::new(),
+                next_id: 1u64,
+            }),
+            job_queued: Condvar::new(),
+            job_updated: Condvar::new(),
+            open_event_streams: AtomicUsize::new(0usize),
+            output_directory,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Jobs> {
+        self.jobs.lock().expect("[ERROR] a render worker panicked")
+    }
+
+    fn update(&self, id: u64, change: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.lock().entries.get_mut(&id) {
+            change(job);
+            job.update_count += 1;
+        }
+        self.job_updated.notify_all();
+    }
+}
+
+fn main() {
+    let arguments: Vec<String> = std::env::args().collect();
+    let address: &str = arguments.get(1).map_or(DEFAULT_ADDRESS, String::as_str);
+    let service: Arc<RenderService> = Arc::new(RenderService::new(
+        arguments
+            .get(2)
+            .map_or(String::from(DEFAULT_OUTPUT_DIRECTORY), String::clone),
+    ));
+
+    for worker_index in 0..RENDER_WORKERS {
+        let worker_service: Arc<RenderService> = Arc::clone(&service);
+        thread::Builder::new()
+            .name(format!("render worker {}", worker_index))
+            .spawn(move || render_worker(&worker_service))
+            .expect("[ERROR] unable to start a render worker");
+    }
+
+    let server: Arc<Server> = Arc::new(
+        Server::http(address)
+            .unwrap_or_else(|error| panic!("[ERROR] unable to listen on {}: {}", address, error)),
+    );
+    eprintln!("render service listening on http://{}", address);
+    // connections beyond the threads wait in the listen backlog until a thread is free
+    let connection_threads: Vec<thread::JoinHandle<()>> = (0..CONNECTION_THREADS)
+        .map(|thread_index| {
+            let thread_server: Arc<Server> = Arc::clone(&server);
+            let thread_service: Arc<RenderService> = Arc::clone(&service);
+            thread::Builder::new()
+                .name(format!("connection {}", thread_index))
+                .spawn(move || {
+                    for request in thread_server.incoming_requests() {
+                        handle_request(request, &thread_service);
+                    }
+                })
+                .expect("[ERROR] unable to start a connection thread")
+        })
+        .collect();
+    for connection_thread in connection_threads {
+        connection_thread
+            .join()
+            .expect("[ERROR] a connection thread panicked");
+    }
+}
+
+fn handle_request(mut request: Request, service: &RenderService) {
+    let path: String = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let segments: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    let method: Method = request.method().clone();
+    // the event stream answers on the connection itself instead of with a single response
+    if let (Method::Get, ["jobs", id, "events"]) = (&method, segments.as_slice()) {
+        stream_events(request, service, id);
+        return;
+    }
+    let response: HttpResponse = match (method, segments.as_slice()) {
+        (Method::Post, ["jobs"]) => submit_job(&mut request, service),
+        (Method::Get, ["jobs"]) => {
+            let jobs = service.lock();
+            let entries: Vec<String> = jobs
+                .entries
+                .iter()
+                .map(|(id, job)| job.to_json(*id))
+                .collect();
+            json_response(200, format!("[{}]", entries.join(", ")))
+        }
+        (Method::Get, ["jobs", id]) => match with_job(service, id, |id, job| job.to_json(id)) {
+            Ok(status) => json_response(200, status),
+            Err(response) => response,
+        },
+        (Method::Get, ["jobs", id, "painting.png"]) => painting_response(service, id),
+        (Method::Delete, ["jobs", id]) => cancel_job(service, id),
+        _ => error_response(404, "not found"),
+    };
+    request
+        .respond(response)
+        .unwrap_or_else(|error| eprintln!("[WARNING] unable to answer a request: {}", error));
+}
+
+// read the request body as json and queue it
+fn submit_job(request: &mut Request, service: &RenderService) -> HttpResponse {
+    return match serde_json::from_reader(request.as_reader()) {
+        Ok(body) => queue_job(&body, service),
+        Err(error) => error_response(400, &format!("invalid json: {}", error)),
+    };
+}
+
+// check a submitted body and queue its config, answers the new job's status
+fn queue_job(body: &Value, service: &RenderService) -> HttpResponse {
+    if !body["config"].is_object() {
+        return error_response(400, "the body requires a config object");
+    }
+    if let Some(key) = input_file_key(&body["config"], "config") {
+        return error_response(400, &format!("configs may not name files: {}", key));
+    }
+    let mut config: StrictYaml = to_strict_yaml(body);
+    let canvas_size = |axis: &str| -> Option<u64> {
+        config["config"]["canvas"]["size"][axis]
+            .as_str()?
+            .parse::<u64>()
+            .ok()
+    };
+    match (canvas_size("x"), canvas_size("y")) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => {
+            if width.saturating_mul(height) > MAX_CANVAS_PIXELS {
+                return error_response(
+                    400,
+                    &format!("the canvas may have at most {} cells", MAX_CANVAS_PIXELS),
+                );
+            }
+        }
+        _ => return error_response(400, "the config requires a canvas size"),
+    }
+
+    let mut jobs = service.lock();
+    if jobs.queue.len() >= QUEUE_CAPACITY {
+        return error_response(503, "the job queue is full");
+    }
+    evict_finished_jobs(&mut jobs);
+    let id: u64 = jobs.next_id;
+    jobs.next_id += 1;
+
+    // every job writes png files into its own directory, without a progress line,
+    // under names the service picks
+    let job_directory: String = format!("{}/{}", service.output_directory, id);
+    for (key, value) in [
+        ("config.output.directory", job_directory.as_str()),
+        ("config.output.painting_name", "painting"),
+        ("config.output.boundry_name", "boundry"),
+        ("config.canvas.storage_directory", job_directory.as_str()),
+    ] {
+        set_config_value(&mut config, key, StrictYaml::String(String::from(value)));
+    }
+    set_config_value(
+        &mut config,
+        "config.output.format",
+        StrictYaml::String(String::from("png")),
+    );
+    set_config_value(
+        &mut config,
+        "config.output.stats.progress",
+        StrictYaml::String(String::from("false")),
+    );
+    let mut config_string: String = String::new();
+    if let Err(error) = StrictYamlEmitter::new(&mut config_string).dump(&config) {
+        return error_response(400, &format!("unable to convert the config: {:?}", error));
+    }
+
+    let job: Job = Job {
+        config: config_string,
+        state: JobState::Queued,
+        cancel_requested: Arc::new(AtomicBool::new(false)),
+        total_pixel_count: 0u64,
+        painting_path: None,
+        progress: None,
+        preview: None,
+        update_count: 0u64,
+    };
+    let status: String = job.to_json(id);
+    jobs.entries.insert(id, job);
+    jobs.queue.push_back(id);
+    service.job_queued.notify_one();
+    return json_response(202, status);
+}
+
+// the dotted path of the first key that names an input file, None when there is none
+fn input_file_key(value: &Value, path: &str) -> Option<String> {
+    match value {
+        Value::Object(entries) => entries.iter().find_map(|(key, value)| {
+            let key_path: String = format!("{}.{}", path, key);
+            if INPUT_FILE_KEYS.contains(&key.as_str()) && !value.is_null() {
+                return Some(key_path);
+            }
+            input_file_key(value, &key_path)
+        }),
+        Value::Array(items) => items
+            .iter()
+            .enumerate()
+            .find_map(|(index, item)| input_file_key(item, &format!("{}.{}", path, index))),
+        _ => None,
+    }
+}
+
+// forget the oldest finished jobs beyond the limit, running and queued jobs are kept
+fn evict_finished_jobs(jobs: &mut Jobs) {
+    let finished_ids: Vec<u64> = jobs
+        .entries
+        .iter()
+        .filter(|(_, job)| job.state.is_finished())
+        .map(|(id, _)| *id)
+        .collect();
+    let evicted_count: usize = finished_ids.len().saturating_sub(FINISHED_JOB_LIMIT);
+    for id in &finished_ids[..evicted_count] {
+        jobs.entries.remove(id);
+    }
+}
+
+// the latest painting file of a job, written atomically by the engine
+fn painting_response(service: &RenderService, id: &str) -> HttpResponse {
+    let painting_path: Option<String> =
+        match with_job(service, id, |_, job| job.painting_path.clone()) {
+            Ok(painting_path) => painting_path,
+            Err(response) => return response,
+        };
+    return match painting_path.map(fs::read) {
+        Some(Ok(bytes)) => {
+            Response::from_data(bytes).with_header(header("Content-Type", "image/png"))
+        }
+        _ => error_response(404, "the job has no painting yet"),
+    };
+}
+
+// queued jobs are dropped from the queue, running jobs stop after their current placement
+fn cancel_job(service: &RenderService, id: &str) -> HttpResponse {
+    let Ok(id) = id.parse::<u64>() else {
+        return error_response(404, "unknown job");
+    };
+    let mut jobs = service.lock();
+    let Some(job) = jobs.entries.get_mut(&id) else {
+        return error_response(404, "unknown job");
+    };
+    return match job.state {
+        JobState::Queued => {
+            job.state = JobState::Cancelled;
+            job.update_count += 1;
+            let status: String = job.to_json(id);
+            jobs.queue.retain(|queued_id| *queued_id != id);
+            service.job_updated.notify_all();
+            json_response(200, status)
+        }
+        JobState::Running => {
+            job.cancel_requested.store(true, Ordering::Relaxed);
+            json_response(202, job.to_json(id))
+        }
+        _ => error_response(409, "the job has already finished"),
+    };
+}
+
+// send a "status" event with the job's status json and a "preview" event with its latest
+// preview every time the job changes, the stream ends once the job has finished
+fn stream_events(request: Request, service: &RenderService, id: &str) {
+    let id: Option<u64> = id
+        .parse::<u64>()
+        .ok()
+        .filter(|id| service.lock().entries.contains_key(id));
+    let Some(id) = id else {
+        request
+            .respond(error_response(404, "unknown job"))
+            .unwrap_or_else(|error| eprintln!("[WARNING] unable to answer a request: {}", error));
+        return;
+    };
+
+    let Some(_stream_slot) = EventStreamSlot::take(&service.open_event_streams) else {
+        request
+            .respond(error_response(503, "too many event streams are open"))
+            .unwrap_or_else(|error| eprintln!("[WARNING] unable to answer a request: {}", error));
+        return;
+    };
+
+    // the web canvas follows the stream from another origin
+    let mut writer: Box<dyn Write + Send> = request.into_writer();
+    let headers: &str = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n";
+    if writer
+        .write_all(headers.as_bytes())
+        .and_then(|_| writer.flush())
+        .is_err()
+    {
+        return;
+    }
+
+    let mut sent_update_count: Option<u64> = None;
+    let mut sent_preview: Option<Arc<String>> = None;
+    let mut last_write: Instant = Instant::now();
+    loop {
+        let mut jobs = service.lock();
+        // every job's updates wake every stream, so others' updates only shorten the wait
+        while jobs.entries.get(&id).map(|job| job.update_count) == sent_update_count {
+            let remaining: Duration = KEEPALIVE_INTERVAL.saturating_sub(last_write.elapsed());
+            if remaining.is_zero() {
+                break;
+            }
+            jobs = service
+                .job_updated
+                .wait_timeout(jobs, remaining)
+                .expect("[ERROR] a render worker panicked")
+                .0;
+        }
+
+        // finished jobs may have been evicted meanwhile, which ends the stream
+        let Some(job) = jobs.entries.get(&id) else {
+            return;
+        };
+        let mut events: String = String::new();
+        if Some(job.update_count) == sent_update_count {
+            events.push_str(": keepalive\n\n");
+        } else {
+            sent_update_count = Some(job.update_count);
+            events.push_str(&format!("event: status\ndata: {}\n\n", job.to_json(id)));
+            if let Some(preview) = job.preview.as_ref().filter(|preview| {
+                !sent_preview
+                    .as_ref()
+                    .is_some_and(|sent_preview| Arc::ptr_eq(sent_preview, preview))
+            }) {
+                events.push_str(&format!("event: preview\ndata: {}\n\n", preview));
+                sent_preview = Some(Arc::clone(preview));
+            }
+        }
+        let is_finished: bool = job.state.is_finished();
+        drop(jobs);
+
+        // a failed write means the client went away
+        if writer
+            .write_all(events.as_bytes())
+            .and_then(|_| writer.flush())
+            .is_err()
+            || is_finished
+        {
+            return;
+        }
+        last_write = Instant::now();
+    }
+}
+
+// one of the event streams counted against MAX_EVENT_STREAMS, given back when dropped
+struct EventStreamSlot<'a> {
+    open_event_streams: &'a AtomicUsize,
+}
+impl<'a> EventStreamSlot<'a> {
+    fn take(open_event_streams: &'a AtomicUsize) -> Option<EventStreamSlot<'a>> {
+        open_event_streams
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+                (count < MAX_EVENT_STREAMS).then_some(count + 1)
+            })
+            .ok()?;
+        return Some(EventStreamSlot { open_event_streams });
+    }
+}
+impl Drop for EventStreamSlot<'_> {
+    fn drop(&mut self) {
+        self.open_event_streams.fetch_sub(1usize, Ordering::AcqRel);
+    }
+}
+
+// take queued jobs one by one and render them
+fn render_worker(service: &RenderService) {
+    loop {
+        let (id, config, cancel_requested) = {
+            let mut jobs = service.lock();
+            let id: u64 = loop {
+                match jobs.queue.pop_front() {
+                    Some(id) => break id,
+                    None => {
+                        jobs = service
+                            .job_queued
+                            .wait(jobs)
+                            .expect("[ERROR] a render worker panicked");
+                    }
+                }
+            };
+            let job: &mut Job = jobs
+                .entries
+                .get_mut(&id)
+                .expect("[ERROR] queued job is missing");
+            job.state = JobState::Running;
+            (id, job.config.clone(), Arc::clone(&job.cancel_requested))
+        };
+
+        // a config error panics inside the engine, that fails the job but not the service
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            render_job(service, id, &config, &cancel_requested)
+        }));
+        let state: JobState = match result {
+            Ok(true) => JobState::Done,
+            Ok(false) => JobState::Cancelled,
+            Err(payload) => JobState::Failed(
+                payload
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| {
+                        payload
+                            .downcast_ref::<&str>()
+                            .map(|message| message.to_string())
+                    })
+                    .unwrap_or_else(|| String::from("the render panicked")),
+            ),
+        };
+        service.update(id, |job| job.state = state);
+    }
+}
+
+// paint a job's config like the shredder's main loop, publishing progress at every update
+// returns false when the job was cancelled
+fn render_job(
+    service: &RenderService,
+    id: u64,
+    config: &str,
+    cancel_requested: &AtomicBool,
+) -> bool {
+    // the engine only writes into directories that already exist
+    fs::create_dir_all(format!("{}/{}", service.output_directory, id))
+        .unwrap_or_else(|error| panic!("[ERROR] unable to create the job directory: {}", error));
+    let mut painting: Painting = initialize_canvas(config, None);
+    let total_pixel_count: u64 =
+        painting.canvas_constraints.x_size as u64 * painting.canvas_constraints.y_size as u64;
+    let painting_path: String = String::from(painting.output_settings.painting_path());
+    let preview: Arc<String> = Arc::new(preview_json(&painting.canvas));
+    service.update(id, |job| {
+        job.total_pixel_count = total_pixel_count;
+        job.painting_path = Some(painting_path);
+        job.preview = Some(preview);
+    });
+    write_output_files(&mut painting);
+
+    let mut current_time: Instant = Instant::now();
+    let run_start_time: Instant = Instant::now();
+    while place_next_pixel(&mut painting) {
+        if cancel_requested.load(Ordering::Relaxed) {
+            return false;
+        }
+        if current_time.elapsed() > painting.output_writer.interval() {
+            current_time = Instant::now();
+            let sample: StatsSample = report_progress(&mut painting, &run_start_time);
+            write_output_files(&mut painting);
+            let preview: Arc<String> = Arc::new(preview_json(&painting.canvas));
+            service.update(id, |job| {
+                job.progress = Some(sample);
+                job.preview = Some(preview);
+            });
+        }
+    }
+
+    let final_sample: StatsSample = finish_painting(&mut painting, &run_start_time);
+    let preview: Arc<String> = Arc::new(preview_json(&painting.canvas));
+    service.update(id, |job| {
+        job.progress = Some(final_sample);
+        job.preview = Some(preview);
+    });
+    return true;
+}
+
+// look up a job by the id segment of the url
+fn with_job<T>(
+    service: &RenderService,
+    id: &str,
+    read: impl FnOnce(u64, &Job) -> T,
+) -> Result<T, HttpResponse> {
+    let unknown = || error_response(404, "unknown job");
+    let id: u64 = id.parse().map_err(|_| unknown())?;
+    let jobs = service.lock();
+    let job: &Job = jobs.entries.get(&id).ok_or_else(unknown)?;
+    return Ok(read(id, job));
+}
+
+// the engine reads every scalar as a string, so numbers and booleans are kept as written
+// null values are left out, like a missing yaml key
+fn to_strict_yaml(value: &Value) -> StrictYaml {
+    match value {
+        Value::Object(entries) => StrictYaml::Hash(
+            entries
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (StrictYaml::String(key.clone()), to_strict_yaml(value)))
+                .collect(),
+        ),
+        Value::Array(items) => StrictYaml::Array(items.iter().map(to_strict_yaml).collect()),
+        Value::String(text) => StrictYaml::String(text.clone()),
+        Value::Null => StrictYaml::BadValue,
+        other => StrictYaml::String(other.to_string()),
+    }
+}
+
+// the downscaled canvas as the data of a preview event,
+// its cells are base64 encoded RGBA with the cell state as alpha, row by row
+fn preview_json(canvas: &TiledCanvas<Cell>) -> String {
+    let preview: TiledCanvas<Cell> = canvas.downscaled(PREVIEW_SIZE);
+    let mut cells: Vec<Cell> =
+        Vec::with_capacity(preview.width() as usize * preview.height() as usize);
+    let mut row: Vec<Cell> = Vec::new();
+    for y in 0..preview.height() {
+        preview.read_row(y, &mut row);
+        cells.extend_from_slice(&row);
+    }
+    return format!(
+        "{{\"width\": {}, \"height\": {}, \"cells\": \"{}\"}}",
+        preview.width(),
+        preview.height(),
+        base64(bytemuck::cast_slice(&cells))
+    );
+}
+
+// standard base64 with padding
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded: String = String::with_capacity(bytes.len().div_ceil(3usize) * 4usize);
+    for chunk in bytes.chunks(3usize) {
+        let group: u32 = (chunk[0] as u32) << 16
+            | (*chunk.get(1usize).unwrap_or(&0u8) as u32) << 8
+            | *chunk.get(2usize).unwrap_or(&0u8) as u32;
+        // a chunk of n bytes fills n + 1 characters, the rest is padding
+        for index in 0..4usize {
+            if index <= chunk.len() {
+                encoded.push(
+                    BASE64_ALPHABET[(group >> (18usize - 6usize * index)) as usize & 63usize]
+                        as char,
+                );
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    return encoded;
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("[ERROR] invalid http header")
+}
+
+fn json_response(status_code: u16, body: String) -> HttpResponse {
+    Response::from_string(body)
+        .with_status_code(status_code)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn error_response(status_code: u16, message: &str) -> HttpResponse {
+    json_response(
+        status_code,
+        format!("{{\"error\": {}}}", Value::String(String::from(message))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strict_yaml_rust::StrictYamlLoader;
+
+    // a service without workers, so queued jobs stay queued
+    fn service() -> RenderService {
+        return RenderService::new(String::from("./output/jobs"));
+    }
+
+    fn submit(service: &RenderService, config: Value) -> u16 {
+        return queue_job(&serde_json::json!({ "config": config }), service)
+            .status_code()
+            .0;
+    }
+
+    fn canvas(width: u64, height: u64) -> Value {
+        return serde_json::json!({
+            "canvas": { "size": { "x": width.to_string(), "y": height.to_string() } }
+        });
+    }
+
+    // a job in the given state, as the worker leaves it
+    fn job(state: JobState) -> Job {
+        return Job {
+            config: String::new(),
+            state,
+            cancel_requested: Arc::new(AtomicBool::new(false)),
+            total_pixel_count: 0u64,
+            painting_path: None,
+            progress: None,
+            preview: None,
+            update_count: 0u64,
+        };
+    }
+
+    #[test]
+    fn queues_jobs_until_the_queue_is_full() {
+        let service: RenderService = service();
+        for _ in 0..QUEUE_CAPACITY {
+            assert_eq!(submit(&service, canvas(8u64, 8u64)), 202u16);
+        }
+        assert_eq!(submit(&service, canvas(8u64, 8u64)), 503u16);
+
+        let jobs = service.lock();
+        assert_eq!(jobs.queue.len(), QUEUE_CAPACITY);
+        // the service picks where every job writes
+        let config: StrictYaml = StrictYamlLoader::load_from_str(&jobs.entries[&1u64].config)
+            .unwrap()
+            .remove(0);
+        assert_eq!(
+            config["config"]["output"]["directory"].as_str(),
+            Some("./output/jobs/1")
+        );
+    }
+
+    #[test]
+    fn rejects_configs_it_does_not_render() {
+        let service: RenderService = service();
+        assert_eq!(submit(&service, serde_json::json!({})), 400u16);
+        assert_eq!(submit(&service, canvas(0u64, 8u64)), 400u16);
+        assert_eq!(submit(&service, canvas(4097u64, 4096u64)), 400u16);
+        let mut with_file: Value = canvas(8u64, 8u64);
+        with_file["script"] = serde_json::json!({ "path": "/etc/passwd" });
+        assert_eq!(submit(&service, with_file), 400u16);
+        assert!(service.lock().entries.is_empty());
+    }
+
+    #[test]
+    fn cancels_queued_and_running_jobs() {
+        let service: RenderService = service();
+        submit(&service, canvas(8u64, 8u64));
+        submit(&service, canvas(8u64, 8u64));
+        service.lock().entries.get_mut(&2u64).unwrap().state = JobState::Running;
+
+        assert_eq!(cancel_job(&service, "1").status_code().0, 200u16);
+        assert_eq!(cancel_job(&service, "2").status_code().0, 202u16);
+        assert_eq!(cancel_job(&service, "1").status_code().0, 409u16);
+        assert_eq!(cancel_job(&service, "3").status_code().0, 404u16);
+        assert_eq!(cancel_job(&service, "first").status_code().0, 404u16);
+
+        let jobs = service.lock();
+        assert!(matches!(jobs.entries[&1u64].state, JobState::Cancelled));
+        assert!(!jobs.queue.contains(&1u64));
+        // a running job stops at its next placement, the worker then marks it cancelled
+        assert!(jobs.entries[&2u64].cancel_requested.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn evicts_the_oldest_finished_jobs() {
+        let mut jobs: Jobs = Jobs {
+            entries: BTreeMap::new(),
+            queue: VecDeque::new(),
+            next_id: 1u64,
+        };
+        for id in 1u64..=(FINISHED_JOB_LIMIT as u64 + 3u64) {
+            let state: JobState = match id {
+                1u64 => JobState::Queued,
+                2u64 => JobState::Running,
+                _ => JobState::Done,
+            };
+            jobs.entries.insert(id, job(state));
+        }
+        evict_finished_jobs(&mut jobs);
+
+        // jobs that have not finished are never evicted
+        assert!(jobs.entries.contains_key(&1u64) && jobs.entries.contains_key(&2u64));
+        assert!(!jobs.entries.contains_key(&3u64));
+        assert!(jobs.entries.contains_key(&4u64));
+        assert_eq!(jobs.entries.len(), FINISHED_JOB_LIMIT + 2usize);
+    }
+}
@@ -0,0 +1,286 @@
+use std::io::Write;
+use strict_yaml_rust::{StrictYaml, StrictYamlLoader};
+
+// png text chunk keywords used to embed the settings of a run
+const CONFIG_KEYWORD: &str = "ShredderConfig";
+const SEED_KEYWORD: &str = "ShredderSeed";
+const ORIGIN_KEYWORD: &str = "ShredderOrigin";
+const SOFTWARE_KEYWORD: &str = "Software";
+// config keys that name input files, which are read again rather than embedded
+const INPUT_FILE_KEYS: [&str; 2] = ["path", "file"];
+
+// which program painted an image, their configs have different layouts
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub(crate) enum RunOrigin {
+    // the shredder, the config is its config.yaml
+    Shredder,
+    // the web canvas, the config holds the canvas's own settings
+    WebCanvas,
+}
+impl RunOrigin {
+    fn name(self) -> &'static str {
+        match self {
+            RunOrigin::Shredder => "shredder",
+            RunOrigin::WebCanvas => "web-canvas",
+        }
+    }
+}
+
+// everything needed to recreate a painting
+pub(crate) struct RunMetadata {
+    // the yaml config the run was started with
+    pub(crate) config: String,
+    // the seed actually used, which may not be part of the config
+    pub(crate) seed: u64,
+    pub(crate) origin: RunOrigin,
+    // the engine version that made the painting
+    pub(crate) version: String,
+}
+impl RunMetadata {
+    // metadata for a run made by this version of the engine
+    pub(crate) fn new(config: String, seed: u64, origin: RunOrigin) -> RunMetadata {
+        RunMetadata {
+            config,
+            seed,
+            origin,
+            version: String::from(env!("CARGO_PKG_VERSION")),
+        }
+    }
+
+    // true when the painting was made by a different engine version
+    pub(crate) fn is_from_other_version(&self) -> bool {
+        self.version != env!("CARGO_PKG_VERSION")
+    }
+
+    // encode an 8 or 16 bit image as png, with the metadata in its text chunks
+    // 16 bit samples are expected in big endian byte order
+    // the shredder streams its pngs through png_encoder, only the web canvas encodes whole images
+    #[allow(dead_code)]
+    pub(crate) fn write_png<W: Write>(
+        &self,
+        writer: W,
+        width: u32,
+        height: u32,
+        color_type: png::ColorType,
+        bit_depth: png::BitDepth,
+        data: &[u8],
+    ) -> Result<(), String> {
+        let mut png_writer = self
+            .png_encoder(writer, width, height, color_type, bit_depth)?
+            .write_header()
+            .map_err(|error| error.to_string())?;
+        png_writer
+            .write_image_data(data)
+            .map_err(|error| error.to_string())?;
+        return Ok(());
+    }
+
+    // a png encoder with the metadata text chunks added, for images written row by row
+    pub(crate) fn png_encoder<W: Write>(
+        &self,
+        writer: W,
+        width: u32,
+        height: u32,
+        color_type: png::ColorType,
+        bit_depth: png::BitDepth,
+    ) -> Result<png::Encoder<'static, W>, String> {
+        let mut encoder: png::Encoder<W> = png::Encoder::new(writer, width, height);
+        encoder.set_color(color_type);
+        encoder.set_depth(bit_depth);
+
+        // the config may hold any text, so it goes into an international (utf-8) chunk
+        encoder
+            .add_itxt_chunk(String::from(CONFIG_KEYWORD), self.config.clone())
+            .map_err(|error| error.to_string())?;
+        encoder
+            .add_text_chunk(String::from(SEED_KEYWORD), self.seed.to_string())
+            .map_err(|error| error.to_string())?;
+        encoder
+            .add_text_chunk(
+                String::from(ORIGIN_KEYWORD),
+                String::from(self.origin.name()),
+            )
+            .map_err(|error| error.to_string())?;
+        encoder
+            .add_text_chunk(
+                String::from(SOFTWARE_KEYWORD),
+                format!("color-shredder {}", self.version),
+            )
+            .map_err(|error| error.to_string())?;
+        return Ok(encoder);
+    }
+
+    // read the metadata back out of a png written by write_png
+    pub(crate) fn read_png(bytes: &[u8]) -> Result<RunMetadata, String> {
+        let reader = png::Decoder::new(bytes)
+            .read_info()
+            .map_err(|error| error.to_string())?;
+        let info: &png::Info = reader.info();
+
+        let config: String = info
+            .utf8_text
+            .iter()
+            .find(|chunk| chunk.keyword == CONFIG_KEYWORD)
+            .ok_or_else(|| String::from("image has no color shredder settings"))?
+            .get_text()
+            .map_err(|error| error.to_string())?;
+        let latin1_text = |keyword: &str| -> Option<&str> {
+            info.uncompressed_latin1_text
+                .iter()
+                .find(|chunk| chunk.keyword == keyword)
+                .map(|chunk| chunk.text.as_str())
+        };
+        let seed: u64 = latin1_text(SEED_KEYWORD)
+            .ok_or_else(|| String::from("image has no color shredder seed"))?
+            .parse::<u64>()
+            .map_err(|_| String::from("image has an invalid color shredder seed"))?;
+        // paintings from before the origin was recorded all came from the shredder
+        let origin: RunOrigin = match latin1_text(ORIGIN_KEYWORD) {
+            None => RunOrigin::Shredder,
+            Some(name) => [RunOrigin::Shredder, RunOrigin::WebCanvas]
+                .into_iter()
+                .find(|origin| origin.name() == name)
+                .ok_or_else(|| format!("image was painted by an unknown program: {}", name))?,
+        };
+        let version: String = latin1_text(SOFTWARE_KEYWORD)
+            .and_then(|software| software.strip_prefix("color-shredder "))
+            .unwrap_or("unknown")
+            .to_string();
+
+        return Ok(RunMetadata {
+            config,
+            seed,
+            origin,
+            version,
+        });
+    }
+
+    // the files the config reads palettes, images and scripts from
+    // only their paths are embedded, so a run repeats only while the files are unchanged
+    // the web canvas embeds its palette and script and has no input files
+    #[allow(dead_code)]
+    pub(crate) fn input_files(&self) -> Vec<String> {
+        let mut input_files: Vec<String> = Vec::new();
+        for document in StrictYamlLoader::load_from_str(&self.config).unwrap_or_default() {
+            collect_input_files(&document, &mut input_files);
+        }
+        return input_files;
+    }
+}
+
+fn collect_input_files(value: &StrictYaml, input_files: &mut Vec<String>) {
+    match value {
+        StrictYaml::Hash(hash) => {
+            for (key, value) in hash {
+                match (key.as_str(), value.as_str()) {
+                    (Some(key), Some(path)) if INPUT_FILE_KEYS.contains(&key) => {
+                        input_files.push(String::from(path))
+                    }
+                    _ => collect_input_files(value, input_files),
+                }
+            }
+        }
+        StrictYaml::Array(values) => {
+            for value in values {
+                collect_input_files(value, input_files);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a one pixel png carrying the metadata
+    fn encode(metadata: &RunMetadata) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        metadata
+            .write_png(
+                &mut bytes,
+                1u32,
+                1u32,
+                png::ColorType::Rgb,
+                png::BitDepth::Eight,
+                &[1u8, 2u8, 3u8],
+            )
+            .unwrap();
+        return bytes;
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        for origin in [RunOrigin::Shredder, RunOrigin::WebCanvas] {
+            let metadata: RunMetadata =
+                RunMetadata::new(String::from("config:\n  seed: \"ü\"\n"), u64::MAX, origin);
+            let read: RunMetadata = RunMetadata::read_png(&encode(&metadata)).unwrap();
+            assert_eq!(read.config, metadata.config);
+            assert_eq!(read.seed, u64::MAX);
+            assert_eq!(read.origin, origin);
+            assert!(!read.is_from_other_version());
+        }
+    }
+
+    #[test]
+    fn paintings_without_an_origin_come_from_the_shredder() {
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut encoder: png::Encoder<&mut Vec<u8>> = png::Encoder::new(&mut bytes, 1u32, 1u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder
+            .add_itxt_chunk(String::from(CONFIG_KEYWORD), String::from("config:\n"))
+            .unwrap();
+        encoder
+            .add_text_chunk(String::from(SEED_KEYWORD), String::from("7"))
+            .unwrap();
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&[0u8, 0u8, 0u8])
+            .unwrap();
+
+        let read: RunMetadata = RunMetadata::read_png(&bytes).unwrap();
+        assert_eq!(read.origin, RunOrigin::Shredder);
+        assert_eq!(read.seed, 7u64);
+        assert!(read.is_from_other_version());
+    }
+
+    #[test]
+    fn rejects_images_without_settings() {
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut encoder: png::Encoder<&mut Vec<u8>> = png::Encoder::new(&mut bytes, 1u32, 1u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder
+            .write_header()
+            .unwrap()
+            .write_image_data(&[0u8, 0u8, 0u8])
+            .unwrap();
+        assert!(RunMetadata::read_png(&bytes).is_err());
+    }
+
+    #[test]
+    fn lists_the_input_files_of_the_config() {
+        let metadata: RunMetadata = RunMetadata::new(
+            String::from(
+                "config:
+  colonies:
+    groups:
+      - palette:
+          file: \"./config/a.gpl\"
+          keyframes:
+            - at: \"50%\"
+              image:
+                path: \"./config/b.png\"
+  script:
+    path: \"./config/c.rhai\"
+",
+            ),
+            1u64,
+            RunOrigin::Shredder,
+        );
+        assert_eq!(
+            metadata.input_files(),
+            vec!["./config/a.gpl", "./config/b.png", "./config/c.rhai"]
+        );
+    }
+}
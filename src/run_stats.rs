@@ -0,0 +1,310 @@
+// statistics of a running painting, shared by the shredder and the web canvas
+// times are passed in as seconds so the same code works without std::time in the browser
+
+use serde_json::Value;
+
+// the time series keeps at most this many points, every other one is dropped when it is full
+// so a long run (or a browser tab left open) keeps its whole span at a coarser resolution
+const MAX_SAMPLE_COUNT: usize = 2048usize;
+
+// one point of the statistics time series
+#[derive(Copy, Clone)]
+pub(crate) struct StatsSample {
+    pub(crate) elapsed_seconds: f64,
+    pub(crate) pixels_placed_count: u64,
+    // placements since the previous sample, per second
+    pub(crate) placements_per_second: f64,
+    pub(crate) frontier_size: usize,
+    pub(crate) mean_chosen_distance: f64,
+    pub(crate) max_chosen_distance: f32,
+    pub(crate) scoring_seconds: f64,
+    pub(crate) bookkeeping_seconds: f64,
+    // None until the placement rate is known
+    pub(crate) eta_seconds: Option<f64>,
+    // mean RGB distance lost by approximate searches, None without audits
+    pub(crate) search_loss: Option<f64>,
+    // fraction of audited approximate searches that found the exact best position
+    pub(crate) search_exact_rate: Option<f64>,
+}
+impl StatsSample {
+    // a single json object, for the time series and the render service
+    pub(crate) fn to_json(self) -> String {
+        format!(
+            "{{\"elapsed_seconds\": {:.3}, \"pixels_placed\": {}, \"placements_per_second\": {:.1}, \"frontier_size\": {}, \"mean_chosen_distance\": {:.3}, \"max_chosen_distance\": {:.3}, \"scoring_seconds\": {:.3}, \"bookkeeping_seconds\": {:.3}, \"eta_seconds\": {}, \"search_loss\": {}, \"search_exact_rate\": {}}}",
+            self.elapsed_seconds,
+            self.pixels_placed_count,
+            self.placements_per_second,
+            self.frontier_size,
+            self.mean_chosen_distance,
+            self.max_chosen_distance,
+            self.scoring_seconds,
+            self.bookkeeping_seconds,
+            self.eta_seconds
+                .map_or(String::from("null"), |eta| format!("{:.1}", eta)),
+            self.search_loss
+                .map_or(String::from("null"), |loss| format!("{:.3}", loss)),
+            self.search_exact_rate
+                .map_or(String::from("null"), |rate| format!("{:.3}", rate))
+        )
+    }
+
+    // read back an object written by to_json, None when a required field is missing
+    // only the web canvas reads samples back, from the render service
+    #[allow(dead_code)]
+    pub(crate) fn from_json(value: &Value) -> Option<StatsSample> {
+        return Some(StatsSample {
+            elapsed_seconds: value["elapsed_seconds"].as_f64()?,
+            pixels_placed_count: value["pixels_placed"].as_u64()?,
+            placements_per_second: value["placements_per_second"].as_f64()?,
+            frontier_size: value["frontier_size"].as_u64()? as usize,
+            mean_chosen_distance: value["mean_chosen_distance"].as_f64()?,
+            max_chosen_distance: value["max_chosen_distance"].as_f64()? as f32,
+            scoring_seconds: value["scoring_seconds"].as_f64()?,
+            bookkeeping_seconds: value["bookkeeping_seconds"].as_f64()?,
+            eta_seconds: value["eta_seconds"].as_f64(),
+            search_loss: value["search_loss"].as_f64(),
+            search_exact_rate: value["search_exact_rate"].as_f64(),
+        });
+    }
+}
+
+pub(crate) struct RunStats {
+    pub(crate) current_pixels_placed_count: u64,
+    total_pixel_count: u64,
+    // chosen distances are RGB distances, the square roots of the chosen scores
+    chosen_distance_sum: f64,
+    chosen_distance_count: u64,
+    max_chosen_distance: f32,
+    // time spent searching for positions vs. everything else of a placement
+    scoring_seconds: f64,
+    bookkeeping_seconds: f64,
+    // approximate searches checked against the exact search
+    search_audit_count: u64,
+    search_exact_count: u64,
+    search_loss_sum: f64,
+    samples: Vec<StatsSample>,
+}
+impl RunStats {
+    pub(crate) fn new(total_pixel_count: u64) -> RunStats {
+        RunStats {
+            current_pixels_placed_count: 0u64,
+            total_pixel_count,
+            chosen_distance_sum: 0f64,
+            chosen_distance_count: 0u64,
+            max_chosen_distance: 0f32,
+            scoring_seconds: 0f64,
+            bookkeeping_seconds: 0f64,
+            search_audit_count: 0u64,
+            search_exact_count: 0u64,
+            search_loss_sum: 0f64,
+            samples: Vec::new(),
+        }
+    }
+
+    // count a placement, seeds and other unscored placements have no chosen score
+    pub(crate) fn record_placement(&mut self, chosen_score: Option<f32>) {
+        self.current_pixels_placed_count += 1;
+        if let Some(chosen_score) = chosen_score {
+            let chosen_distance: f32 = chosen_score.max(0f32).sqrt();
+            self.chosen_distance_sum += chosen_distance as f64;
+            self.chosen_distance_count += 1;
+            self.max_chosen_distance = self.max_chosen_distance.max(chosen_distance);
+        }
+    }
+
+    pub(crate) fn add_scoring_time(&mut self, seconds: f64) {
+        self.scoring_seconds += seconds;
+    }
+
+    pub(crate) fn add_bookkeeping_time(&mut self, seconds: f64) {
+        self.bookkeeping_seconds += seconds;
+    }
+
+    // compare the score an approximate search chose with the exact best score
+    pub(crate) fn record_search_audit(&mut self, approximate_score: f32, exact_score: f32) {
+        self.search_audit_count += 1;
+        if approximate_score <= exact_score {
+            self.search_exact_count += 1;
+        }
+        // in RGB distance, like the chosen distances
+        self.search_loss_sum +=
+            (approximate_score.max(0f32).sqrt() - exact_score.max(0f32).sqrt()).max(0f32) as f64;
+    }
+
+    // append a point to the time series and return it
+    pub(crate) fn sample(&mut self, elapsed_seconds: f64, frontier_size: usize) -> StatsSample {
+        // the rate is measured since the previous sample so it follows the run's slowdown
+        let (previous_seconds, previous_count): (f64, u64) =
+            self.samples.last().map_or((0f64, 0u64), |sample| {
+                (sample.elapsed_seconds, sample.pixels_placed_count)
+            });
+        let interval_seconds: f64 = elapsed_seconds - previous_seconds;
+        let placements_per_second: f64 = if interval_seconds > 0f64 {
+            (self.current_pixels_placed_count - previous_count) as f64 / interval_seconds
+        } else {
+            0f64
+        };
+
+        let remaining_count: u64 = self
+            .total_pixel_count
+            .saturating_sub(self.current_pixels_placed_count);
+        let eta_seconds: Option<f64> = if remaining_count == 0 {
+            Some(0f64)
+        } else if placements_per_second > 0f64 {
+            Some(remaining_count as f64 / placements_per_second)
+        } else {
+            None
+        };
+
+        let sample: StatsSample = StatsSample {
+            elapsed_seconds,
+            pixels_placed_count: self.current_pixels_placed_count,
+            placements_per_second,
+            frontier_size,
+            mean_chosen_distance: if self.chosen_distance_count > 0 {
+                self.chosen_distance_sum / self.chosen_distance_count as f64
+            } else {
+                0f64
+            },
+            max_chosen_distance: self.max_chosen_distance,
+            scoring_seconds: self.scoring_seconds,
+            bookkeeping_seconds: self.bookkeeping_seconds,
+            eta_seconds,
+            search_loss: (self.search_audit_count > 0)
+                .then(|| self.search_loss_sum / self.search_audit_count as f64),
+            search_exact_rate: (self.search_audit_count > 0)
+                .then(|| self.search_exact_count as f64 / self.search_audit_count as f64),
+        };
+        self.push(sample);
+        return sample;
+    }
+
+    // append a point, thinning out the older points once the series is full
+    fn push(&mut self, sample: StatsSample) {
+        if self.samples.len() >= MAX_SAMPLE_COUNT {
+            // counted from the end, so the latest point stays the base of the next rate
+            let sample_count: usize = self.samples.len();
+            let mut index: usize = 0usize;
+            self.samples.retain(|_| {
+                index += 1;
+                (sample_count - index).is_multiple_of(2usize)
+            });
+        }
+        self.samples.push(sample);
+    }
+
+    // a single line summary, meant to be redrawn in place on a terminal
+    pub(crate) fn progress_line(&self, sample: &StatsSample) -> String {
+        let percent: f64 = if self.total_pixel_count > 0 {
+            sample.pixels_placed_count as f64 * 100f64 / self.total_pixel_count as f64
+        } else {
+            100f64
+        };
+        let busy_seconds: f64 = sample.scoring_seconds + sample.bookkeeping_seconds;
+        let scoring_percent: f64 = if busy_seconds > 0f64 {
+            sample.scoring_seconds * 100f64 / busy_seconds
+        } else {
+            0f64
+        };
+        let search_quality: String = match (sample.search_loss, sample.search_exact_rate) {
+            (Some(search_loss), Some(search_exact_rate)) => format!(
+                " | search loss {:.2} exact {:.0}%",
+                search_loss,
+                search_exact_rate * 100f64
+            ),
+            _ => String::new(),
+        };
+        format!(
+            "{}/{} ({:.1}%) | {:.0} px/s | frontier {} | distance mean {:.1} max {:.1} | scoring {:.0}%{} | eta {}",
+            sample.pixels_placed_count,
+            self.total_pixel_count,
+            percent,
+            sample.placements_per_second,
+            sample.frontier_size,
+            sample.mean_chosen_distance,
+            sample.max_chosen_distance,
+            scoring_percent,
+            search_quality,
+            format_duration(sample.eta_seconds)
+        )
+    }
+
+    // the whole time series as a json document
+    pub(crate) fn to_json(&self) -> String {
+        let samples: Vec<String> = self
+            .samples
+            .iter()
+            .map(|sample| format!("    {}", sample.to_json()))
+            .collect();
+        format!(
+            "{{\n  \"total_pixels\": {},\n  \"samples\": [\n{}\n  ]\n}}\n",
+            self.total_pixel_count,
+            samples.join(",\n")
+        )
+    }
+
+    // the whole time series as csv with a header row, unknown etas are left empty
+    pub(crate) fn to_csv(&self) -> String {
+        let mut csv: String = String::from(
+            "elapsed_seconds,pixels_placed,placements_per_second,frontier_size,mean_chosen_distance,max_chosen_distance,scoring_seconds,bookkeeping_seconds,eta_seconds,search_loss,search_exact_rate\n",
+        );
+        for sample in &self.samples {
+            csv.push_str(&format!(
+                "{:.3},{},{:.1},{},{:.3},{:.3},{:.3},{:.3},{},{},{}\n",
+                sample.elapsed_seconds,
+                sample.pixels_placed_count,
+                sample.placements_per_second,
+                sample.frontier_size,
+                sample.mean_chosen_distance,
+                sample.max_chosen_distance,
+                sample.scoring_seconds,
+                sample.bookkeeping_seconds,
+                sample
+                    .eta_seconds
+                    .map_or(String::new(), |eta| format!("{:.1}", eta)),
+                sample
+                    .search_loss
+                    .map_or(String::new(), |loss| format!("{:.3}", loss)),
+                sample
+                    .search_exact_rate
+                    .map_or(String::new(), |rate| format!("{:.3}", rate))
+            ));
+        }
+        return csv;
+    }
+}
+
+// following a render service job, which only the web canvas does
+#[allow(dead_code)]
+impl RunStats {
+    // append a point taken by the job instead of sampling this run
+    // a status repeats its latest point until the job reports again, repeats are skipped
+    pub(crate) fn push_sample(&mut self, sample: StatsSample) {
+        let is_repeat: bool = self.samples.last().is_some_and(|last| {
+            last.elapsed_seconds == sample.elapsed_seconds
+                && last.pixels_placed_count == sample.pixels_placed_count
+        });
+        if !is_repeat {
+            self.current_pixels_placed_count = sample.pixels_placed_count;
+            self.push(sample);
+        }
+    }
+
+    pub(crate) fn total_pixel_count(&self) -> u64 {
+        self.total_pixel_count
+    }
+}
+
+// hours, minutes and seconds, or "--" while unknown
+pub(crate) fn format_duration(seconds: Option<f64>) -> String {
+    let Some(seconds) = seconds else {
+        return String::from("--");
+    };
+    let total_seconds: u64 = seconds.max(0f64).round() as u64;
+    format!(
+        "{}:{:02}:{:02}",
+        total_seconds / 3600,
+        (total_seconds / 60) % 60,
+        total_seconds % 60
+    )
+}
@@ -0,0 +1,241 @@
+use image::{Rgb, RgbImage};
+use std::sync::Arc;
+use strict_yaml_rust::StrictYaml;
+
+use crate::cell::Cell;
+use crate::diagnostic_maps::{DiagnosticMaps, RecordedMaps};
+use crate::flow_field::FlowField;
+use crate::script::PaintingScript;
+use crate::tiled_canvas::TiledCanvas;
+use crate::{
+    parse_config_value, parse_optional_config_value, tie_breaker, Constraints, Coordinate,
+};
+
+// terms that are not color distances are normalized to 0..1, then scaled so a weight of 1
+// costs as much as a difference of 255 in one channel of the squared RGB distance
+const TERM_SCALE: f32 = 255f32 * 255f32;
+
+// one ingredient of a candidate's score, lower scores win
+enum ScoreTerm {
+    // distance to the colored neighbors, blending their minimum and average
+    NeighborColor,
+    // distance to the color of a target image at the cell, the image is stretched over the canvas
+    TargetImage(RgbImage),
+    // distance from the seed the cell's region grew from, relative to the canvas diagonal
+    SeedDistance,
+    // cells that joined the boundry region recently cost more, favoring the oldest cells
+    FrontierAge,
+    // a fresh random value for every cell and placement
+    Noise,
+    // growth against the flow field costs more
+    Flow(FlowField),
+}
+
+// everything a term may look at besides the candidate cell itself
+pub(crate) struct ScoringContext<'a> {
+    pub(crate) target_color: Rgb<u8>,
+    // the palette's blend between the minimum and average neighbor distance
+    pub(crate) average_weight: f32,
+    pub(crate) placement_count: u64,
+    pub(crate) noise_salt: u64,
+    pub(crate) canvas_image: &'a TiledCanvas<Cell>,
+    pub(crate) canvas_constraints: &'a Constraints,
+    pub(crate) diagnostic_maps: &'a DiagnosticMaps,
+}
+
+// what evaluate_position found around a candidate cell
+pub(crate) struct Neighborhood {
+    pub(crate) min_color_distance: f32,
+    pub(crate) average_color_distance: f32,
+    // sum of the offsets from the cell to its colored neighbors
+    pub(crate) neighbor_offset: [f32; 2],
+}
+
+// a weighted sum of score terms
+pub(crate) struct ScoringPipeline {
+    terms: Vec<(ScoreTerm, f32)>,
+    // a script's adjust_score function gets the last word on every score
+    script: Option<Arc<PaintingScript>>,
+}
+impl ScoringPipeline {
+    // read the "scoring: terms" list, without it only the neighbor color distance counts
+    // a "flow" section adds the flow field as a term with the flow's weight
+    pub(crate) fn from_config(
+        config: &StrictYaml,
+        flow_config: &StrictYaml,
+        canvas_constraints: &Constraints,
+        seed: u64,
+        script: Option<&Arc<PaintingScript>>,
+    ) -> ScoringPipeline {
+        let mut terms: Vec<(ScoreTerm, f32)> = match config["terms"].as_vec() {
+            Some(term_configs) => term_configs.iter().map(parse_term).collect(),
+            None => vec![(ScoreTerm::NeighborColor, 1f32)],
+        };
+
+        if let Some(flow_field) = FlowField::from_config(flow_config, canvas_constraints, seed) {
+            terms.push((
+                ScoreTerm::Flow(flow_field),
+                parse_weight(&flow_config["weight"]),
+            ));
+        }
+
+        if terms.is_empty() {
+            panic!("[ERROR] scoring needs at least one term");
+        }
+        return ScoringPipeline {
+            terms,
+            script: script
+                .filter(|script| script.has_adjust_score())
+                .map(Arc::clone),
+        };
+    }
+
+    // the per cell records the terms read
+    pub(crate) fn recorded_maps(&self) -> RecordedMaps {
+        let uses = |wanted: fn(&ScoreTerm) -> bool| self.terms.iter().any(|(term, _)| wanted(term));
+        RecordedMaps {
+            seed_owners: uses(|term| matches!(term, ScoreTerm::SeedDistance)),
+            boundry_since: uses(|term| matches!(term, ScoreTerm::FrontierAge)),
+            ..RecordedMaps::default()
+        }
+    }
+
+    // the weighted sum of all terms for a candidate cell
+    pub(crate) fn score(
+        &self,
+        position: &Coordinate,
+        neighborhood: &Neighborhood,
+        context: &ScoringContext,
+    ) -> f32 {
+        let score: f32 = self
+            .terms
+            .iter()
+            .map(|(term, weight)| weight * term_value(term, position, neighborhood, context))
+            .sum();
+
+        let Some(script) = &self.script else {
+            return score;
+        };
+        return script
+            .adjust_score(
+                position.x,
+                position.y,
+                score,
+                &neighbor_colors(position, context),
+            )
+            .unwrap_or_else(|error| panic!("[ERROR] {}", error));
+    }
+}
+
+fn parse_term(config: &StrictYaml) -> (ScoreTerm, f32) {
+    let name: String = parse_config_value(&config["name"]);
+    let term: ScoreTerm = match name.as_str() {
+        "neighbor_color" => ScoreTerm::NeighborColor,
+        "target_image" => {
+            let path: String = parse_config_value(&config["path"]);
+            ScoreTerm::TargetImage(
+                image::open(&path)
+                    .expect("[ERROR] unable to read scoring target image")
+                    .to_rgb8(),
+            )
+        }
+        "seed_distance" => ScoreTerm::SeedDistance,
+        "frontier_age" => ScoreTerm::FrontierAge,
+        "noise" => ScoreTerm::Noise,
+        "flow" => panic!("[ERROR] the flow term is configured in the flow section"),
+        other => panic!("[ERROR] unknown scoring term: {}", other),
+    };
+    return (term, parse_weight(&config["weight"]));
+}
+
+// a term's weight, 1 when it is left out
+// a NaN weight would make every score NaN, and the searches keep the lowest score
+fn parse_weight(config: &StrictYaml) -> f32 {
+    let weight: f32 = parse_optional_config_value(config, 1f32);
+    if !weight.is_finite() || weight < 0f32 {
+        panic!("[ERROR] scoring weights must be finite numbers of at least 0");
+    }
+    return weight;
+}
+
+fn term_value(
+    term: &ScoreTerm,
+    position: &Coordinate,
+    neighborhood: &Neighborhood,
+    context: &ScoringContext,
+) -> f32 {
+    match term {
+        ScoreTerm::NeighborColor => {
+            neighborhood.min_color_distance
+                + (neighborhood.average_color_distance - neighborhood.min_color_distance)
+                    * context.average_weight
+        }
+        ScoreTerm::TargetImage(target_image) => {
+            let target_pixel: &Rgb<u8> = target_image.get_pixel(
+                (position.x as u64 * target_image.width() as u64
+                    / context.canvas_constraints.x_size as u64) as u32,
+                (position.y as u64 * target_image.height() as u64
+                    / context.canvas_constraints.y_size as u64) as u32,
+            );
+            squared_distance(&context.target_color, target_pixel)
+        }
+        ScoreTerm::SeedDistance => {
+            let Some(seed_position) = context.diagnostic_maps.seed_position(position) else {
+                return 0f32;
+            };
+            let diagonal: f32 = (context.canvas_constraints.x_size as f32)
+                .hypot(context.canvas_constraints.y_size as f32);
+            let distance: f32 = (position.x as f32 - seed_position.x as f32)
+                .hypot(position.y as f32 - seed_position.y as f32);
+            distance / diagonal * TERM_SCALE
+        }
+        ScoreTerm::FrontierAge => {
+            if context.placement_count == 0 {
+                return 0f32;
+            }
+            let waited: u64 = context
+                .placement_count
+                .saturating_sub(context.diagnostic_maps.boundry_since(position));
+            (1f32 - waited as f32 / context.placement_count as f32) * TERM_SCALE
+        }
+        ScoreTerm::Noise => {
+            let cell_index: usize = position.y as usize
+                * context.canvas_constraints.x_size as usize
+                + position.x as usize;
+            (tie_breaker(context.noise_salt, cell_index) >> 40) as f32 / (1u64 << 24) as f32
+                * TERM_SCALE
+        }
+        ScoreTerm::Flow(flow_field) => {
+            flow_field.misalignment(position.x, position.y, neighborhood.neighbor_offset)
+                * TERM_SCALE
+        }
+    }
+}
+
+// the colors of the colored neighbors of a position
+fn neighbor_colors(position: &Coordinate, context: &ScoringContext) -> Vec<[u8; 3]> {
+    let mut colors: Vec<[u8; 3]> = Vec::with_capacity(8);
+    for y in
+        position.y.saturating_sub(1)..=(position.y + 1).min(context.canvas_constraints.y_size - 1)
+    {
+        for x in position.x.saturating_sub(1)
+            ..=(position.x + 1).min(context.canvas_constraints.x_size - 1)
+        {
+            let is_neighbor: bool = context
+                .canvas_constraints
+                .neighborhood
+                .contains(x + 1 - position.x, y + 1 - position.y);
+            let cell: Cell = context.canvas_image.get(x, y);
+            if is_neighbor && cell.is_painted() {
+                colors.push(cell.rgb());
+            }
+        }
+    }
+    return colors;
+}
+
+fn squared_distance(a: &Rgb<u8>, b: &Rgb<u8>) -> f32 {
+    (0..3)
+        .map(|channel| (a[channel] as f32 - b[channel] as f32).powi(2))
+        .sum()
+}
@@ -0,0 +1,169 @@
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::events::TargetCast;
+use yew::{html, Component, Context, Html};
+
+// the shredder's scoring terms: config name, label, default weight and the slider's range
+// the color distances are squared RGB distances, the other terms cost as much as a single
+// channel difference of 255 at weight 1, so they take far larger weights to compete
+// target_image and flow need their own settings (an image path, a flow section) in the config
+const SCORE_TERMS: [(&str, &str, f32, WeightRange); 6] = [
+    ("neighbor_color", "Neighbor Color", 1f32, COLOR_WEIGHTS),
+    ("target_image", "Target Image", 0f32, COLOR_WEIGHTS),
+    ("seed_distance", "Seed Distance", 0f32, TERM_WEIGHTS),
+    ("frontier_age", "Frontier Age", 0f32, TERM_WEIGHTS),
+    ("noise", "Noise", 0f32, TERM_WEIGHTS),
+    ("flow", "Flow", 0f32, TERM_WEIGHTS),
+];
+
+// min, max and step of a weight slider
+type WeightRange = (f32, f32, f32);
+const COLOR_WEIGHTS: WeightRange = (0f32, 10f32, 0.1f32);
+const TERM_WEIGHTS: WeightRange = (0f32, 1000f32, 5f32);
+
+// the flow field types with the settings each needs, indented for the flow section
+const FLOW_TYPES: [(&str, &str); 4] = [
+    ("noise", "    scale: \"64\"\n"),
+    ("wind", "    direction: \"0\"\n"),
+    ("radial", "    outward: \"true\"\n"),
+    ("image", "    path: \"./config/flow.png\"\n"),
+];
+
+// yew messages
+pub(crate) enum Msg {
+    // term index, new weight
+    WeightInput(usize, f32),
+    // flow type index
+    FlowTypeInput(usize),
+}
+
+// yew sub-component with a weight slider per scoring term
+// shows the matching sections of config.yaml, nested under its config key
+pub(crate) struct ScoringPanel {
+    weights: [f32; SCORE_TERMS.len()],
+    flow_type: usize,
+}
+impl ScoringPanel {
+    // the "scoring" and "flow" config sections for the current weights, zero weights are left out
+    // without any term the engine would fall back to the neighbor color,
+    // so that term is kept at weight 0 when every other weight is 0
+    fn config_snippet(&self) -> String {
+        let mut terms: String = String::new();
+        let mut flow_weight: Option<f32> = None;
+        for ((name, _, _, _), weight) in SCORE_TERMS.iter().zip(self.weights.iter()) {
+            if *weight == 0f32 {
+                continue;
+            }
+            match *name {
+                "flow" => flow_weight = Some(*weight),
+                "target_image" => terms.push_str(&format!(
+                    "      - name: target_image\n        weight: \"{}\"\n        path: \"./config/target.png\"\n",
+                    weight
+                )),
+                _ => terms.push_str(&format!(
+                    "      - name: {}\n        weight: \"{}\"\n",
+                    name, weight
+                )),
+            }
+        }
+        if terms.is_empty() {
+            terms.push_str("      - name: neighbor_color\n        weight: \"0\"\n");
+        }
+
+        let mut snippet: String = format!("config:\n  scoring:\n    terms:\n{}", terms);
+        if let Some(flow_weight) = flow_weight {
+            let (flow_type, flow_settings) = FLOW_TYPES[self.flow_type];
+            snippet.push_str(&format!(
+                "  flow:\n    type: {}\n{}    weight: \"{}\"\n",
+                flow_type, flow_settings, flow_weight
+            ));
+        }
+        return snippet;
+    }
+}
+impl Component for ScoringPanel {
+    type Message = Msg;
+    type Properties = ();
+
+    // ScoringPanel init
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self {
+            weights: SCORE_TERMS.map(|(_, _, default_weight, _)| default_weight),
+            flow_type: 0usize,
+        }
+    }
+
+    // ScoringPanel update logic
+    fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::WeightInput(term_index, weight) => {
+                self.weights[term_index] = weight;
+                true
+            }
+            Msg::FlowTypeInput(flow_type) => {
+                self.flow_type = flow_type;
+                true
+            }
+        }
+    }
+
+    // ScoringPanel view logic
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let sliders: Html = SCORE_TERMS
+            .iter()
+            .enumerate()
+            .map(|(term_index, (_, label, _, (weight_min, weight_max, weight_step)))| {
+                let weight_input_callback: yew::Callback<web_sys::InputEvent> =
+                    ctx.link().batch_callback(move |event: web_sys::InputEvent| {
+                        event
+                            .target_unchecked_into::<HtmlInputElement>()
+                            .value()
+                            .parse::<f32>()
+                            .ok()
+                            .map(|weight| Msg::WeightInput(term_index, weight))
+                    });
+                html! {
+                    <div class="centered-div">
+                        <label class="status-text slider-label">{label}</label>
+                        <input
+                            type="range"
+                            min={weight_min.to_string()}
+                            max={weight_max.to_string()}
+                            step={weight_step.to_string()}
+                            value={self.weights[term_index].to_string()}
+                            oninput={weight_input_callback}
+                        />
+                        <span class="status-text">{format!("{:.2}", self.weights[term_index])}</span>
+                    </div>
+                }
+            })
+            .collect();
+
+        let flow_type_callback: yew::Callback<web_sys::Event> =
+            ctx.link().callback(|event: web_sys::Event| {
+                let select: HtmlSelectElement = event.target_unchecked_into::<HtmlSelectElement>();
+                Msg::FlowTypeInput(select.selected_index().max(0) as usize)
+            });
+        let flow_options: Html = FLOW_TYPES
+            .iter()
+            .enumerate()
+            .map(|(flow_index, (flow_type, _))| {
+                html! {
+                    <option value={*flow_type} selected={flow_index == self.flow_type}>{flow_type}</option>
+                }
+            })
+            .collect();
+
+        html! {
+            <div>
+                {sliders}
+                <div class="centered-div">
+                    <label class="status-text slider-label">{"Flow Type"}</label>
+                    <select onchange={flow_type_callback}>{flow_options}</select>
+                </div>
+                <div class="centered-div">
+                    <textarea class="config-snippet" readonly=true rows="12" value={self.config_snippet()} />
+                </div>
+            </div>
+        }
+    }
+}
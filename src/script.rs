@@ -0,0 +1,212 @@
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST, FLOAT, INT};
+
+// limits that keep a script from hanging or exhausting memory, per call
+const MAX_OPERATIONS: u64 = 1_000_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 4096;
+const MAX_ARRAY_SIZE: usize = 4096;
+const MAX_MAP_SIZE: usize = 256;
+
+// what a script's next_color function is told about the painting
+pub(crate) struct ScriptStats {
+    pub(crate) pixels_placed_count: u64,
+    pub(crate) total_pixel_count: u64,
+    pub(crate) colony_index: usize,
+    // uniform values in 0..1 drawn from the run's seeded generator,
+    // scripts have no random functions of their own so runs stay reproducible
+    pub(crate) random: [f64; 3],
+}
+
+// a user script that may define
+//   fn next_color(stats) -> [red, green, blue], channels 0..255
+//   fn adjust_score(x, y, score, neighbors) -> the new score, neighbors are [r, g, b] arrays
+// scripts run sandboxed: no modules, no file access and bounded work per call
+pub(crate) struct PaintingScript {
+    engine: Engine,
+    ast: AST,
+    has_next_color: bool,
+    has_adjust_score: bool,
+}
+impl PaintingScript {
+    pub(crate) fn compile(source: &str) -> Result<PaintingScript, String> {
+        let mut engine: Engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_ARRAY_SIZE);
+        engine.set_max_map_size(MAX_MAP_SIZE);
+        engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+        // printing would interleave with the progress output
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+
+        let ast: AST = engine
+            .compile(source)
+            .map_err(|error| format!("script error: {}", error))?;
+        let defines = |name: &str, parameter_count: usize| {
+            ast.iter_functions()
+                .any(|function| function.name == name && function.params.len() == parameter_count)
+        };
+        let has_next_color: bool = defines("next_color", 1);
+        let has_adjust_score: bool = defines("adjust_score", 4);
+        if !has_next_color && !has_adjust_score {
+            return Err(String::from(
+                "script defines neither next_color(stats) nor adjust_score(x, y, score, neighbors)",
+            ));
+        }
+        // the top-level statements run once here, the calls only run the functions
+        engine
+            .run_ast(&ast)
+            .map_err(|error| format!("script error: {}", error))?;
+
+        return Ok(PaintingScript {
+            engine,
+            ast,
+            has_next_color,
+            has_adjust_score,
+        });
+    }
+
+    pub(crate) fn has_next_color(&self) -> bool {
+        self.has_next_color
+    }
+
+    pub(crate) fn has_adjust_score(&self) -> bool {
+        self.has_adjust_score
+    }
+
+    // ask the script for the next color
+    pub(crate) fn next_color(&self, stats: &ScriptStats) -> Result<[u8; 3], String> {
+        let mut stats_map: Map = Map::new();
+        stats_map.insert("placed".into(), (stats.pixels_placed_count as INT).into());
+        stats_map.insert("total".into(), (stats.total_pixel_count as INT).into());
+        stats_map.insert(
+            "progress".into(),
+            (stats.pixels_placed_count as FLOAT / stats.total_pixel_count.max(1) as FLOAT).into(),
+        );
+        stats_map.insert("colony".into(), (stats.colony_index as INT).into());
+        stats_map.insert(
+            "random".into(),
+            Dynamic::from_array(
+                stats
+                    .random
+                    .iter()
+                    .map(|value| Dynamic::from_float(*value))
+                    .collect(),
+            ),
+        );
+
+        let color: Array = self
+            .engine
+            .call_fn_with_options::<Array>(
+                call_options(),
+                &mut Scope::new(),
+                &self.ast,
+                "next_color",
+                (stats_map,),
+            )
+            .map_err(|error| format!("next_color: {}", error))?;
+        if color.len() != 3 {
+            return Err(String::from("next_color must return [red, green, blue]"));
+        }
+
+        let mut channels: [u8; 3] = [0u8; 3];
+        for (channel, value) in channels.iter_mut().zip(color.iter()) {
+            let value: FLOAT = if let Ok(integer) = value.as_int() {
+                integer as FLOAT
+            } else {
+                value
+                    .as_float()
+                    .map_err(|_| String::from("next_color channels must be numbers"))?
+            };
+            *channel = value.round().clamp(0.0, 255.0) as u8;
+        }
+        return Ok(channels);
+    }
+
+    // let the script change the score of a candidate cell
+    pub(crate) fn adjust_score(
+        &self,
+        x: u32,
+        y: u32,
+        score: f32,
+        neighbor_colors: &[[u8; 3]],
+    ) -> Result<f32, String> {
+        let neighbors: Array = neighbor_colors
+            .iter()
+            .map(|color| {
+                Dynamic::from_array(
+                    color
+                        .iter()
+                        .map(|channel| Dynamic::from_int(*channel as INT))
+                        .collect(),
+                )
+            })
+            .collect();
+
+        let adjusted: Dynamic = self
+            .engine
+            .call_fn_with_options::<Dynamic>(
+                call_options(),
+                &mut Scope::new(),
+                &self.ast,
+                "adjust_score",
+                (x as INT, y as INT, score as FLOAT, neighbors),
+            )
+            .map_err(|error| format!("adjust_score: {}", error))?;
+        let score: f32 = if let Ok(integer) = adjusted.as_int() {
+            integer as f32
+        } else {
+            adjusted
+                .as_float()
+                .map(|value| value as f32)
+                .map_err(|_| String::from("adjust_score must return a number"))?
+        };
+        // a NaN would make the winner depend on the order the candidates are compared in
+        if !score.is_finite() {
+            return Err(format!("adjust_score returned {} for {}, {}", score, x, y));
+        }
+        return Ok(score);
+    }
+}
+
+// calls skip the script's top-level statements, they already ran when it was compiled
+fn call_options() -> CallFnOptions<'static> {
+    return CallFnOptions::new().eval_ast(false);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_top_level_statements_once_at_compile() {
+        assert!(
+            PaintingScript::compile("throw \"broken\"; fn adjust_score(x, y, s, n) { s }").is_err()
+        );
+
+        // the body and the function each stay within the operation limit of a call,
+        // running the body again on every call would exceed it
+        let script: PaintingScript = PaintingScript::compile(
+            "let t = 0; for i in 0..250000 { t += 1; }
+            fn adjust_score(x, y, s, n) { let t = 0; for i in 0..250000 { t += 1; } s + 1.0 }",
+        )
+        .unwrap();
+        for _ in 0..3 {
+            assert_eq!(script.adjust_score(0u32, 0u32, 1f32, &[]), Ok(2f32));
+        }
+    }
+
+    #[test]
+    fn rejects_scores_that_are_not_finite() {
+        for result in ["0.0 / 0.0", "1.0 / 0.0", "1e300"] {
+            let source: String = format!("fn adjust_score(x, y, s, n) {{ {} }}", result);
+            let script: PaintingScript = PaintingScript::compile(&source).unwrap();
+            assert!(
+                script.adjust_score(0u32, 0u32, 1f32, &[]).is_err(),
+                "accepted {}",
+                result
+            );
+        }
+    }
+}
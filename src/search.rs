@@ -0,0 +1,80 @@
+use rand::{seq::index, Rng};
+use strict_yaml_rust::StrictYaml;
+
+use crate::parse_optional_config_value;
+
+// how much of a boundry region is scored for every placement
+enum SearchMode {
+    // every cell, the true best position
+    Exact,
+    // a uniform random sample of the cells
+    Sample,
+    // one random cell from each of equally sized runs of the boundry region list
+    // removals swap the last cell into the gap, so the list is only loosely in growth order,
+    // this spreads the sample evenly over the list, not strictly over the front
+    Stratified,
+}
+
+// settings of the "search" config section
+pub(crate) struct SearchSettings {
+    mode: SearchMode,
+    // fraction of the boundry region scored by the approximate modes, the speed/quality knob
+    quality: f32,
+    // approximate modes never score fewer cells than this, smaller regions are searched exactly
+    min_sample_size: usize,
+    // every this many placements the exact search also runs, to estimate the quality loss
+    audit_interval: u64,
+}
+impl SearchSettings {
+    pub(crate) fn from_config(config: &StrictYaml) -> SearchSettings {
+        let mode: SearchMode = match config["mode"].as_str() {
+            None | Some("exact") => SearchMode::Exact,
+            Some("sample") => SearchMode::Sample,
+            Some("stratified") => SearchMode::Stratified,
+            Some(other) => panic!("[ERROR] unknown search mode: {}", other),
+        };
+        let quality: f32 = parse_optional_config_value(&config["quality"], 0.1f32);
+        if quality <= 0f32 || quality > 1f32 {
+            panic!("[ERROR] search quality must be greater than 0 and at most 1");
+        }
+        return SearchSettings {
+            mode,
+            quality,
+            min_sample_size: parse_optional_config_value(&config["min_sample_size"], 1024usize)
+                .max(1usize),
+            audit_interval: parse_optional_config_value(&config["audit_interval"], 256u64),
+        };
+    }
+
+    // the boundry region indices to score, None to score all of them
+    pub(crate) fn candidate_indices<R: Rng + ?Sized>(
+        &self,
+        boundry_size: usize,
+        rng: &mut R,
+    ) -> Option<Vec<usize>> {
+        let sample_size: usize =
+            ((boundry_size as f32 * self.quality).ceil() as usize).max(self.min_sample_size);
+        if sample_size >= boundry_size {
+            return None;
+        }
+
+        match self.mode {
+            SearchMode::Exact => None,
+            SearchMode::Sample => Some(index::sample(rng, boundry_size, sample_size).into_vec()),
+            SearchMode::Stratified => Some(
+                (0..sample_size)
+                    .map(|stratum| {
+                        let start: usize = stratum * boundry_size / sample_size;
+                        let end: usize = (stratum + 1) * boundry_size / sample_size;
+                        rng.gen_range(start..end.max(start + 1))
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    // whether an approximate placement should be checked against the exact search
+    pub(crate) fn audit_due(&self, pixels_placed_count: u64) -> bool {
+        self.audit_interval > 0 && pixels_placed_count.is_multiple_of(self.audit_interval)
+    }
+}
@@ -1,192 +1,545 @@
-use image::{GrayImage, ImageFormat, Luma, Rgb, RgbImage};
-use palette::{convert::TryIntoColor, Hsv, Srgb};
-use rand::random;
-use rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
-use std::{fs, time::Instant};
-use strict_yaml_rust::StrictYamlLoader;
-
-struct Painting {
-    image: RgbImage,
-    boundry_region_image: GrayImage,
-    boundry_region_list: Vec<Coordinate>,
-    starting_locations: Vec<Coordinate>,
-    canvas_constraints: Constraints,
-    canvas_stats: Stats,
+// the modules are crate visible so benches can compile the engine as a module of their own
+pub(crate) mod cell;
+pub(crate) mod colony;
+pub(crate) mod color_distance;
+pub(crate) mod color_source;
+pub(crate) mod diagnostic_maps;
+// shared with the web canvas, which also previews the distributions
+#[allow(dead_code)]
+pub(crate) mod distribution;
+pub(crate) mod flow_field;
+pub(crate) mod image_palette;
+pub(crate) mod inverse;
+pub(crate) mod output;
+pub(crate) mod output_writer;
+// shared with the web canvas, which also reports the size of a loaded palette
+#[allow(dead_code)]
+pub(crate) mod palette_file;
+pub(crate) mod quality;
+pub(crate) mod run_metadata;
+pub(crate) mod run_stats;
+pub(crate) mod scoring;
+pub(crate) mod script;
+pub(crate) mod search;
+pub(crate) mod sweep;
+// shared with the web canvas, which also redraws every tile after a reset
+#[allow(dead_code)]
+pub(crate) mod tiled_canvas;
+
+use cell::Cell;
+use colony::{parse_starting_locations, Colony, ColonyScheduler, SchedulingPolicy};
+use color_distance::{neighbor_distances, NeighborBatch, NeighborColors, NeighborDistances};
+use color_source::{ColorSource, PaletteState};
+use diagnostic_maps::DiagnosticMaps;
+use image::{Rgb, RgbaImage};
+use inverse::{draw_remaining_colors, CellSelection, GenerationMode};
+use output::OutputSettings;
+use output_writer::{OutputWriter, Snapshot};
+use quality::QualityReport;
+use rand::{random, rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+    ParallelSlice,
+};
+use run_metadata::{RunMetadata, RunOrigin};
+use run_stats::{RunStats, StatsSample};
+use scoring::{Neighborhood, ScoringContext, ScoringPipeline};
+use script::{PaintingScript, ScriptStats};
+use search::SearchSettings;
+use std::{
+    fmt::Debug,
+    fs,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use strict_yaml_rust::{StrictYaml, StrictYamlLoader};
+use sweep::run_sweep;
+use tiled_canvas::TiledCanvas;
+
+pub(crate) struct Painting {
+    // color and state of every cell, which also marks the cells of all boundry regions
+    pub(crate) canvas: TiledCanvas<Cell>,
+    pub(crate) colonies: Vec<Colony>,
+    pub(crate) colony_scheduler: ColonyScheduler,
+    // color first or position first
+    pub(crate) generation_mode: GenerationMode,
+    // exact or sampled search of the boundry regions
+    pub(crate) search_settings: SearchSettings,
+    // weighted terms every candidate position is scored with
+    pub(crate) scoring_pipeline: ScoringPipeline,
+    // a user script that may replace the palettes' colors
+    pub(crate) script: Option<Arc<PaintingScript>>,
+    pub(crate) canvas_constraints: Constraints,
+    pub(crate) canvas_stats: RunStats,
+    // placement order, chosen scores, seed ownership and boundry ages, as far as they are read
+    pub(crate) diagnostic_maps: DiagnosticMaps,
+    pub(crate) output_settings: Arc<OutputSettings>,
+    // writes the output images in the background
+    pub(crate) output_writer: OutputWriter,
+    // every random choice of a run is drawn from this generator, seeded from the config
+    pub(crate) rng: StdRng,
+}
+pub(crate) struct Constraints {
+    pub(crate) x_size: u32,
+    pub(crate) y_size: u32,
+    // which of the surrounding cells a cell grows into and is compared with
+    pub(crate) neighborhood: NeighborhoodShape,
 }
-struct Constraints {
-    x_size: u32,
-    y_size: u32,
+// the cells around a cell that count as its neighbors
+#[derive(Copy, Clone, PartialEq)]
+pub(crate) enum NeighborhoodShape {
+    // all eight surrounding cells
+    Moore,
+    // only the four cells sharing an edge
+    VonNeumann,
 }
-struct Stats {
-    current_pixels_placed_count: u64,
+impl NeighborhoodShape {
+    fn from_config(config: &StrictYaml) -> NeighborhoodShape {
+        match config.as_str() {
+            None | Some("moore") => NeighborhoodShape::Moore,
+            Some("von_neumann") => NeighborhoodShape::VonNeumann,
+            Some(other) => panic!("[ERROR] unknown neighborhood: {}", other),
+        }
+    }
+
+    // whether the cell at i, j of the 3x3 grid around a cell is one of its neighbors
+    pub(crate) fn contains(self, i: u32, j: u32) -> bool {
+        // the cell itself is never its own neighbor
+        if i == 1 && j == 1 {
+            return false;
+        }
+        return match self {
+            NeighborhoodShape::Moore => true,
+            NeighborhoodShape::VonNeumann => i == 1 || j == 1,
+        };
+    }
 }
-struct Pixel {
-    position: Coordinate,
-    color: Rgb<u8>,
+pub(crate) struct Pixel {
+    pub(crate) position: Coordinate,
+    pub(crate) color: Rgb<u8>,
 }
 #[derive(Copy, Clone)]
-struct Coordinate {
-    x: u32,
-    y: u32,
+pub(crate) struct Coordinate {
+    pub(crate) x: u32,
+    pub(crate) y: u32,
 }
 
 fn main() {
+    // "from-image <painting.png>" re-runs the settings embedded in an earlier painting
+    // "analyze <painting>" prints the quality metrics of a finished painting
+    // "sweep <sweep.yaml>" renders every variation of a base config
+    // otherwise the config file is read
+    let arguments: Vec<String> = std::env::args().collect();
+    let (config_string, seed_override): (String, Option<u64>) =
+        match arguments.get(1).map(String::as_str) {
+            Some("from-image") => {
+                let image_path: &String = arguments
+                    .get(2)
+                    .expect("[ERROR] from-image requires the path of a png painting");
+                let image_bytes: Vec<u8> =
+                    fs::read(image_path).expect("[ERROR] unable to read painting");
+                let metadata: RunMetadata = RunMetadata::read_png(&image_bytes)
+                    .unwrap_or_else(|error| panic!("[ERROR] {}: {}", image_path, error));
+                if metadata.origin != RunOrigin::Shredder {
+                    panic!(
+                        "[ERROR] {} was painted by the web canvas, load its settings there instead",
+                        image_path
+                    );
+                }
+                if metadata.is_from_other_version() {
+                    eprintln!(
+                        "[WARNING] {} was painted by engine version {}, results may differ",
+                        image_path, metadata.version
+                    );
+                }
+                for input_file in metadata.input_files() {
+                    eprintln!(
+                        "[WARNING] {} is not embedded in the painting and is read again",
+                        input_file
+                    );
+                }
+                (metadata.config, Some(metadata.seed))
+            }
+            Some("sweep") => {
+                let sweep_path: &String = arguments
+                    .get(2)
+                    .expect("[ERROR] sweep requires the path of a sweep file");
+                run_sweep(sweep_path);
+                return;
+            }
+            Some("analyze") => {
+                let image_path: &String = arguments
+                    .get(2)
+                    .expect("[ERROR] analyze requires the path of a painting");
+                let image: RgbaImage = image::open(image_path)
+                    .unwrap_or_else(|error| panic!("[ERROR] {}: {}", image_path, error))
+                    .to_rgba8();
+                let report: QualityReport = QualityReport::analyze(
+                    image.width(),
+                    image.height(),
+                    bytemuck::cast_slice(image.as_raw()),
+                );
+                for (label, value) in report.lines() {
+                    println!("{}: {}", label, value);
+                }
+                return;
+            }
+            Some(other) => panic!("[ERROR] unknown command: {}", other),
+            None => (
+                fs::read_to_string("./config/config.yaml")
+                    .expect("[ERROR] unable to read config.yaml"),
+                None,
+            ),
+        };
+
     // set output constraints, initialize canvas, get starting positions
-    let mut working_canvas: Painting = initialize_canvas();
+    let mut working_canvas: Painting = initialize_canvas(&config_string, seed_override);
 
     // initial update of the output files
-    write_output_files(&working_canvas);
+    write_output_files(&mut working_canvas);
 
     // create a timer to update at regular intervals
     let mut current_time = Instant::now();
+    let run_start_time: Instant = Instant::now();
 
-    // run the simulation loop as long as there are available positions in the boundry region
-    while working_canvas.boundry_region_list.len() > 0 {
-        let temp_color: Srgb = Hsv::new(
-            0.55f32 * 360f32,
-            random::<f32>().clamp(0.5f32, 1.0f32),
-            random::<f32>().clamp(0.0f32, 1.0f32),
-        )
-        .try_into_color()
-        .unwrap();
-        let temp_color_alt: Srgb = Hsv::new(
-            0.59f32 * 360f32,
-            random::<f32>().clamp(0.5f32, 1.0f32),
-            random::<f32>().clamp(0.0f32, 1.0f32),
-        )
-        .try_into_color()
-        .unwrap();
-
-        // choose a random color
-        let mut target_color: Rgb<u8> = Rgb([
-            (temp_color.red * 255f32).floor() as u8,
-            (temp_color.green * 255f32).floor() as u8,
-            (temp_color.blue * 255f32).floor() as u8,
-        ]);
-        if random::<bool>() {
-            target_color = Rgb([
-                (temp_color_alt.red * 255f32).floor() as u8,
-                (temp_color_alt.green * 255f32).floor() as u8,
-                (temp_color_alt.blue * 255f32).floor() as u8,
-            ]);
-        }
-        // determine best location
-        let target_pixel = get_best_position_for_color(target_color, &mut working_canvas);
-
-        // update the canvas
-        place_pixel(&target_pixel, &mut working_canvas);
-
-        // update output files after given interval
-        if current_time.elapsed().as_secs_f32() > (1f32 / 2f32) {
+    // run the simulation loop as long as any colony has available positions in its boundry region
+    while place_next_pixel(&mut working_canvas) {
+        // update output files after given interval, which grows with the time writing takes
+        if current_time.elapsed() > working_canvas.output_writer.interval() {
             current_time = Instant::now();
-            write_output_files(&working_canvas);
+            report_progress(&mut working_canvas, &run_start_time);
+            write_output_files(&mut working_canvas);
         }
     }
 
     // final update of the output files
-    write_output_files(&working_canvas);
+    finish_painting(&mut working_canvas, &run_start_time);
+}
+
+// write the final output files of a finished painting, returns the final statistics
+pub(crate) fn finish_painting(
+    working_canvas: &mut Painting,
+    run_start_time: &Instant,
+) -> StatsSample {
+    let final_sample: StatsSample = report_progress(working_canvas, run_start_time);
+    working_canvas.output_settings.finish_progress();
+    let final_snapshot: Snapshot = take_snapshot(&mut working_canvas.canvas);
+    working_canvas.output_writer.finish(final_snapshot);
+
+    // the auxiliary maps are only written once, for the finished painting
+    working_canvas.output_settings.write_maps(
+        &working_canvas.diagnostic_maps,
+        &working_canvas.canvas,
+        working_canvas.canvas_stats.current_pixels_placed_count,
+    );
+    return final_sample;
 }
 
-fn initialize_canvas() -> Painting {
-    // read config file into a string
-    let config_string =
-        fs::read_to_string("./config/config.yaml").expect("[ERROR] unable to read config.yaml");
+// grow the next scheduled colony by one pixel
+// returns false once every boundry region is empty and the painting is finished
+pub(crate) fn place_next_pixel(working_canvas: &mut Painting) -> bool {
+    let Some(colony_index) = working_canvas
+        .colony_scheduler
+        .next_colony(&working_canvas.colonies, &mut working_canvas.rng)
+    else {
+        return false;
+    };
+    let placement_start_time: Instant = Instant::now();
+
+    // look up the colony's palette at the current point of the painting
+    let palette_state: PaletteState = working_canvas.colonies[colony_index]
+        .color_source
+        .palette_at(working_canvas.canvas_stats.current_pixels_placed_count);
+
+    // determine the best location for a random color of the colony's own palette,
+    // or the best remaining color for a chosen location
+    let scoring_start_time: Instant;
+    let (target_pixel, chosen_score) = match working_canvas.generation_mode {
+        GenerationMode::ColorFirst => {
+            let target_color: Rgb<u8> = next_color(colony_index, &palette_state, working_canvas);
+            scoring_start_time = Instant::now();
+            get_best_position_for_color(
+                target_color,
+                palette_state.average_weight,
+                colony_index,
+                working_canvas,
+            )
+        }
+        GenerationMode::PositionFirst(cell_selection) => {
+            scoring_start_time = Instant::now();
+            get_best_color_for_position(
+                cell_selection,
+                &palette_state,
+                colony_index,
+                working_canvas,
+            )
+        }
+    };
+    let scoring_seconds: f64 = scoring_start_time.elapsed().as_secs_f64();
+
+    // update the canvas
+    place_pixel(
+        &target_pixel,
+        colony_index,
+        Some(chosen_score),
+        working_canvas,
+    );
+
+    // everything but the search counts as bookkeeping
+    working_canvas
+        .canvas_stats
+        .add_scoring_time(scoring_seconds);
+    working_canvas
+        .canvas_stats
+        .add_bookkeeping_time(placement_start_time.elapsed().as_secs_f64() - scoring_seconds);
+    return true;
+}
 
+pub(crate) fn initialize_canvas(config_string: &str, seed_override: Option<u64>) -> Painting {
     // parse config string using yaml structure
-    let config = &StrictYamlLoader::load_from_str(&config_string)
+    let config = &StrictYamlLoader::load_from_str(config_string)
         .expect("[ERROR] unable to parse config.yaml")[0]["config"];
 
     // hold the output image dimensions
     let working_constraints: Constraints = Constraints {
-        x_size: config["canvas"]["size"]["x"]
-            .as_str()
-            .expect("[ERROR] failed to parse config value as string")
-            .parse::<u32>()
-            .expect("[ERROR] failed to convert config str to int"),
-        y_size: config["canvas"]["size"]["y"]
-            .as_str()
-            .expect("[ERROR] failed to parse config value as string")
-            .parse::<u32>()
-            .expect("[ERROR] failed to convert config str to int"),
+        x_size: parse_config_value(&config["canvas"]["size"]["x"]),
+        y_size: parse_config_value(&config["canvas"]["size"]["y"]),
+        neighborhood: NeighborhoodShape::from_config(&config["canvas"]["neighborhood"]),
     };
 
+    // palette keyframes given as percentages are resolved against the canvas size
+    let total_pixel_count: u64 =
+        working_constraints.x_size as u64 * working_constraints.y_size as u64;
+
+    // the same seed and config reproduce the same painting
+    // without a configured seed a random one is chosen
+    let seed: u64 = seed_override
+        .unwrap_or_else(|| parse_optional_config_value(&config["seed"], random::<u64>()));
+
     // hold running stats
-    let working_stats: Stats = Stats {
-        current_pixels_placed_count: 0u64,
+    let working_stats: RunStats = RunStats::new(total_pixel_count);
+
+    // each colony owns its seeds, palette and boundry region
+    // without a colonies section all seeds share the default palette
+    let colonies: Vec<Colony> = match config["colonies"]["groups"].as_vec() {
+        Some(groups) => groups
+            .iter()
+            .map(|group| Colony::from_config(group, total_pixel_count))
+            .collect(),
+        None => vec![Colony {
+            color_source: ColorSource::default(),
+            boundry_region_list: Vec::new(),
+            starting_locations: parse_starting_locations(&config["canvas"]["starting_locations"]),
+            weight: 1f32,
+            remaining_colors: Vec::new(),
+        }],
     };
 
-    let mut starting_points = Vec::new();
+    // the config and seed are embedded in the output so the run can be recreated
+    let output_settings: Arc<OutputSettings> = Arc::new(OutputSettings::from_config(
+        &config["output"],
+        RunMetadata::new(String::from(config_string), seed, RunOrigin::Shredder),
+        &working_constraints,
+    ));
+    let output_interval: f32 = parse_optional_config_value(&config["output"]["interval"], 0.5f32);
 
-    // CUSTOMIZED:
-    for location in config["canvas"]["starting_locations"] {
-        let _x = &location["x"]
-            .as_str()
-            .expect("[ERROR] failed to parse config value as string")
-            .parse::<u32>()
-            .expect("[ERROR] failed to convert config str to int");
-
-        let _y = &location["y"]
-            .as_str()
-            .expect("[ERROR] failed to parse config value as string")
-            .parse::<u32>()
-            .expect("[ERROR] failed to convert config str to int");
-
-        starting_points.push(Coordinate { x: _x, y: _y });
-    }
+    // huge canvases can keep their cells in scratch files instead of memory
+    let mapped_directory: Option<String> = match config["canvas"]["storage"].as_str() {
+        None | Some("memory") => None,
+        Some("mapped") => Some(parse_optional_config_value(
+            &config["canvas"]["storage_directory"],
+            String::from("./output"),
+        )),
+        Some(other) => panic!("[ERROR] unknown canvas storage: {}", other),
+    };
+
+    // a script that defines next_color takes over from the palettes,
+    // one that defines adjust_score refines every score
+    let script: Option<Arc<PaintingScript>> = config["script"]["path"].as_str().map(|path| {
+        let source: String = fs::read_to_string(path).expect("[ERROR] unable to read script file");
+        Arc::new(
+            PaintingScript::compile(&source)
+                .unwrap_or_else(|error| panic!("[ERROR] {}: {}", path, error)),
+        )
+    });
+
+    let scoring_pipeline: ScoringPipeline = ScoringPipeline::from_config(
+        &config["scoring"],
+        &config["flow"],
+        &working_constraints,
+        seed,
+        script.as_ref(),
+    );
+    let generation_mode: GenerationMode = GenerationMode::from_config(&config["generation"]);
+    // only the records an output map, a scoring term or the cell selection reads are kept
+    let diagnostic_maps: DiagnosticMaps = DiagnosticMaps::new(
+        working_constraints.x_size,
+        working_constraints.y_size,
+        mapped_directory.as_deref(),
+        output_settings
+            .recorded_maps()
+            .union(scoring_pipeline.recorded_maps())
+            .union(generation_mode.recorded_maps()),
+    );
 
     // hold all info required for painting
     let mut working_canvas: Painting = Painting {
-        image: RgbImage::new(working_constraints.x_size, working_constraints.y_size),
-        boundry_region_image: GrayImage::new(
+        canvas: TiledCanvas::create(
             working_constraints.x_size,
             working_constraints.y_size,
+            mapped_directory.as_deref(),
+            "canvas",
+        ),
+        colonies,
+        colony_scheduler: ColonyScheduler::new(SchedulingPolicy::from_config(
+            &config["colonies"]["scheduling"],
+        )),
+        scoring_pipeline,
+        generation_mode,
+        search_settings: SearchSettings::from_config(&config["search"]),
+        script,
+        output_writer: OutputWriter::spawn(
+            Arc::clone(&output_settings),
+            Duration::from_secs_f32(output_interval),
+            &working_constraints,
+            mapped_directory.as_deref(),
         ),
-        starting_locations: starting_points,
+        output_settings,
+        diagnostic_maps,
         canvas_constraints: working_constraints,
         canvas_stats: working_stats,
-        boundry_region_list: Vec::new(),
+        rng: StdRng::seed_from_u64(seed),
     };
 
-    // loop over starting positions and place random colors at each
-    for index in 0..working_canvas.starting_locations.len() {
-        let temp_color: Srgb = Hsv::new(
-            0.55f32 * 360f32,
-            random::<f32>().clamp(0.5f32, 1.0f32),
-            random::<f32>().clamp(0.0f32, 1.0f32),
-        )
-        .try_into_color()
-        .unwrap();
-        let temp_color_alt: Srgb = Hsv::new(
-            0.59f32 * 360f32,
-            random::<f32>().clamp(0.5f32, 1.0f32),
-            random::<f32>().clamp(0.0f32, 1.0f32),
-        )
-        .try_into_color()
-        .unwrap();
+    // loop over every colony's starting positions and place a color from its palette at each
+    for colony_index in 0..working_canvas.colonies.len() {
+        for index in 0..working_canvas.colonies[colony_index]
+            .starting_locations
+            .len()
+        {
+            let position: Coordinate =
+                working_canvas.colonies[colony_index].starting_locations[index];
+            if position.x >= working_canvas.canvas_constraints.x_size
+                || position.y >= working_canvas.canvas_constraints.y_size
+            {
+                panic!(
+                    "[ERROR] starting location {}, {} is outside the canvas",
+                    position.x, position.y
+                );
+            }
+
+            // a seed listed twice is only placed once
+            let cell: Cell = working_canvas.canvas.get(position.x, position.y);
+            if cell.is_painted() {
+                continue;
+            }
+            // a seed next to an earlier one takes its cell out of that colony's boundry region,
+            // otherwise the cell would be painted a second time later
+            if cell.is_frontier() {
+                release_boundry_position(&position, &mut working_canvas);
+            }
 
-        // choose a random color
-        let mut target_color: Rgb<u8> = Rgb([
-            (temp_color.red * 255f32).floor() as u8,
-            (temp_color.green * 255f32).floor() as u8,
-            (temp_color.blue * 255f32).floor() as u8,
-        ]);
-        if random::<bool>() {
-            target_color = Rgb([
-                (temp_color_alt.red * 255f32).floor() as u8,
-                (temp_color_alt.green * 255f32).floor() as u8,
-                (temp_color_alt.blue * 255f32).floor() as u8,
-            ]);
+            let palette_state: PaletteState = working_canvas.colonies[colony_index]
+                .color_source
+                .palette_at(0u64);
+            let target_pixel = Pixel {
+                position,
+                color: next_color(colony_index, &palette_state, &mut working_canvas),
+            };
+            // every seed owns the territory grown from it
+            working_canvas
+                .diagnostic_maps
+                .claim_seed(&target_pixel.position);
+            place_pixel(&target_pixel, colony_index, None, &mut working_canvas)
         }
-        let target_pixel = Pixel {
-            position: working_canvas.starting_locations[index].clone(),
-            color: target_color,
-        };
-        place_pixel(&target_pixel, &mut working_canvas)
+    }
+
+    // position first generation draws every colony's colors up front
+    if let GenerationMode::PositionFirst(_) = working_canvas.generation_mode {
+        draw_remaining_colors(&mut working_canvas, total_pixel_count);
     }
 
     return working_canvas;
 }
 
+// the next color of a colony, from the script when it defines next_color
+fn next_color(
+    colony_index: usize,
+    palette_state: &PaletteState,
+    working_canvas: &mut Painting,
+) -> Rgb<u8> {
+    let pixels_placed_count: u64 = working_canvas.canvas_stats.current_pixels_placed_count;
+    return next_color_at(
+        colony_index,
+        palette_state,
+        pixels_placed_count,
+        working_canvas,
+    );
+}
+
+// the color of a colony for a given point of the painting,
+// position first generation draws its whole pool before the first placement
+pub(crate) fn next_color_at(
+    colony_index: usize,
+    palette_state: &PaletteState,
+    pixels_placed_count: u64,
+    working_canvas: &mut Painting,
+) -> Rgb<u8> {
+    let Some(script) = working_canvas
+        .script
+        .as_ref()
+        .filter(|script| script.has_next_color())
+    else {
+        return working_canvas.colonies[colony_index]
+            .color_source
+            .next_color(palette_state, &mut working_canvas.rng);
+    };
+
+    let stats: ScriptStats = ScriptStats {
+        pixels_placed_count,
+        total_pixel_count: working_canvas.canvas_constraints.x_size as u64
+            * working_canvas.canvas_constraints.y_size as u64,
+        colony_index,
+        random: [
+            working_canvas.rng.gen::<f64>(),
+            working_canvas.rng.gen::<f64>(),
+            working_canvas.rng.gen::<f64>(),
+        ],
+    };
+    let color: [u8; 3] = script
+        .next_color(&stats)
+        .unwrap_or_else(|error| panic!("[ERROR] {}", error));
+    return Rgb(color);
+}
+
+// parse a required config value
+pub(crate) fn parse_config_value<T>(value: &StrictYaml) -> T
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    value
+        .as_str()
+        .expect("[ERROR] failed to parse config value as string")
+        .parse::<T>()
+        .expect("[ERROR] failed to convert config str")
+}
+
+// parse an optional config value, using the default when it is missing
+pub(crate) fn parse_optional_config_value<T>(value: &StrictYaml, default: T) -> T
+where
+    T: FromStr,
+    T::Err: Debug,
+{
+    match value.as_str() {
+        Some(_) => parse_config_value(value),
+        None => default,
+    }
+}
+
+// kept for hand-placed runs, starting locations normally come from the config
+#[allow(dead_code)]
 fn get_initial_locations(working_constraints: &Constraints) -> Vec<Coordinate> {
     // hold starting locations
     let mut starting_points = Vec::new();
@@ -202,36 +555,88 @@ fn get_initial_locations(working_constraints: &Constraints) -> Vec<Coordinate> {
     return starting_points;
 }
 
-// write the output PNG files
-fn write_output_files(working_canvas: &Painting) {
-    // write the RGB painting file
+// sample the run statistics, then show and write them
+pub(crate) fn report_progress(
+    working_canvas: &mut Painting,
+    run_start_time: &Instant,
+) -> StatsSample {
+    let sample: StatsSample = working_canvas.canvas_stats.sample(
+        run_start_time.elapsed().as_secs_f64(),
+        frontier_size(working_canvas),
+    );
     working_canvas
-        .image
-        .save_with_format("./output/painting.png", ImageFormat::Png)
-        .unwrap();
+        .output_settings
+        .report_progress(&working_canvas.canvas_stats, &sample);
+    return sample;
+}
 
-    // write the boundry region image
+// the number of cells in all boundry regions together
+pub(crate) fn frontier_size(working_canvas: &Painting) -> usize {
+    return working_canvas
+        .colonies
+        .iter()
+        .map(|colony| colony.boundry_region_list.len())
+        .sum();
+}
+
+// hand the changed parts of the output images to the background writer
+// the update is skipped while the previous one is still being written
+pub(crate) fn write_output_files(working_canvas: &mut Painting) {
     working_canvas
-        .boundry_region_image
-        .save_with_format("./output/boundry.png", ImageFormat::Png)
-        .unwrap();
+        .output_writer
+        .submit(|| take_snapshot(&mut working_canvas.canvas));
 }
 
-// update a pixel on the canvas and add its neighbors to the boundry region
-fn place_pixel(target: &Pixel, working_canvas: &mut Painting) {
+// the canvas for the background writer, a painting in memory shares its tiles with it
+// and a mapped one copies the tiles changed since the last snapshot
+fn take_snapshot(canvas: &mut TiledCanvas<Cell>) -> Snapshot {
+    if let Some(shared_canvas) = canvas.share() {
+        return Snapshot::Shared(shared_canvas);
+    }
+    return Snapshot::ChangedTiles(
+        canvas
+            .take_dirty_tiles()
+            .into_iter()
+            .map(|tile_index| {
+                let mut cells: Vec<Cell> = Vec::new();
+                canvas.read_tile(tile_index, &mut cells);
+                (tile_index, cells)
+            })
+            .collect(),
+    );
+}
+
+// update a pixel on the canvas and add its neighbors to the colony's boundry region
+pub(crate) fn place_pixel(
+    target: &Pixel,
+    colony_index: usize,
+    chosen_score: Option<f32>,
+    working_canvas: &mut Painting,
+) {
     // update a pixel on the canvas
-    working_canvas
-        .image
-        .put_pixel(target.position.x, target.position.y, target.color);
+    working_canvas.canvas.put(
+        target.position.x,
+        target.position.y,
+        Cell::painted(target.color.0),
+    );
 
     // update counter
-    working_canvas.canvas_stats.current_pixels_placed_count += 1;
+    working_canvas.canvas_stats.record_placement(chosen_score);
+    working_canvas.diagnostic_maps.record_placement(
+        &target.position,
+        working_canvas.canvas_stats.current_pixels_placed_count,
+        chosen_score.unwrap_or(0f32),
+    );
 
     // loop over neighbors in a 3x3 grid around the target
     for i in 0..3 {
         for j in 0..3 {
-            // skip self
-            if i == 1 && j == 1 {
+            // skip self, and the corners when only edges count
+            if !working_canvas
+                .canvas_constraints
+                .neighborhood
+                .contains(i, j)
+            {
                 continue;
             }
             // prevent less than zero out-of-bounds
@@ -253,106 +658,314 @@ fn place_pixel(target: &Pixel, working_canvas: &mut Painting) {
             let neighbor_x_coord: u32 = target.position.x + i - 1;
             let neighbor_y_coord: u32 = target.position.y + j - 1;
 
-            // get the neigbor's luma (boundry region value)
-            let neighbor_luma: Luma<u8> = *working_canvas
-                .boundry_region_image
-                .get_pixel(neighbor_x_coord, neighbor_y_coord);
-
-            // get the neighbor's color
-            let neighbor_color: Rgb<u8> = *working_canvas
-                .image
-                .get_pixel(neighbor_x_coord, neighbor_y_coord);
-
             // ensure locations are not added to the boundry region as duplicates
-            if neighbor_luma != Luma([0u8]) || neighbor_color != Rgb([0u8, 0u8, 0u8]) {
+            // (cells already claimed by another colony's boundry region stay with that colony)
+            if !working_canvas
+                .canvas
+                .get(neighbor_x_coord, neighbor_y_coord)
+                .is_empty()
+            {
                 continue;
             }
 
-            // add this neighbor to the boundry region LIST
-            working_canvas.boundry_region_list.push(Coordinate {
+            // add this neighbor to the colony's boundry region LIST
+            let neighbor: Coordinate = Coordinate {
                 x: neighbor_x_coord,
                 y: neighbor_y_coord,
-            });
-            // add this neighbor to the boundry region IMAGE (luma)
-            working_canvas.boundry_region_image.put_pixel(
-                neighbor_x_coord,
-                neighbor_y_coord,
-                Luma([255u8]),
+            };
+            working_canvas.colonies[colony_index]
+                .boundry_region_list
+                .push(neighbor);
+            working_canvas.diagnostic_maps.claim_boundry(
+                &neighbor,
+                &target.position,
+                working_canvas.canvas_stats.current_pixels_placed_count,
             );
+            // mark this neighbor as frontier on the canvas
+            working_canvas
+                .canvas
+                .put(neighbor_x_coord, neighbor_y_coord, Cell::FRONTIER);
+        }
+    }
+}
+
+// boundry cells scored together by the simd distance kernel, and by one thread
+const EVALUATION_BATCH_SIZE: usize = 256;
+
+pub(crate) fn get_best_position_for_color(
+    target_color: Rgb<u8>,
+    average_weight: f32,
+    colony_index: usize,
+    working_canvas: &mut Painting,
+) -> (Pixel, f32) {
+    // equally good positions are broken by a seeded hash of their index,
+    // which keeps the parallel search reproducible
+    let tie_breaker_salt: u64 = working_canvas.rng.gen::<u64>();
+
+    // large boundry regions may only be sampled, depending on the search settings
+    let candidate_indices: Option<Vec<usize>> = working_canvas.search_settings.candidate_indices(
+        working_canvas.colonies[colony_index]
+            .boundry_region_list
+            .len(),
+        &mut working_canvas.rng,
+    );
+
+    let scoring_context: ScoringContext = scoring_context(
+        target_color,
+        average_weight,
+        tie_breaker_salt,
+        working_canvas,
+    );
+    let boundry_region_list: &Vec<Coordinate> =
+        &working_canvas.colonies[colony_index].boundry_region_list;
+
+    let better = |a: (f32, Coordinate, usize), b: (f32, Coordinate, usize)| {
+        better_candidate(tie_breaker_salt, a, b)
+    };
+    // candidates are scored in batches, each batch on one thread
+    let exact_search = || {
+        (0..boundry_region_list.len().div_ceil(EVALUATION_BATCH_SIZE))
+            .into_par_iter()
+            .flat_map_iter(|batch_index| {
+                let start: usize = batch_index * EVALUATION_BATCH_SIZE;
+                let end: usize = (start + EVALUATION_BATCH_SIZE).min(boundry_region_list.len());
+                evaluate_positions(
+                    boundry_region_list,
+                    start..end,
+                    &scoring_context,
+                    &working_canvas.scoring_pipeline,
+                )
+            })
+            .reduce_with(&better)
+            .unwrap()
+    };
+
+    // approximate searches are compared with the exact one now and then,
+    // which estimates how much quality the sampling costs
+    let mut search_audit: Option<(f32, f32)> = None;
+    let (best_value, _, best_position_index) = match &candidate_indices {
+        None => exact_search(),
+        Some(indices) => {
+            let approximate_best: (f32, Coordinate, usize) = indices
+                .par_chunks(EVALUATION_BATCH_SIZE)
+                .flat_map_iter(|batch| {
+                    evaluate_positions(
+                        boundry_region_list,
+                        batch.iter().copied(),
+                        &scoring_context,
+                        &working_canvas.scoring_pipeline,
+                    )
+                })
+                .reduce_with(&better)
+                .unwrap();
+            if working_canvas
+                .search_settings
+                .audit_due(working_canvas.canvas_stats.current_pixels_placed_count)
+            {
+                search_audit = Some((approximate_best.0, exact_search().0));
+            }
+            approximate_best
         }
+    };
+    if let Some((approximate_score, exact_score)) = search_audit {
+        working_canvas
+            .canvas_stats
+            .record_search_audit(approximate_score, exact_score);
     }
+
+    let position: Coordinate =
+        take_boundry_position(colony_index, best_position_index, working_canvas);
+    return (
+        Pixel {
+            color: target_color,
+            position,
+        },
+        best_value,
+    );
 }
 
-fn get_best_position_for_color(target_color: Rgb<u8>, working_canvas: &mut Painting) -> Pixel {
-    let (_best_value, best_position, best_position_index) = working_canvas
-        .boundry_region_list
+// position first: pick a boundry cell, then the remaining color that suits it best
+fn get_best_color_for_position(
+    cell_selection: CellSelection,
+    palette_state: &PaletteState,
+    colony_index: usize,
+    working_canvas: &mut Painting,
+) -> (Pixel, f32) {
+    let cell_index: usize = cell_selection.choose_cell(
+        &working_canvas.colonies[colony_index].boundry_region_list,
+        &working_canvas.diagnostic_maps,
+        &working_canvas.canvas,
+        &working_canvas.canvas_constraints,
+        &mut working_canvas.rng,
+    );
+    let position: Coordinate =
+        working_canvas.colonies[colony_index].boundry_region_list[cell_index];
+
+    // a colony that used up its pool keeps growing with fresh palette colors
+    if working_canvas.colonies[colony_index]
+        .remaining_colors
+        .is_empty()
+    {
+        let target_color: Rgb<u8> = next_color(colony_index, palette_state, working_canvas);
+        let tie_breaker_salt: u64 = working_canvas.rng.gen::<u64>();
+        let (score, _, _) = evaluate_position(
+            &position,
+            cell_index,
+            &scoring_context(
+                target_color,
+                palette_state.average_weight,
+                tie_breaker_salt,
+                working_canvas,
+            ),
+            &working_canvas.scoring_pipeline,
+        );
+        take_boundry_position(colony_index, cell_index, working_canvas);
+        return (
+            Pixel {
+                color: target_color,
+                position,
+            },
+            score,
+        );
+    }
+
+    // the same scoring as color first, with the cell fixed and the color varying
+    // the cell's neighbors are the same for every color, so they are gathered once
+    let tie_breaker_salt: u64 = working_canvas.rng.gen::<u64>();
+    let base_context: ScoringContext = scoring_context(
+        Rgb([0u8, 0u8, 0u8]),
+        palette_state.average_weight,
+        tie_breaker_salt,
+        working_canvas,
+    );
+    let (neighbors, neighbor_offset) = gather_neighbors(
+        &position,
+        &working_canvas.canvas,
+        &working_canvas.canvas_constraints,
+    );
+    let (best_value, _, best_color_index) = working_canvas.colonies[colony_index]
+        .remaining_colors
         .par_iter()
         .enumerate()
-        .map(|available_location| {
-            evaluate_position(
-                available_location.1,
-                available_location.0,
-                &target_color,
-                &working_canvas.image,
-                &working_canvas.canvas_constraints,
+        .map(|(color_index, color)| {
+            score_neighborhood(
+                &position,
+                color_index,
+                &neighbor_distances(&neighbors, *color),
+                neighbor_offset,
+                &ScoringContext {
+                    target_color: Rgb(*color),
+                    ..base_context
+                },
+                &working_canvas.scoring_pipeline,
             )
         })
-        .reduce_with(|a, b| {
-            if a.0 < b.0 {
-                return a;
-            } else if a.0 == b.0 && random::<bool>() {
-                return a;
-            } else {
-                return b;
-            }
-        })
+        .reduce_with(|a, b| better_candidate(tie_breaker_salt, a, b))
         .unwrap();
 
-    // remove target pixel from boundrry region IMAGE
-    working_canvas
-        .boundry_region_image
-        .put_pixel(best_position.x, best_position.y, Luma([0u8]));
+    let target_color: [u8; 3] = working_canvas.colonies[colony_index]
+        .remaining_colors
+        .swap_remove(best_color_index);
+    take_boundry_position(colony_index, cell_index, working_canvas);
+    return (
+        Pixel {
+            color: Rgb(target_color),
+            position,
+        },
+        best_value,
+    );
+}
+
+// the better of two scored candidates, the lower score wins
+// equal scores are broken by a seeded hash of the candidates' indices,
+// which keeps the parallel searches reproducible
+fn better_candidate(
+    tie_breaker_salt: u64,
+    a: (f32, Coordinate, usize),
+    b: (f32, Coordinate, usize),
+) -> (f32, Coordinate, usize) {
+    if a.0 < b.0
+        || (a.0 == b.0 && tie_breaker(tie_breaker_salt, a.2) < tie_breaker(tie_breaker_salt, b.2))
+    {
+        return a;
+    }
+    return b;
+}
 
+// everything the scoring terms look at besides the candidate cells
+pub(crate) fn scoring_context<'a>(
+    target_color: Rgb<u8>,
+    average_weight: f32,
+    tie_breaker_salt: u64,
+    working_canvas: &'a Painting,
+) -> ScoringContext<'a> {
+    return ScoringContext {
+        target_color,
+        average_weight,
+        placement_count: working_canvas.canvas_stats.current_pixels_placed_count,
+        // the noise term is derived from the salt so it draws nothing extra from the rng
+        noise_salt: tie_breaker(tie_breaker_salt, usize::MAX),
+        canvas_image: &working_canvas.canvas,
+        canvas_constraints: &working_canvas.canvas_constraints,
+        diagnostic_maps: &working_canvas.diagnostic_maps,
+    };
+}
+
+// remove a cell from a colony's boundry region, returning its position
+pub(crate) fn take_boundry_position(
+    colony_index: usize,
+    boundry_index: usize,
+    working_canvas: &mut Painting,
+) -> Coordinate {
     // if this is not the last location of the boundry region
     // swap remove the target pixel location from the boundry region LIST
     // (swap remove is much faster)
-    if working_canvas.boundry_region_list.len() > 1 {
-        return Pixel {
-            color: target_color,
-            position: working_canvas
-                .boundry_region_list
-                .swap_remove(best_position_index),
-        };
+    let boundry_region_list: &mut Vec<Coordinate> =
+        &mut working_canvas.colonies[colony_index].boundry_region_list;
+    let position: Coordinate = if boundry_region_list.len() > 1 {
+        boundry_region_list.swap_remove(boundry_index)
     }
     // for the last elemet remove normally
     else {
-        return Pixel {
-            color: target_color,
-            position: working_canvas
-                .boundry_region_list
-                .remove(best_position_index),
-        };
+        boundry_region_list.remove(boundry_index)
+    };
+
+    // remove the frontier mark of the target cell from the canvas
+    working_canvas
+        .canvas
+        .put(position.x, position.y, Cell::EMPTY);
+    return position;
+}
+
+// remove a frontier cell from whichever colony's boundry region holds it
+fn release_boundry_position(position: &Coordinate, working_canvas: &mut Painting) {
+    for colony_index in 0..working_canvas.colonies.len() {
+        if let Some(boundry_index) = working_canvas.colonies[colony_index]
+            .boundry_region_list
+            .iter()
+            .position(|cell| cell.x == position.x && cell.y == position.y)
+        {
+            take_boundry_position(colony_index, boundry_index, working_canvas);
+            return;
+        }
     }
 }
 
-fn evaluate_position(
+// the colors of a cell's painted neighbors,
+// and the sum of the offsets to them, which the flow field compares to its direction
+fn gather_neighbors(
     target_location: &Coordinate,
-    target_index: usize,
-    target_color: &Rgb<u8>,
-    canvas_image: &RgbImage,
+    canvas_image: &TiledCanvas<Cell>,
     canvas_constraints: &Constraints,
-) -> (f32, Coordinate, usize) {
-    let mut cummulative_color_distance: f32 = 0f32;
-    let mut neighbor_count: u64 = 0;
-    let mut color_distance: f32;
-    let _average_color_distance: f32;
-    let mut min_color_distance: f32 = f32::MAX;
+) -> (NeighborColors, [f32; 2]) {
+    let mut neighbors: NeighborColors = NeighborColors::new();
+    let mut neighbor_offset: [f32; 2] = [0f32, 0f32];
 
     // loop over neighbors in a 3x3 grid around the target
     for i in 0..3 {
         for j in 0..3 {
-            // skip self
-            if i == 1 && j == 1 {
+            // skip self, and the corners when only edges count
+            if !canvas_constraints.neighborhood.contains(i, j) {
                 continue;
             }
             // prevent less than zero out-of-bounds
@@ -374,36 +987,113 @@ fn evaluate_position(
             let neighbor_x_coord: u32 = target_location.x + i - 1;
             let neighbor_y_coord: u32 = target_location.y + j - 1;
 
-            // get color at neighbor's coordinates
-            let neighbor_color: Rgb<u8> =
-                *canvas_image.get_pixel(neighbor_x_coord, neighbor_y_coord);
+            // get the cell at neighbor's coordinates
+            let neighbor_cell: Cell = canvas_image.get(neighbor_x_coord, neighbor_y_coord);
 
             // skip un-colored
-            if neighbor_color == Rgb([0u8, 0u8, 0u8]) {
+            if !neighbor_cell.is_painted() {
                 continue;
             }
 
-            // increment neighbor count
-            neighbor_count += 1;
+            neighbors.push(neighbor_cell.rgb());
+            neighbor_offset[0] += i as f32 - 1f32;
+            neighbor_offset[1] += j as f32 - 1f32;
+        }
+    }
+    return (neighbors, neighbor_offset);
+}
+
+// score a single cell with the scalar distance kernel
+pub(crate) fn evaluate_position(
+    target_location: &Coordinate,
+    target_index: usize,
+    scoring_context: &ScoringContext,
+    scoring_pipeline: &ScoringPipeline,
+) -> (f32, Coordinate, usize) {
+    let (neighbors, neighbor_offset) = gather_neighbors(
+        target_location,
+        scoring_context.canvas_image,
+        scoring_context.canvas_constraints,
+    );
+    let distances: NeighborDistances =
+        neighbor_distances(&neighbors, scoring_context.target_color.0);
+    return score_neighborhood(
+        target_location,
+        target_index,
+        &distances,
+        neighbor_offset,
+        scoring_context,
+        scoring_pipeline,
+    );
+}
 
-            //compute color distance
-            color_distance = 0f32;
-            for i in 0..3 {
-                color_distance +=
-                    (target_color[i] as f32 - neighbor_color[i] as f32).powf(2f32) as f32;
-            }
-            cummulative_color_distance += color_distance;
+// score a batch of boundry cells, their color distances are computed together by the simd kernel
+fn evaluate_positions(
+    boundry_region_list: &[Coordinate],
+    candidate_indices: impl Iterator<Item = usize>,
+    scoring_context: &ScoringContext,
+    scoring_pipeline: &ScoringPipeline,
+) -> Vec<(f32, Coordinate, usize)> {
+    let mut batch: NeighborBatch = NeighborBatch::with_capacity(EVALUATION_BATCH_SIZE);
+    let mut candidates: Vec<(usize, [f32; 2])> = Vec::with_capacity(EVALUATION_BATCH_SIZE);
+    for index in candidate_indices {
+        let (neighbors, neighbor_offset) = gather_neighbors(
+            &boundry_region_list[index],
+            scoring_context.canvas_image,
+            scoring_context.canvas_constraints,
+        );
+        batch.push(&neighbors);
+        candidates.push((index, neighbor_offset));
+    }
 
-            // update MIN
-            if color_distance < min_color_distance {
-                min_color_distance = color_distance;
-            } else if color_distance == min_color_distance && random::<bool>() {
-                min_color_distance = color_distance;
-            }
-        }
+    let mut distances: Vec<NeighborDistances> = Vec::with_capacity(batch.len());
+    batch.distances(scoring_context.target_color.0, &mut distances);
+    return candidates
+        .iter()
+        .zip(distances.iter())
+        .map(|((index, neighbor_offset), distances)| {
+            score_neighborhood(
+                &boundry_region_list[*index],
+                *index,
+                distances,
+                *neighbor_offset,
+                scoring_context,
+                scoring_pipeline,
+            )
+        })
+        .collect();
+}
+
+// combine the color distances with the other configured terms
+fn score_neighborhood(
+    target_location: &Coordinate,
+    target_index: usize,
+    distances: &NeighborDistances,
+    neighbor_offset: [f32; 2],
+    scoring_context: &ScoringContext,
+    scoring_pipeline: &ScoringPipeline,
+) -> (f32, Coordinate, usize) {
+    // without colored neighbors there is nothing to compare against
+    if distances.count == 0 {
+        return (f32::MAX, *target_location, target_index);
     }
 
-    // update AVG
-    _average_color_distance = cummulative_color_distance / neighbor_count as f32;
-    return (min_color_distance, *target_location, target_index);
+    let score: f32 = scoring_pipeline.score(
+        target_location,
+        &Neighborhood {
+            min_color_distance: distances.min,
+            average_color_distance: distances.sum / distances.count as f32,
+            neighbor_offset,
+        },
+        scoring_context,
+    );
+    return (score, *target_location, target_index);
+}
+
+// mix a candidate's index with the step's salt (splitmix64 finalizer)
+pub(crate) fn tie_breaker(salt: u64, index: usize) -> u64 {
+    let mut value: u64 = salt ^ (index as u64);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return value ^ (value >> 31);
 }
@@ -0,0 +1,414 @@
+use image::{imageops, Rgba, RgbaImage};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use serde_json::Value;
+use std::{
+    fs,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+use strict_yaml_rust::{strict_yaml::Hash, StrictYaml, StrictYamlEmitter, StrictYamlLoader};
+
+use crate::cell::Cell;
+use crate::tiled_canvas::TiledCanvas;
+use crate::{
+    finish_painting, initialize_canvas, parse_config_value, parse_optional_config_value,
+    place_next_pixel, Painting,
+};
+
+// space around and between the thumbnails of the contact sheet, in pixels
+const SHEET_GAP: u32 = 4u32;
+const SHEET_BACKGROUND: Rgba<u8> = Rgba([32u8, 32u8, 32u8, 255u8]);
+
+// one dimension of a sweep: a config value and the values it takes
+struct SweepAxis {
+    name: String,
+    // dotted path below the "config" section, e.g. "search.mode"
+    path: String,
+    // label and value, scalar values are their own label, others are numbered
+    values: Vec<(String, StrictYaml)>,
+}
+impl SweepAxis {
+    // read one entry of the "axes" list
+    fn from_config(config: &StrictYaml) -> SweepAxis {
+        let path: String = parse_config_value(&config["path"]);
+        let values: Vec<(String, StrictYaml)> = config["values"]
+            .as_vec()
+            .filter(|values| !values.is_empty())
+            .unwrap_or_else(|| panic!("[ERROR] sweep axis {} requires a list of values", path))
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let label: String = value
+                    .as_str()
+                    .map(String::from)
+                    .unwrap_or_else(|| (index + 1).to_string());
+                (label, value.clone())
+            })
+            .collect();
+        return SweepAxis {
+            name: parse_optional_config_value(&config["name"], path.clone()),
+            path,
+            values,
+        };
+    }
+}
+
+// one variation of the base config
+struct SweepJob {
+    // also the name of the job's output directory
+    name: String,
+    // axis name and value label
+    parameters: Vec<(String, String)>,
+    config: String,
+}
+
+// what a finished job leaves for the contact sheet and the index
+struct JobResult {
+    painting_path: String,
+    thumbnail: RgbaImage,
+    seconds: f64,
+}
+
+// render every combination of the sweep's axes, several jobs at a time,
+// then write a contact sheet and an index of the results
+pub(crate) fn run_sweep(sweep_path: &str) {
+    let sweep_string: String = fs::read_to_string(sweep_path)
+        .unwrap_or_else(|error| panic!("[ERROR] {}: {}", sweep_path, error));
+    let sweep = &StrictYamlLoader::load_from_str(&sweep_string)
+        .expect("[ERROR] unable to parse sweep file")[0]["sweep"];
+
+    let base_path: String = parse_config_value(&sweep["base"]);
+    let base_string: String = fs::read_to_string(&base_path)
+        .unwrap_or_else(|error| panic!("[ERROR] {}: {}", base_path, error));
+    let base: StrictYaml = StrictYamlLoader::load_from_str(&base_string)
+        .expect("[ERROR] unable to parse the sweep's base config")
+        .remove(0);
+
+    let directory: String =
+        parse_optional_config_value(&sweep["directory"], String::from("./output/sweep"));
+    let thumbnail_size: u32 = parse_optional_config_value(&sweep["thumbnail_size"], 128u32);
+    // every job scores in parallel too, so a few jobs at a time already fill all cores
+    let parallel_jobs: usize =
+        parse_optional_config_value(&sweep["parallel_jobs"], rayon::current_num_threads());
+    let axes: Vec<SweepAxis> = sweep["axes"]
+        .as_vec()
+        .expect("[ERROR] a sweep requires a list of axes")
+        .iter()
+        .map(SweepAxis::from_config)
+        .collect();
+
+    let jobs: Vec<SweepJob> = expand_jobs(&base, &axes, &directory);
+    for job in jobs.iter() {
+        fs::create_dir_all(format!("{}/{}", directory, job.name))
+            .unwrap_or_else(|error| panic!("[ERROR] {}/{}: {}", directory, job.name, error));
+    }
+
+    let finished_count: AtomicUsize = AtomicUsize::new(0usize);
+    let results: Vec<JobResult> = rayon::ThreadPoolBuilder::new()
+        .num_threads(parallel_jobs.max(1usize))
+        .build()
+        .expect("[ERROR] unable to start the sweep's threads")
+        .install(|| {
+            jobs.par_iter()
+                .map(|job| {
+                    let result: JobResult = run_job(job, thumbnail_size);
+                    eprintln!(
+                        "[{}/{}] {} took {:.1}s",
+                        finished_count.fetch_add(1usize, Ordering::Relaxed) + 1usize,
+                        jobs.len(),
+                        job.name,
+                        result.seconds
+                    );
+                    result
+                })
+                .collect()
+        });
+
+    // the last axis runs along the rows of the contact sheet
+    let columns: usize = axes.last().map_or(1usize, |axis| axis.values.len());
+    let sheet_path: String = format!("{}/contact_sheet.png", directory);
+    contact_sheet(&results, columns, thumbnail_size)
+        .save(&sheet_path)
+        .unwrap_or_else(|error| panic!("[ERROR] {}: {}", sheet_path, error));
+    write_index(&directory, &jobs, &results, thumbnail_size);
+}
+
+// one job per combination of axis values, the last axis varies fastest
+fn expand_jobs(base: &StrictYaml, axes: &[SweepAxis], directory: &str) -> Vec<SweepJob> {
+    let job_count: usize = axes.iter().map(|axis| axis.values.len()).product();
+    return (0..job_count)
+        .map(|job_index| {
+            let mut config: StrictYaml = base.clone();
+            let mut parameters: Vec<(String, String)> = Vec::new();
+            let mut remainder: usize = job_index;
+            for axis in axes.iter().rev() {
+                let (label, value) = &axis.values[remainder % axis.values.len()];
+                remainder /= axis.values.len();
+                set_config_value(&mut config, &format!("config.{}", axis.path), value.clone());
+                parameters.insert(0usize, (axis.name.clone(), label.clone()));
+            }
+
+            // the index keeps job directories unique and in sweep order
+            let mut name: String = format!("{:03}", job_index);
+            for (axis_name, label) in parameters.iter() {
+                name.push_str(&format!(
+                    "_{}-{}",
+                    file_name_part(axis_name),
+                    file_name_part(label)
+                ));
+            }
+
+            // every job writes into its own directory, progress lines would only interleave
+            set_config_value(
+                &mut config,
+                "config.output.directory",
+                StrictYaml::String(format!("{}/{}", directory, name)),
+            );
+            set_config_value(
+                &mut config,
+                "config.output.stats.progress",
+                StrictYaml::String(String::from("false")),
+            );
+
+            let mut config_string: String = String::new();
+            StrictYamlEmitter::new(&mut config_string)
+                .dump(&config)
+                .expect("[ERROR] unable to write a sweep job's config");
+            SweepJob {
+                name,
+                parameters,
+                config: config_string,
+            }
+        })
+        .collect();
+}
+
+// replace the value at a dotted path, creating missing sections on the way
+pub(crate) fn set_config_value(config: &mut StrictYaml, path: &str, value: StrictYaml) {
+    let mut node: &mut StrictYaml = config;
+    for key in path.split('.') {
+        if !matches!(node, StrictYaml::Hash(_)) {
+            *node = StrictYaml::Hash(Hash::new());
+        }
+        let StrictYaml::Hash(section) = node else {
+            unreachable!()
+        };
+        node = section
+            .entry(StrictYaml::String(String::from(key)))
+            .or_insert(StrictYaml::BadValue);
+    }
+    *node = value;
+}
+
+// letters, digits, dots and dashes, everything else becomes a dash
+fn file_name_part(text: &str) -> String {
+    text.chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() || character == '.' || character == '-' {
+                character
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+fn run_job(job: &SweepJob, thumbnail_size: u32) -> JobResult {
+    let run_start_time: Instant = Instant::now();
+    let mut painting: Painting = initialize_canvas(&job.config, None);
+    while place_next_pixel(&mut painting) {}
+    finish_painting(&mut painting, &run_start_time);
+
+    return JobResult {
+        painting_path: String::from(painting.output_settings.painting_path()),
+        thumbnail: thumbnail(&painting.canvas, thumbnail_size),
+        seconds: run_start_time.elapsed().as_secs_f64(),
+    };
+}
+
+// the canvas shrunk to fit a square of the given size, unpainted cells are transparent
+fn thumbnail(canvas: &TiledCanvas<Cell>, size: u32) -> RgbaImage {
+    let preview: TiledCanvas<Cell> = canvas.downscaled(size);
+    return RgbaImage::from_fn(preview.width(), preview.height(), |x, y| {
+        let cell: Cell = preview.get(x, y);
+        let [red, green, blue] = cell.rgb();
+        Rgba([
+            red,
+            green,
+            blue,
+            if cell.is_painted() { 255u8 } else { 0u8 },
+        ])
+    });
+}
+
+// every job's thumbnail in a grid, in sweep order
+fn contact_sheet(results: &[JobResult], columns: usize, thumbnail_size: u32) -> RgbaImage {
+    let rows: usize = results.len().div_ceil(columns);
+    let cell_size: u32 = thumbnail_size + SHEET_GAP;
+    let mut sheet: RgbaImage = RgbaImage::from_pixel(
+        columns as u32 * cell_size + SHEET_GAP,
+        rows as u32 * cell_size + SHEET_GAP,
+        SHEET_BACKGROUND,
+    );
+    for (index, result) in results.iter().enumerate() {
+        imageops::overlay(
+            &mut sheet,
+            &result.thumbnail,
+            (SHEET_GAP + (index % columns) as u32 * cell_size) as i64,
+            (SHEET_GAP + (index / columns) as u32 * cell_size) as i64,
+        );
+    }
+    return sheet;
+}
+
+// index.json for tools and index.html for people, painting paths are relative to the sweep
+fn write_index(directory: &str, jobs: &[SweepJob], results: &[JobResult], thumbnail_size: u32) {
+    let relative_path = |path: &str| -> String {
+        String::from(
+            path.strip_prefix(&format!("{}/", directory))
+                .unwrap_or(path),
+        )
+    };
+
+    let mut json_entries: Vec<String> = Vec::new();
+    let mut html_entries: Vec<String> = Vec::new();
+    for (job, result) in jobs.iter().zip(results.iter()) {
+        let painting_path: String = relative_path(&result.painting_path);
+        let json_parameters: Vec<String> = job
+            .parameters
+            .iter()
+            .map(|(name, label)| format!("{}: {}", json_string(name), json_string(label)))
+            .collect();
+        json_entries.push(format!(
+            "    {{\"name\": {}, \"parameters\": {{{}}}, \"painting\": {}, \"seconds\": {:.3}}}",
+            json_string(&job.name),
+            json_parameters.join(", "),
+            json_string(&painting_path),
+            result.seconds
+        ));
+
+        let html_parameters: Vec<String> = job
+            .parameters
+            .iter()
+            .map(|(name, label)| format!("{}: {}", html_text(name), html_text(label)))
+            .collect();
+        html_entries.push(format!(
+            "<figure><a href=\"{0}\"><img src=\"{0}\" width=\"{1}\"></a><figcaption>{2}</figcaption></figure>",
+            html_text(&painting_path),
+            thumbnail_size,
+            html_parameters.join("<br>")
+        ));
+    }
+
+    let json_path: String = format!("{}/index.json", directory);
+    fs::write(
+        &json_path,
+        format!("{{\n  \"jobs\": [\n{}\n  ]\n}}\n", json_entries.join(",\n")),
+    )
+    .unwrap_or_else(|error| panic!("[ERROR] {}: {}", json_path, error));
+
+    let html_path: String = format!("{}/index.html", directory);
+    fs::write(
+        &html_path,
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>sweep</title>\n<style>body {{ background: #202020; color: #d0d0d0; font-family: monospace; }} figure {{ display: inline-block; vertical-align: top; }} img {{ image-rendering: pixelated; }}</style>\n</head>\n<body>\n<img src=\"contact_sheet.png\">\n{}\n</body>\n</html>\n",
+            html_entries.join("\n")
+        ),
+    )
+    .unwrap_or_else(|error| panic!("[ERROR] {}: {}", html_path, error));
+}
+
+fn json_string(text: &str) -> String {
+    return Value::String(String::from(text)).to_string();
+}
+
+fn html_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(text: &str) -> StrictYaml {
+        return StrictYamlLoader::load_from_str(text).unwrap().remove(0);
+    }
+
+    #[test]
+    fn expands_every_combination_with_the_last_axis_fastest() {
+        let base: StrictYaml = load("config:\n  seed: \"1\"\n");
+        let axes: Vec<SweepAxis> = load(
+            "- path: \"search.mode\"
+  values:
+    - \"exact\"
+    - \"sample\"
+- name: \"size\"
+  path: \"canvas.size\"
+  values:
+    - x: \"8\"
+      y: \"8\"
+    - x: \"16\"
+      y: \"8\"
+",
+        )
+        .as_vec()
+        .unwrap()
+        .iter()
+        .map(SweepAxis::from_config)
+        .collect();
+
+        let jobs: Vec<SweepJob> = expand_jobs(&base, &axes, "out");
+        let names: Vec<&str> = jobs.iter().map(|job| job.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "000_search.mode-exact_size-1",
+                "001_search.mode-exact_size-2",
+                "002_search.mode-sample_size-1",
+                "003_search.mode-sample_size-2",
+            ]
+        );
+        assert_eq!(
+            jobs[1].parameters,
+            vec![
+                (String::from("search.mode"), String::from("exact")),
+                (String::from("size"), String::from("2")),
+            ]
+        );
+
+        let config: StrictYaml = load(&jobs[1].config);
+        assert_eq!(config["config"]["seed"].as_str(), Some("1"));
+        assert_eq!(config["config"]["search"]["mode"].as_str(), Some("exact"));
+        assert_eq!(config["config"]["canvas"]["size"]["x"].as_str(), Some("16"));
+        assert_eq!(
+            config["config"]["output"]["directory"].as_str(),
+            Some("out/001_search.mode-exact_size-2")
+        );
+        assert_eq!(
+            config["config"]["output"]["stats"]["progress"].as_str(),
+            Some("false")
+        );
+    }
+
+    #[test]
+    fn replaces_values_below_scalars() {
+        let mut config: StrictYaml = load("config:\n  search: \"exact\"\n");
+        set_config_value(
+            &mut config,
+            "config.search.mode",
+            StrictYaml::String(String::from("sample")),
+        );
+        assert_eq!(config["config"]["search"]["mode"].as_str(), Some("sample"));
+    }
+
+    #[test]
+    fn escapes_names_for_files_and_json() {
+        assert_eq!(file_name_part("a b/c.d"), "a-b-c.d");
+        let name: &str = "say \"hi\"\\\n\u{1}";
+        let parsed: Value = serde_json::from_str(&json_string(name)).unwrap();
+        assert_eq!(parsed.as_str(), Some(name));
+    }
+}
@@ -0,0 +1,235 @@
+use bytemuck::Pod;
+use std::sync::Arc;
+
+// edge length of the square tiles, in cells
+pub(crate) const TILE_SIZE: u32 = 256;
+const TILE_CELL_COUNT: usize = (TILE_SIZE * TILE_SIZE) as usize;
+
+// where the cells of a tiled canvas live
+enum TileStorage<T> {
+    // tiles are allocated on their first non-zero write, unwritten tiles read as zero
+    // shared copies hold the same tiles, a shared tile is copied before it is written
+    Sparse(Vec<Option<Arc<[T]>>>),
+    // every tile in one file backed mapping, the operating system pages tiles in and out
+    #[cfg(not(target_arch = "wasm32"))]
+    Mapped(memmap2::MmapMut),
+}
+
+// a grid of cells stored as square tiles instead of one contiguous buffer
+// so huge canvases only hold the tiles that were painted,
+// and output and rendering can work through the canvas one tile at a time
+pub(crate) struct TiledCanvas<T: Pod> {
+    width: u32,
+    height: u32,
+    tiles_across: u32,
+    storage: TileStorage<T>,
+    // tiles written since the last call of take_dirty_tiles
+    dirty: Vec<bool>,
+}
+impl<T: Pod> TiledCanvas<T> {
+    // an all zero canvas held in memory
+    pub(crate) fn new(width: u32, height: u32) -> TiledCanvas<T> {
+        let tile_count: usize = tile_count(width, height);
+        TiledCanvas {
+            width,
+            height,
+            tiles_across: width.div_ceil(TILE_SIZE),
+            storage: TileStorage::Sparse((0..tile_count).map(|_| None).collect()),
+            dirty: vec![false; tile_count],
+        }
+    }
+
+    // an all zero canvas backed by a scratch file at the given path
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn mapped(width: u32, height: u32, path: &str) -> Result<TiledCanvas<T>, String> {
+        let tile_count: usize = tile_count(width, height);
+        let file: std::fs::File = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|error| format!("{}: {}", path, error))?;
+        file.set_len((tile_count * TILE_CELL_COUNT * std::mem::size_of::<T>()) as u64)
+            .map_err(|error| format!("{}: {}", path, error))?;
+        // the scratch file belongs to this run, nothing else writes to it while it is mapped
+        let mapping: memmap2::MmapMut = unsafe { memmap2::MmapMut::map_mut(&file) }
+            .map_err(|error| format!("{}: {}", path, error))?;
+        // unlink the scratch file right away,
+        // the mapping keeps its data until the canvas is dropped
+        // (platforms that refuse to unlink an open file leave it behind)
+        let _ = std::fs::remove_file(path);
+
+        return Ok(TiledCanvas {
+            width,
+            height,
+            tiles_across: width.div_ceil(TILE_SIZE),
+            storage: TileStorage::Mapped(mapping),
+            dirty: vec![false; tile_count],
+        });
+    }
+
+    // a canvas in memory, or mapped to "{directory}/{name}.tiles" when a directory is given
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn create(
+        width: u32,
+        height: u32,
+        mapped_directory: Option<&str>,
+        name: &str,
+    ) -> TiledCanvas<T> {
+        match mapped_directory {
+            None => TiledCanvas::new(width, height),
+            Some(directory) => {
+                TiledCanvas::mapped(width, height, &format!("{}/{}.tiles", directory, name))
+                    .unwrap_or_else(|error| {
+                        panic!("[ERROR] unable to map canvas storage: {}", error)
+                    })
+            }
+        }
+    }
+
+    pub(crate) fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub(crate) fn height(&self) -> u32 {
+        self.height
+    }
+
+    // tile index and the cell's index within that tile
+    fn locate(&self, x: u32, y: u32) -> (usize, usize) {
+        let tile_index: usize = ((y / TILE_SIZE) * self.tiles_across + x / TILE_SIZE) as usize;
+        let cell_index: usize = ((y % TILE_SIZE) * TILE_SIZE + x % TILE_SIZE) as usize;
+        return (tile_index, cell_index);
+    }
+
+    pub(crate) fn get(&self, x: u32, y: u32) -> T {
+        let (tile_index, cell_index) = self.locate(x, y);
+        match &self.storage {
+            TileStorage::Sparse(tiles) => tiles[tile_index]
+                .as_ref()
+                .map_or(T::zeroed(), |cells| cells[cell_index]),
+            #[cfg(not(target_arch = "wasm32"))]
+            TileStorage::Mapped(mapping) => {
+                bytemuck::cast_slice::<u8, T>(mapping)[tile_index * TILE_CELL_COUNT + cell_index]
+            }
+        }
+    }
+
+    pub(crate) fn put(&mut self, x: u32, y: u32, value: T) {
+        let (tile_index, cell_index) = self.locate(x, y);
+        self.dirty[tile_index] = true;
+        match &mut self.storage {
+            TileStorage::Sparse(tiles) => {
+                // zeros written to an unallocated tile change nothing
+                if tiles[tile_index].is_none()
+                    && bytemuck::bytes_of(&value).iter().all(|byte| *byte == 0u8)
+                {
+                    return;
+                }
+                let tile: &mut Arc<[T]> = tiles[tile_index]
+                    .get_or_insert_with(|| Arc::from(vec![T::zeroed(); TILE_CELL_COUNT]));
+                Arc::make_mut(tile)[cell_index] = value;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            TileStorage::Mapped(mapping) => {
+                bytemuck::cast_slice_mut::<u8, T>(mapping)
+                    [tile_index * TILE_CELL_COUNT + cell_index] = value;
+            }
+        }
+    }
+
+    // left, top, width and height of a tile, the last row and column of tiles may be cut off
+    pub(crate) fn tile_bounds(&self, tile_index: usize) -> (u32, u32, u32, u32) {
+        let left: u32 = (tile_index as u32 % self.tiles_across) * TILE_SIZE;
+        let top: u32 = (tile_index as u32 / self.tiles_across) * TILE_SIZE;
+        return (
+            left,
+            top,
+            TILE_SIZE.min(self.width - left),
+            TILE_SIZE.min(self.height - top),
+        );
+    }
+
+    // copy a tile's cells row by row into the buffer, which is cleared first
+    pub(crate) fn read_tile(&self, tile_index: usize, cells: &mut Vec<T>) {
+        let (left, top, width, height) = self.tile_bounds(tile_index);
+        cells.clear();
+        for y in top..top + height {
+            cells.extend((left..left + width).map(|x| self.get(x, y)));
+        }
+    }
+
+    // overwrite a tile with cells laid out like read_tile's
+    pub(crate) fn write_tile(&mut self, tile_index: usize, cells: &[T]) {
+        let (left, top, width, _) = self.tile_bounds(tile_index);
+        for (cell_index, cell) in cells.iter().enumerate() {
+            let x: u32 = left + cell_index as u32 % width;
+            let y: u32 = top + cell_index as u32 / width;
+            self.put(x, y, *cell);
+        }
+    }
+
+    // copy one row of the canvas into the buffer, which is cleared first
+    pub(crate) fn read_row(&self, y: u32, cells: &mut Vec<T>) {
+        cells.clear();
+        cells.extend((0..self.width).map(|x| self.get(x, y)));
+    }
+
+    // a copy shrunk to fit a square of the given edge length, sampling the nearest cell
+    // canvases that already fit are copied at their own size
+    pub(crate) fn downscaled(&self, size: u32) -> TiledCanvas<T> {
+        let scale: f64 = (size as f64 / self.width.max(self.height) as f64).min(1f64);
+        let width: u32 = ((self.width as f64 * scale).round() as u32).max(1u32);
+        let height: u32 = ((self.height as f64 * scale).round() as u32).max(1u32);
+        let mut scaled: TiledCanvas<T> = TiledCanvas::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                scaled.put(
+                    x,
+                    y,
+                    self.get(
+                        (x as u64 * self.width as u64 / width as u64) as u32,
+                        (y as u64 * self.height as u64 / height as u64) as u32,
+                    ),
+                );
+            }
+        }
+        return scaled;
+    }
+
+    // a copy that shares the tiles of a canvas in memory, so it costs no cells of its own
+    // until one side writes to a shared tile, which then gets its own copy of that tile
+    // mapped canvases keep every tile in one mapping and cannot share them
+    pub(crate) fn share(&self) -> Option<TiledCanvas<T>> {
+        match &self.storage {
+            TileStorage::Sparse(tiles) => Some(TiledCanvas {
+                width: self.width,
+                height: self.height,
+                tiles_across: self.tiles_across,
+                storage: TileStorage::Sparse(tiles.clone()),
+                dirty: vec![false; self.dirty.len()],
+            }),
+            #[cfg(not(target_arch = "wasm32"))]
+            TileStorage::Mapped(_) => None,
+        }
+    }
+
+    // the tiles written since the previous call
+    pub(crate) fn take_dirty_tiles(&mut self) -> Vec<usize> {
+        let dirty_tiles: Vec<usize> = (0..self.dirty.len())
+            .filter(|tile_index| self.dirty[*tile_index])
+            .collect();
+        self.dirty.iter_mut().for_each(|dirty| *dirty = false);
+        return dirty_tiles;
+    }
+
+    // have every tile reported by the next take_dirty_tiles, e.g. after the target was cleared
+    pub(crate) fn mark_all_dirty(&mut self) {
+        self.dirty.iter_mut().for_each(|dirty| *dirty = true);
+    }
+}
+
+fn tile_count(width: u32, height: u32) -> usize {
+    width.div_ceil(TILE_SIZE) as usize * height.div_ceil(TILE_SIZE) as usize
+}
@@ -0,0 +1,245 @@
+use proptest::prelude::*;
+use std::collections::HashSet;
+
+// the engine is a binary, so its sources are compiled as a module of the test
+// and its modules find the items they reach through the crate root via the glob import
+#[path = "../src/shredder.rs"]
+#[allow(dead_code)]
+mod shredder;
+
+use shredder::cell::Cell;
+use shredder::*;
+
+// generation mode and cell selection
+const GENERATION_MODES: [(&str, &str); 4] = [
+    ("color_first", "random"),
+    ("position_first", "random"),
+    ("position_first", "oldest"),
+    ("position_first", "most_neighbors"),
+];
+const SEARCH_MODES: [&str; 3] = ["exact", "sample", "stratified"];
+const SCHEDULING_POLICIES: [&str; 3] = ["round_robin", "proportional", "weighted"];
+
+// one randomly configured run
+#[derive(Clone, Debug)]
+struct RunCase {
+    width: u32,
+    height: u32,
+    seed: u64,
+    // the starting locations of every colony
+    colonies: Vec<Vec<(u32, u32)>>,
+    generation: (&'static str, &'static str),
+    search: &'static str,
+    scheduling: &'static str,
+}
+impl RunCase {
+    fn config(&self) -> String {
+        let mut config: String = format!(
+            concat!(
+                "config:\n",
+                "  seed: \"{}\"\n",
+                "  canvas:\n",
+                "    size:\n",
+                "      x: \"{}\"\n",
+                "      y: \"{}\"\n",
+                "  generation:\n",
+                "    mode: \"{}\"\n",
+                "    cell: \"{}\"\n",
+                // small samples, so the approximate searches also sample tiny canvases
+                "  search:\n",
+                "    mode: \"{}\"\n",
+                "    quality: \"0.5\"\n",
+                "    min_sample_size: \"1\"\n",
+                "  colonies:\n",
+                "    scheduling: \"{}\"\n",
+                "    groups:\n",
+            ),
+            self.seed,
+            self.width,
+            self.height,
+            self.generation.0,
+            self.generation.1,
+            self.search,
+            self.scheduling
+        );
+        for starting_locations in self.colonies.iter() {
+            config.push_str("      - starting_locations:\n");
+            for (x, y) in starting_locations.iter() {
+                config.push_str(&format!(
+                    "          - x: \"{}\"\n            y: \"{}\"\n",
+                    x, y
+                ));
+            }
+        }
+        return config;
+    }
+}
+
+// coordinates along one axis, the edges are drawn as often as the whole interior
+fn edge_biased(size: u32) -> impl Strategy<Value = u32> + Clone {
+    prop_oneof![Just(0u32), Just(size - 1), 0..size]
+}
+
+fn run_case() -> impl Strategy<Value = RunCase> {
+    (1u32..=24u32, 1u32..=24u32).prop_flat_map(|(width, height)| {
+        let location = (edge_biased(width), edge_biased(height));
+        (
+            any::<u64>(),
+            prop::collection::vec(prop::collection::vec(location, 1..=4), 1..=3),
+            prop::sample::select(GENERATION_MODES.to_vec()),
+            prop::sample::select(SEARCH_MODES.to_vec()),
+            prop::sample::select(SCHEDULING_POLICIES.to_vec()),
+        )
+            .prop_map(
+                move |(seed, colonies, generation, search, scheduling)| RunCase {
+                    width,
+                    height,
+                    seed,
+                    colonies,
+                    generation,
+                    search,
+                    scheduling,
+                },
+            )
+    })
+}
+
+fn has_painted_neighbor(painting: &Painting, position: &Coordinate) -> bool {
+    let constraints: &Constraints = &painting.canvas_constraints;
+    for y in position.y.saturating_sub(1)..=(position.y + 1).min(constraints.y_size - 1) {
+        for x in position.x.saturating_sub(1)..=(position.x + 1).min(constraints.x_size - 1) {
+            if (x, y) != (position.x, position.y) && painting.canvas.get(x, y).is_painted() {
+                return true;
+            }
+        }
+    }
+    return false;
+}
+
+// the invariants that hold between any two placements
+fn check_invariants(painting: &Painting) -> Result<(), TestCaseError> {
+    // every boundry cell is listed once, in one colony, and is an empty cell next to a painted one
+    let mut frontier: HashSet<(u32, u32)> = HashSet::new();
+    for colony in painting.colonies.iter() {
+        for position in colony.boundry_region_list.iter() {
+            prop_assert!(
+                frontier.insert((position.x, position.y)),
+                "{}, {} is listed twice",
+                position.x,
+                position.y
+            );
+            prop_assert!(
+                painting.canvas.get(position.x, position.y).is_frontier(),
+                "{}, {} is listed but not marked as frontier",
+                position.x,
+                position.y
+            );
+            prop_assert!(
+                has_painted_neighbor(painting, position),
+                "{}, {} has no painted neighbor",
+                position.x,
+                position.y
+            );
+        }
+    }
+
+    // no cell is marked as frontier without being listed,
+    // and no cell was painted twice
+    let mut painted_count: u64 = 0u64;
+    for y in 0..painting.canvas_constraints.y_size {
+        for x in 0..painting.canvas_constraints.x_size {
+            let cell: Cell = painting.canvas.get(x, y);
+            if cell.is_painted() {
+                painted_count += 1;
+            }
+            prop_assert!(
+                !cell.is_frontier() || frontier.contains(&(x, y)),
+                "{}, {} is marked as frontier but not listed",
+                x,
+                y
+            );
+        }
+    }
+    prop_assert_eq!(
+        painted_count,
+        painting.canvas_stats.current_pixels_placed_count
+    );
+    return Ok(());
+}
+
+// run to the end, checking the invariants after every placement
+fn check_run(config: &str) -> Result<(), TestCaseError> {
+    let mut painting: Painting = initialize_canvas(config, None);
+    let cell_count: u64 =
+        painting.canvas_constraints.x_size as u64 * painting.canvas_constraints.y_size as u64;
+    check_invariants(&painting)?;
+
+    // a run paints every cell once, so it can not take more placements than there are cells
+    let mut placement_count: u64 = painting.canvas_stats.current_pixels_placed_count;
+    while place_next_pixel(&mut painting) {
+        placement_count += 1;
+        prop_assert!(placement_count <= cell_count, "the run does not terminate");
+        check_invariants(&painting)?;
+    }
+
+    // every cell is reachable from any seed, so the finished canvas is fully painted
+    prop_assert_eq!(frontier_size(&painting), 0usize);
+    prop_assert_eq!(placement_count, cell_count);
+    return Ok(());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn runs_paint_every_cell_exactly_once(case in run_case()) {
+        check_run(&case.config())?;
+    }
+}
+
+#[test]
+fn seeds_at_edges_and_corners() {
+    for (width, height) in [
+        (1u32, 1u32),
+        (1u32, 7u32),
+        (7u32, 1u32),
+        (2u32, 2u32),
+        (9u32, 5u32),
+    ] {
+        let (right, bottom): (u32, u32) = (width - 1, height - 1);
+        let positions: [(u32, u32); 8] = [
+            (0u32, 0u32),
+            (right, 0u32),
+            (0u32, bottom),
+            (right, bottom),
+            (width / 2, 0u32),
+            (width / 2, bottom),
+            (0u32, height / 2),
+            (right, height / 2),
+        ];
+        for position in positions {
+            let case: RunCase = RunCase {
+                width,
+                height,
+                seed: 0u64,
+                colonies: vec![vec![position]],
+                generation: GENERATION_MODES[0],
+                search: SEARCH_MODES[0],
+                scheduling: SCHEDULING_POLICIES[0],
+            };
+            check_run(&case.config()).unwrap();
+        }
+
+        // every edge and corner seeded at once, by competing colonies
+        let case: RunCase = RunCase {
+            width,
+            height,
+            seed: 0u64,
+            colonies: positions.iter().map(|position| vec![*position]).collect(),
+            generation: GENERATION_MODES[0],
+            search: SEARCH_MODES[0],
+            scheduling: SCHEDULING_POLICIES[0],
+        };
+        check_run(&case.config()).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+// golden image regression tests
+// every case paints a small canvas with a fixed seed and compares its cells byte for byte
+// with the png stored in tests/golden, after an intentional change of behavior
+// the goldens are rewritten with
+//     GOLDEN_BLESS=1 cargo test --test golden
+use std::{fs, fs::File, io::BufWriter, path::PathBuf};
+
+// the engine is a binary, so its sources are compiled as a module of the test
+// and its modules find the items they reach through the crate root via the glob import
+#[path = "../src/shredder.rs"]
+#[allow(dead_code)]
+mod shredder;
+
+use shredder::cell::Cell;
+use shredder::*;
+
+const CANVAS_SIZE: u32 = 32u32;
+
+// a single seed in the middle of the canvas, the cases add their own sections
+fn base_config(neighborhood: &str) -> String {
+    return format!(
+        r#"config:
+  seed: "1"
+  canvas:
+    size:
+      x: "32"
+      y: "32"
+    neighborhood: "{}"
+    starting_locations:
+      - x: "16"
+        y: "16"
+"#,
+        neighborhood
+    );
+}
+
+// every case is painted with each neighborhood, suffix of the golden's name and config value
+const NEIGHBORHOODS: [(&str, &str); 2] = [("", "moore"), ("_von_neumann", "von_neumann")];
+
+// name of the golden and the config sections of the case
+const GOLDEN_CASES: [(&str, &str); 14] = [
+    ("default", ""),
+    // scoring metrics
+    (
+        "average_distance",
+        r#"  colonies:
+    groups:
+      - starting_locations:
+          - x: "16"
+            y: "16"
+        palette:
+          average_weight: "1"
+"#,
+    ),
+    (
+        "seed_distance",
+        r#"  scoring:
+    terms:
+      - name: "neighbor_color"
+      - name: "seed_distance"
+        weight: "100"
+"#,
+    ),
+    (
+        "frontier_age",
+        r#"  scoring:
+    terms:
+      - name: "neighbor_color"
+      - name: "frontier_age"
+        weight: "10"
+"#,
+    ),
+    (
+        "noise",
+        r#"  scoring:
+    terms:
+      - name: "neighbor_color"
+      - name: "noise"
+        weight: "1000"
+"#,
+    ),
+    // flow fields
+    (
+        "wind_flow",
+        r#"  flow:
+    type: "wind"
+    direction: "45"
+    weight: "1000"
+"#,
+    ),
+    (
+        "radial_flow",
+        r#"  flow:
+    type: "radial"
+    weight: "1000"
+"#,
+    ),
+    (
+        "noise_flow",
+        r#"  flow:
+    type: "noise"
+    scale: "8"
+    weight: "1000"
+"#,
+    ),
+    // color sources
+    (
+        "oklch_palette",
+        r#"  colonies:
+    groups:
+      - starting_locations:
+          - x: "16"
+            y: "16"
+        palette:
+          space: "oklch"
+"#,
+    ),
+    (
+        "normal_distribution",
+        r#"  colonies:
+    groups:
+      - starting_locations:
+          - x: "16"
+            y: "16"
+        palette:
+          value: "normal mean=0.5 std_dev=0.1"
+"#,
+    ),
+    (
+        "palette_keyframes",
+        r#"  colonies:
+    groups:
+      - starting_locations:
+          - x: "16"
+            y: "16"
+        palette:
+          hues:
+            - "0.0"
+            - "0.3"
+          keyframes:
+            - at: "50%"
+              hues:
+                - "0.6"
+"#,
+    ),
+    // generation and search
+    (
+        "position_first",
+        r#"  generation:
+    mode: "position_first"
+    cell: "oldest"
+"#,
+    ),
+    (
+        "stratified_search",
+        r#"  search:
+    mode: "stratified"
+    quality: "0.25"
+    min_sample_size: "8"
+"#,
+    ),
+    (
+        "competing_colonies",
+        r#"  colonies:
+    scheduling: "proportional"
+    groups:
+      - starting_locations:
+          - x: "0"
+            y: "0"
+        palette:
+          hues:
+            - "0.0"
+      - starting_locations:
+          - x: "31"
+            y: "31"
+        palette:
+          hues:
+            - "0.6"
+"#,
+    ),
+];
+
+// the finished canvas as rgba bytes, the cell state is the alpha channel
+fn render(config: &str) -> Vec<u8> {
+    let mut painting: Painting = initialize_canvas(config, None);
+    while place_next_pixel(&mut painting) {}
+
+    let mut pixels: Vec<u8> = Vec::new();
+    let mut row: Vec<Cell> = Vec::new();
+    for y in 0..painting.canvas.height() {
+        painting.canvas.read_row(y, &mut row);
+        pixels.extend_from_slice(bytemuck::cast_slice(&row));
+    }
+    return pixels;
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.png", name))
+}
+
+fn write_png(path: &PathBuf, pixels: &[u8]) {
+    let file: File =
+        File::create(path).unwrap_or_else(|error| panic!("[ERROR] {}: {}", path.display(), error));
+    let mut encoder = png::Encoder::new(BufWriter::new(file), CANVAS_SIZE, CANVAS_SIZE);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(pixels))
+        .unwrap_or_else(|error| panic!("[ERROR] {}: {}", path.display(), error));
+}
+
+// the golden's rgba bytes, None when it has not been blessed yet
+fn read_png(path: &PathBuf) -> Option<Vec<u8>> {
+    let file: File = File::open(path).ok()?;
+    let mut reader = png::Decoder::new(file)
+        .read_info()
+        .unwrap_or_else(|error| panic!("[ERROR] {}: {}", path.display(), error));
+    let mut pixels: Vec<u8> = vec![0u8; reader.output_buffer_size()];
+    let info: png::OutputInfo = reader
+        .next_frame(&mut pixels)
+        .unwrap_or_else(|error| panic!("[ERROR] {}: {}", path.display(), error));
+    pixels.truncate(info.buffer_size());
+    return Some(pixels);
+}
+
+#[test]
+fn paintings_match_goldens() {
+    let bless: bool = std::env::var_os("GOLDEN_BLESS").is_some();
+    let mut failures: Vec<String> = Vec::new();
+
+    for ((case_name, sections), (suffix, neighborhood)) in GOLDEN_CASES
+        .iter()
+        .flat_map(|case| NEIGHBORHOODS.iter().map(move |neighborhood| (case, neighborhood)))
+    {
+        let name: String = format!("{}{}", case_name, suffix);
+        let pixels: Vec<u8> = render(&format!("{}{}", base_config(neighborhood), sections));
+        let path: PathBuf = golden_path(&name);
+        if bless {
+            fs::create_dir_all(path.parent().unwrap())
+                .expect("[ERROR] unable to create the golden directory");
+            write_png(&path, &pixels);
+            continue;
+        }
+
+        match read_png(&path) {
+            None => failures.push(format!("{}: no golden, bless it first", name)),
+            Some(golden) if golden != pixels => {
+                // the actual painting is kept next to the build output for comparison
+                let actual_path: PathBuf =
+                    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+                write_png(&actual_path, &pixels);
+                let changed_count: usize = golden
+                    .chunks(4)
+                    .zip(pixels.chunks(4))
+                    .filter(|(golden, actual)| golden != actual)
+                    .count();
+                failures.push(format!(
+                    "{}: {} of {} cells differ, painted {}",
+                    name,
+                    changed_count,
+                    CANVAS_SIZE * CANVAS_SIZE,
+                    actual_path.display()
+                ));
+            }
+            Some(_) => {}
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "paintings differ from their goldens, rerun with GOLDEN_BLESS=1 if the change is intended:\n{}",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,175 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+// the parsers only need rand, so their source is compiled on its own
+#[path = "../src/palette_file.rs"]
+#[allow(dead_code)]
+mod palette_file;
+
+use palette_file::ColorPool;
+
+// an ASE file with a single block, built from its raw parts
+fn ase_file(block_count: u32, block_type: u16, block_length: u32, block: &[u8]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend_from_slice(b"ASEF");
+    bytes.extend_from_slice(&[0u8, 1u8, 0u8, 0u8]);
+    bytes.extend_from_slice(&block_count.to_be_bytes());
+    bytes.extend_from_slice(&block_type.to_be_bytes());
+    bytes.extend_from_slice(&block_length.to_be_bytes());
+    bytes.extend_from_slice(block);
+    return bytes;
+}
+
+// a color entry named "a" with the given model and channel values
+fn ase_color_block(model: &[u8; 4], values: &[f32]) -> Vec<u8> {
+    let mut block: Vec<u8> = Vec::new();
+    block.extend_from_slice(&2u16.to_be_bytes());
+    block.extend_from_slice(&[0u8, b'a', 0u8, 0u8]);
+    block.extend_from_slice(model);
+    for value in values {
+        block.extend_from_slice(&value.to_be_bytes());
+    }
+    // the color type, global, spot or normal
+    block.extend_from_slice(&2u16.to_be_bytes());
+    return block;
+}
+
+// every color a pool hands out without jitter
+fn sampled_colors(pool: &ColorPool) -> Vec<[u8; 3]> {
+    let mut rng: StdRng = StdRng::seed_from_u64(7u64);
+    let mut colors: Vec<[u8; 3]> = (0..200).map(|_| pool.sample(&mut rng, 0f32)).collect();
+    colors.sort();
+    colors.dedup();
+    return colors;
+}
+
+#[test]
+fn parses_gimp_palettes() {
+    let text: &str = "GIMP Palette\nName: test\nColumns: 2\n# comment\n255 0 0 red\n0 0 255\n";
+    let pool: ColorPool = ColorPool::parse(text.as_bytes()).unwrap();
+    assert_eq!(pool.len(), 2);
+    assert_eq!(
+        sampled_colors(&pool),
+        vec![[0u8, 0u8, 255u8], [255u8, 0u8, 0u8]]
+    );
+}
+
+#[test]
+fn parses_jasc_palettes() {
+    let text: &str = "JASC-PAL\n0100\n2\n10 20 30\n40 50 60\n";
+    let pool: ColorPool = ColorPool::parse(text.as_bytes()).unwrap();
+    assert_eq!(
+        sampled_colors(&pool),
+        vec![[10u8, 20u8, 30u8], [40u8, 50u8, 60u8]]
+    );
+
+    let missing_color: &str = "JASC-PAL\n0100\n3\n10 20 30\n40 50 60\n";
+    assert!(ColorPool::parse(missing_color.as_bytes()).is_err());
+}
+
+#[test]
+fn parses_hex_lists_and_merges_duplicates() {
+    let text: &str = "; comment\n#ff0000 2\n00ff00\n#FF0000 0.5\n";
+    let pool: ColorPool = ColorPool::parse(text.as_bytes()).unwrap();
+    assert_eq!(pool.len(), 2);
+    assert_eq!(
+        sampled_colors(&pool),
+        vec![[0u8, 255u8, 0u8], [255u8, 0u8, 0u8]]
+    );
+
+    assert!(ColorPool::parse(b"#ff00\n").is_err());
+    assert!(ColorPool::parse(b"#ff0000 heavy\n").is_err());
+}
+
+#[test]
+fn never_draws_colors_without_weight() {
+    let pool: ColorPool = ColorPool::parse(b"#ff0000 0\n#00ff00 1\n").unwrap();
+    assert_eq!(sampled_colors(&pool), vec![[0u8, 255u8, 0u8]]);
+}
+
+#[test]
+fn rejects_invalid_weights() {
+    for weight in ["-1", "NaN", "inf", "-inf"] {
+        let text: String = format!("#ff0000 {}\n#00ff00\n", weight);
+        assert!(
+            ColorPool::parse(text.as_bytes()).is_err(),
+            "accepted weight {}",
+            weight
+        );
+    }
+    // a negative weight must not hide behind a duplicate that makes the sum positive
+    assert!(ColorPool::parse(b"#ff0000 -1\n#ff0000 2\n").is_err());
+    assert!(ColorPool::parse(b"#ff0000 0\n#00ff00 0\n").is_err());
+    assert!(ColorPool::parse(b"#ff0000 3e38\n#00ff00 3e38\n").is_err());
+    assert!(ColorPool::parse(b"").is_err());
+}
+
+#[test]
+fn keeps_jittered_colors_in_range() {
+    let pool: ColorPool = ColorPool::parse(b"#ff0000\n").unwrap();
+    let mut rng: StdRng = StdRng::seed_from_u64(7u64);
+    for _ in 0..200 {
+        let color: [u8; 3] = pool.sample(&mut rng, 4f32);
+        assert!(color[0] >= 251u8);
+        assert!(color[1] <= 4u8 && color[2] <= 4u8);
+    }
+}
+
+#[test]
+fn parses_ase_color_models() {
+    let rgb: Vec<u8> = ase_color_block(b"RGB ", &[1f32, 0f32, 0.5f32]);
+    let pool: ColorPool =
+        ColorPool::parse(&ase_file(1u32, 0x0001, rgb.len() as u32, &rgb)).unwrap();
+    assert_eq!(sampled_colors(&pool), vec![[255u8, 0u8, 128u8]]);
+
+    let cmyk: Vec<u8> = ase_color_block(b"CMYK", &[0f32, 1f32, 1f32, 0f32]);
+    let pool: ColorPool =
+        ColorPool::parse(&ase_file(1u32, 0x0001, cmyk.len() as u32, &cmyk)).unwrap();
+    assert_eq!(sampled_colors(&pool), vec![[255u8, 0u8, 0u8]]);
+
+    let gray: Vec<u8> = ase_color_block(b"Gray", &[0f32]);
+    let pool: ColorPool =
+        ColorPool::parse(&ase_file(1u32, 0x0001, gray.len() as u32, &gray)).unwrap();
+    assert_eq!(sampled_colors(&pool), vec![[0u8, 0u8, 0u8]]);
+
+    let lab: Vec<u8> = ase_color_block(b"LAB ", &[0f32, 0f32, 0f32]);
+    assert!(ColorPool::parse(&ase_file(1u32, 0x0001, lab.len() as u32, &lab)).is_err());
+}
+
+#[test]
+fn rejects_malformed_ase_files() {
+    let rgb: Vec<u8> = ase_color_block(b"RGB ", &[1f32, 0f32, 0f32]);
+
+    // a block length pointing past the end, and one that overflows the offset
+    assert!(ColorPool::parse(&ase_file(1u32, 0x0001, rgb.len() as u32 + 1, &rgb)).is_err());
+    assert!(ColorPool::parse(&ase_file(1u32, 0x0001, u32::MAX, &rgb)).is_err());
+    // more blocks announced than the file holds
+    assert!(ColorPool::parse(&ase_file(2u32, 0x0001, rgb.len() as u32, &rgb)).is_err());
+    // a name longer than its block, and a file cut off inside the header
+    let mut long_name: Vec<u8> = rgb.clone();
+    long_name[0..2].copy_from_slice(&u16::MAX.to_be_bytes());
+    assert!(ColorPool::parse(&ase_file(1u32, 0x0001, rgb.len() as u32, &long_name)).is_err());
+    assert!(ColorPool::parse(b"ASEF\0\x01").is_err());
+    // a block too short for its values, even though the file goes on after it
+    let mut short_block: Vec<u8> = ase_file(2u32, 0x0001, rgb.len() as u32 - 6, &rgb);
+    short_block.extend_from_slice(&0xc002u16.to_be_bytes());
+    short_block.extend_from_slice(&0u32.to_be_bytes());
+    assert!(ColorPool::parse(&short_block).is_err());
+}
+
+#[test]
+fn round_trips_through_hex_lists() {
+    let pool: ColorPool = ColorPool::parse(b"#ff0000 0.1\n#00ff00 3\n#0000ff 1e-7\n").unwrap();
+    let copy: ColorPool = ColorPool::parse(pool.to_hex_list().as_bytes()).unwrap();
+    assert_eq!(copy.to_hex_list(), pool.to_hex_list());
+
+    // both pools draw the same colors from the same seed
+    let mut rng: StdRng = StdRng::seed_from_u64(7u64);
+    let mut copy_rng: StdRng = StdRng::seed_from_u64(7u64);
+    for _ in 0..200 {
+        assert_eq!(
+            pool.sample(&mut rng, 0f32),
+            copy.sample(&mut copy_rng, 0f32)
+        );
+    }
+}